@@ -1,22 +1,20 @@
 use std::{
+    collections::BTreeMap,
     error::Error,
     fs::File,
-    io::{BufReader, BufWriter},
-    path::PathBuf,
+    io::BufWriter,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
 };
 
-use anyhow::Context;
 use argh::FromArgs;
 use indicatif::{ProgressBar, ProgressStyle};
 use ray_tracer::{
-    camera::{Camera, PPMRenderWriter, RenderProgressTracker},
-    color::Color,
-    hittable::{HittableList, bvh::BVHNode, quad::Quad, sphere::Sphere},
-    material::{Dielectric, Lambertian, Metal},
-    scene_loader::SceneFile,
-    texture::{CheckerTexture, ImageTexture, NoiseTexture},
+    camera::{Camera, PPMRenderWriter, RenderProgressTracker, ShutterCurve},
+    denoise::Denoiser,
+    export, import,
+    scene_loader::{CameraSpec, SceneFile, SceneFormat},
+    scenes,
     vec::{Point3, Vec3},
 };
 
@@ -29,45 +27,69 @@ struct Args {
 
 #[derive(FromArgs)]
 #[argh(subcommand)]
+// Parsed once at startup, the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 enum SubCommand {
     Render(RenderSceneArgs),
     Dump(DumpSceneArgs),
+    Migrate(MigrateSceneArgs),
+    List(ListGeneratorsArgs),
+    Info(SceneInfoArgs),
+    Import(ImportSceneArgs),
+    Export(ExportSceneArgs),
 }
 
 #[derive(FromArgs)]
 /// camera/image options
 #[argh(subcommand, name = "render")]
 struct RenderSceneArgs {
-    #[argh(option, short = 'r', default = "16.0/9.0")]
-    /// aspect ratio
-    aspect_ratio: f64,
-    #[argh(option, short = 'w', default = "400")]
-    /// image width
-    image_width: i32,
-    #[argh(option, short = 's', default = "100")]
-    /// samples per pixel for antialiasing
-    samples_per_pixel: i32,
-    #[argh(option, short = 'd', default = "10")]
-    /// max number of ray bounces into scene
-    max_depth: i32,
-    #[argh(option, default = "90.0")]
-    /// vertical field of view
-    vfov: f64,
-    #[argh(option, default = "Point3::ZERO")]
-    /// point camera is looking from
-    lookfrom: Point3,
-    #[argh(option, default = "Point3::new(0.0, 0.0, -1.0)")]
-    /// point camera is looking at
-    lookat: Point3,
-    #[argh(option, default = "Vec3::new(0.0, 1.0, 0.0)")]
-    /// camera relative up direction
-    vup: Vec3,
+    #[argh(option, short = 'r')]
+    /// aspect ratio, defaults to the scene's camera or 16/9
+    aspect_ratio: Option<f64>,
+    #[argh(option, short = 'w')]
+    /// image width, defaults to the scene's camera or 400
+    image_width: Option<i32>,
+    #[argh(option, short = 's')]
+    /// samples per pixel for antialiasing, defaults to the scene's camera or
+    /// 100
+    samples_per_pixel: Option<i32>,
+    #[argh(option, short = 'd')]
+    /// max number of ray bounces into scene, defaults to the scene's camera
+    /// or 10
+    max_depth: Option<i32>,
+    #[argh(option)]
+    /// vertical field of view, defaults to the scene's camera or 90
+    vfov: Option<f64>,
+    #[argh(option)]
+    /// point camera is looking from, defaults to the scene's camera or the
+    /// origin
+    lookfrom: Option<Point3>,
+    #[argh(option)]
+    /// point camera is looking at, defaults to the scene's camera or 0,0,-1
+    lookat: Option<Point3>,
+    #[argh(option)]
+    /// camera relative up direction, defaults to the scene's camera or 0,1,0
+    vup: Option<Vec3>,
+    #[argh(option)]
+    /// variation angle of rays through each pixel, defaults to the scene's
+    /// camera or 0
+    defocus_angle: Option<f64>,
+    #[argh(option)]
+    /// distance from camera lookfrom point to plane of perfect focus,
+    /// defaults to the scene's camera or 10
+    focus_dist: Option<f64>,
+    #[argh(switch)]
+    /// denoise the image after rendering
+    denoise: bool,
     #[argh(option, default = "0.0")]
-    /// variation angle of rays through each pixel
-    defocus_angle: f64,
-    #[argh(option, default = "10.0")]
-    /// distance from camera lookfrom point to plane of perfect focus
-    focus_dist: f64,
+    /// time the shutter opens
+    shutter_open: f64,
+    #[argh(option, default = "1.0")]
+    /// time the shutter closes
+    shutter_close: f64,
+    #[argh(option, default = "ShutterCurve::Box")]
+    /// exposure over the shutter interval, box or triangle
+    shutter_curve: ShutterCurve,
     #[argh(
         option,
         short = 'o',
@@ -75,18 +97,91 @@ struct RenderSceneArgs {
     )]
     /// output file
     output_path: PathBuf,
+    #[argh(option, short = 'I')]
+    /// extra directory to look for textures and other assets in, can be
+    /// repeated. Assets are looked up next to the scene file first
+    search_path: Vec<PathBuf>,
     #[argh(positional)]
-    /// the scene file to render
+    /// the scene file to render, in json, toml, yaml or ron
     scene_path: PathBuf,
 }
 
 #[derive(FromArgs)]
-/// dump a hard coded scene
+/// dump a generated scene
 #[argh(subcommand, name = "dump")]
 struct DumpSceneArgs {
     #[argh(positional)]
-    /// scene id to dump
+    /// name of the scene generator, see `list`
     scene: String,
+    #[argh(option, short = 'p')]
+    /// generator parameter as name=value, can be repeated
+    param: Vec<String>,
+    #[argh(option, short = 'o')]
+    /// output file, its extension picks json, toml, yaml or ron. Asset paths
+    /// are written relative to it. Writes json to stdout when omitted
+    output_path: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+/// upgrade a scene file to the latest format version in place
+#[argh(subcommand, name = "migrate")]
+struct MigrateSceneArgs {
+    #[argh(positional)]
+    /// the scene file to migrate
+    scene_path: PathBuf,
+}
+
+#[derive(FromArgs)]
+/// list the scene generators and their parameters
+#[argh(subcommand, name = "list")]
+struct ListGeneratorsArgs {}
+
+#[derive(FromArgs)]
+/// summarize what a scene file contains
+#[argh(subcommand, name = "info")]
+struct SceneInfoArgs {
+    #[argh(option, short = 'I')]
+    /// extra directory to look for textures and other assets in, can be
+    /// repeated
+    search_path: Vec<PathBuf>,
+    #[argh(positional)]
+    /// the scene file to inspect
+    scene_path: PathBuf,
+}
+
+#[derive(FromArgs)]
+/// convert a pbrt-v3/v4 (.pbrt) or glTF 2.0 (.gltf, .glb) scene to a scene
+/// file. Textures embedded in glTF files are extracted next to it
+#[argh(subcommand, name = "import")]
+struct ImportSceneArgs {
+    #[argh(positional)]
+    /// the scene to convert
+    input_path: PathBuf,
+    #[argh(option, short = 'o')]
+    /// output file, its extension picks json, toml, yaml or ron. Asset paths
+    /// are written relative to it. Writes json to stdout when omitted
+    output_path: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+/// convert a scene file to meshes for other tools, as wavefront obj with an
+/// mtl file next to it or glTF 2.0 (.gltf, .glb). Shapes are tessellated and
+/// materials approximated
+#[argh(subcommand, name = "export")]
+struct ExportSceneArgs {
+    #[argh(positional)]
+    /// the scene file to convert
+    scene_path: PathBuf,
+    #[argh(option, short = 'o')]
+    /// output file, its extension picks obj, gltf or glb
+    output_path: PathBuf,
+    #[argh(option, default = "32")]
+    /// steps around curved shapes such as spheres
+    segments: usize,
+    #[argh(option, short = 'I')]
+    /// extra directory to look for textures and other assets in, can be
+    /// repeated
+    search_path: Vec<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -94,24 +189,33 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     match args.command {
         SubCommand::Render(args) => {
-            let file = File::open(args.scene_path)?;
-            let reader = BufReader::new(file);
-            let scene: SceneFile =
-                serde_json::from_reader(reader).context("Failed to load scene file")?;
-
+            let scene = SceneFile::load_resolved(&args.scene_path, &args.search_path)?;
+            let scene_camera = scene.camera().cloned().unwrap_or_default();
             let world = scene.into_list()?;
 
-            let camera = Camera::builder()
-                .image_width(args.image_width)
-                .aspect_ratio(args.aspect_ratio)
-                .samples_per_pixel(args.samples_per_pixel)
-                .max_depth(args.max_depth)
-                .vfov(args.vfov)
-                .lookfrom(args.lookfrom)
-                .lookat(args.lookat)
-                .vup(args.vup)
-                .defocus_angle(args.defocus_angle)
-                .focus_dist(args.focus_dist)
+            // Options given on the command line override the scene's camera
+            let overrides = CameraSpec {
+                aspect_ratio: args.aspect_ratio,
+                image_width: args.image_width,
+                samples_per_pixel: args.samples_per_pixel,
+                max_depth: args.max_depth,
+                vfov: args.vfov,
+                lookfrom: args.lookfrom,
+                lookat: args.lookat,
+                vup: args.vup,
+                defocus_angle: args.defocus_angle,
+                focus_dist: args.focus_dist,
+            };
+            let builder = Camera::builder()
+                .aspect_ratio(16.0 / 9.0)
+                .image_width(400)
+                .samples_per_pixel(100);
+            let camera = overrides
+                .apply(scene_camera.apply(builder))
+                .denoiser(args.denoise.then(Denoiser::default))
+                .shutter_open(args.shutter_open)
+                .shutter_close(args.shutter_close)
+                .shutter_curve(args.shutter_curve)
                 .build();
 
             let output = File::create(args.output_path)?;
@@ -124,223 +228,151 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .unwrap()
             );
 
-            let mut pb = IndicatifProgressTracker(pb);
+            let pb = IndicatifProgressTracker(pb);
 
-            camera.render(&world, &mut writer, &mut pb).unwrap();
+            camera.render(&world, &mut writer, &pb).unwrap();
 
             pb.0.finish_with_message("Rendering complete");
         }
         SubCommand::Dump(args) => {
-            let world = match args.scene.as_str() {
-                "cover" => Ok(book_cover()),
-                "checkered_spheres" => Ok(checkered_spheres()),
-                "earth" => earth(),
-                "perlin_spheres" => Ok(perlin_spheres()),
-                "quads" => quads(),
-                _ => Err(anyhow::anyhow!("invalid scene id: '{}'", args.scene)),
-            }?;
+            let mut generator = scenes::generator(&args.scene)
+                .ok_or_else(|| anyhow::anyhow!("invalid scene id: '{}'", args.scene))?;
+            for param in &args.param {
+                let (name, value) = param
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("expected name=value, got '{param}'"))?;
+                generator.set_param(name, value)?;
+            }
 
-            let scene: SceneFile = world.into();
+            let world = generator.generate()?;
 
-            let stdout = std::io::stdout();
-            let writer = BufWriter::new(stdout.lock());
+            let mut scene: SceneFile = world.into();
 
-            serde_json::to_writer_pretty(writer, &scene)?;
+            match args.output_path {
+                Some(path) => {
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    scene.relativize_asset_paths(dir)?;
+                    scene.save(&path)?;
+                }
+                None => println!("{}", scene.serialize(SceneFormat::Json)?),
+            }
         }
-    }
-
-    Ok(())
-}
+        SubCommand::Migrate(args) => {
+            let mut scene = SceneFile::load(&args.scene_path)?;
 
-struct IndicatifProgressTracker(ProgressBar);
-
-impl RenderProgressTracker for IndicatifProgressTracker {
-    fn init(&self, total: usize) {
-        self.0.set_length(total as u64);
-    }
+            let version = scene.version();
+            if !scene.migrate()? {
+                eprintln!("{:?} is already at version {version}", args.scene_path);
+                return Ok(());
+            }
 
-    fn tick(&self, _current: usize) {
-        self.0.inc(1);
-    }
-}
+            scene.save(&args.scene_path)?;
 
-fn book_cover() -> HittableList {
-    let mut world = HittableList::default();
-    let checker = Arc::new(CheckerTexture::from_color(
-        "checker",
-        0.32,
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-
-    let ground_material = Arc::new(Lambertian::from_texture(checker));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f64 = rand::random();
-            let center = Point3::new(
-                a as f64 + 0.9 * rand::random::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
+            eprintln!(
+                "migrated {:?} from version {version} to {}",
+                args.scene_path,
+                scene.version()
             );
-
-            if (&center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let name = format!("diffuse_{a}_{b}");
-                    let albedo = Color::random() * Color::random();
-                    let sphere_material = Arc::new(Lambertian::new(name, albedo));
-                    let center_2 = &center + Vec3::new(0.0, rand::random_range(0.0..0.5), 0.0);
-                    world.add(Arc::new(Sphere::new_moving(
-                        center.clone(),
-                        center_2,
-                        0.2,
-                        sphere_material,
-                    )));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let name = format!("metal_{a}_{b}");
-                    let albedo = Color::random_bounded(0.5, 1.0);
-                    let fuzz = rand::random_range(0.0..0.5);
-                    let sphere_material = Arc::new(Metal::new(name, albedo, fuzz));
-                    world.add(Arc::new(Sphere::new(center.clone(), 0.2, sphere_material)));
-                } else {
-                    // glass
-                    let name = format!("glass_{a}_{b}");
-                    let sphere_material = Arc::new(Dielectric::new(name, 1.5));
-                    world.add(Arc::new(Sphere::new(center.clone(), 0.2, sphere_material)));
+        }
+        SubCommand::List(_) => {
+            for generator in scenes::generators() {
+                println!("{}: {}", generator.name(), generator.description());
+                for param in generator.params() {
+                    println!(
+                        "    {} = {}: {}",
+                        param.name, param.value, param.description
+                    );
                 }
             }
         }
+        SubCommand::Info(args) => print_scene_info(args)?,
+        SubCommand::Import(args) => {
+            let imported = import::read(&args.input_path)?;
+            for warning in &imported.warnings {
+                eprintln!("warning: {warning}");
+            }
+            imported.write_assets()?;
+
+            let mut scene = imported.scene;
+            match args.output_path {
+                Some(path) => {
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    scene.relativize_asset_paths(dir)?;
+                    scene.save(&path)?;
+                }
+                None => println!("{}", scene.serialize(SceneFormat::Json)?),
+            }
+        }
+        SubCommand::Export(args) => {
+            let scene = SceneFile::load_resolved(&args.scene_path, &args.search_path)?;
+            let tessellation = export::tessellate(&scene, args.segments)?;
+            for warning in &tessellation.warnings {
+                eprintln!("warning: {warning}");
+            }
+            tessellation.write(&args.output_path)?;
+        }
     }
 
-    let material_1 = Arc::new(Dielectric::new("material_1", 1.5));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material_1,
-    )));
-
-    let material_2 = Arc::new(Lambertian::new("material_2", Color::new(0.4, 0.2, 0.1)));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material_2,
-    )));
-
-    let material_3 = Arc::new(Metal::new("material_3", Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material_3,
-    )));
-
-    let mut bvh_world = HittableList::default();
-    bvh_world.add(Arc::new(BVHNode::new(world)));
-    bvh_world
-}
-
-fn checkered_spheres() -> HittableList {
-    let mut world = HittableList::default();
-
-    let checker = Arc::new(CheckerTexture::from_color(
-        "checker",
-        0.32,
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, -10.0, 0.0),
-        10.0,
-        Arc::new(Lambertian::from_texture(checker.clone())),
-    )));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, 10.0, 0.0),
-        10.0,
-        Arc::new(Lambertian::from_texture(checker.clone())),
-    )));
-
-    world
+    Ok(())
 }
 
-fn earth() -> anyhow::Result<HittableList> {
-    let earth_texture = Arc::new(ImageTexture::new("textures/earthmap.jpg")?);
-    let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
-    let globe = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, earth_surface));
+fn print_scene_info(args: SceneInfoArgs) -> anyhow::Result<()> {
+    let scene = SceneFile::load_resolved(&args.scene_path, &args.search_path)?;
+    let unused: Vec<String> = scene
+        .unused_materials()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+    let world = scene.into_list()?;
+    let stats = world.stats();
+
+    let print_counts = |title: &str, counts: &BTreeMap<&str, usize>| {
+        println!("{title}: {}", counts.values().sum::<usize>());
+        for (kind, count) in counts {
+            println!("    {kind}: {count}");
+        }
+    };
+
+    println!("scene: {}", args.scene_path.display());
+    let b = &stats.bounds;
+    println!(
+        "bounds: ({}, {}, {}) to ({}, {}, {})",
+        b.x.min, b.y.min, b.z.min, b.x.max, b.y.max, b.z.max
+    );
+    print_counts("shapes", &stats.shapes);
+    println!("bvh: {} nodes, depth {}", stats.bvh_nodes, stats.bvh_depth);
+    print_counts("materials", &stats.materials);
+    print_counts("textures", &stats.textures);
+    println!(
+        "texture memory: {:.1} MiB",
+        stats.texture_bytes as f64 / (1024.0 * 1024.0)
+    );
+    for (path, error) in &stats.unreadable_images {
+        println!(
+            "unreadable image, not counted: {} ({error})",
+            path.display()
+        );
+    }
 
-    let mut world = HittableList::default();
-    world.add(globe);
+    if !unused.is_empty() {
+        println!("unused materials: {}", unused.join(", "));
+    }
+    for group in &stats.duplicate_materials {
+        println!("duplicate materials: {}", group.join(" = "));
+    }
 
-    Ok(world)
+    Ok(())
 }
 
-fn perlin_spheres() -> HittableList {
-    let pertext = Arc::new(NoiseTexture::new(4.0));
-    let pertext_mat = Arc::new(Lambertian::from_texture(pertext));
-    let mut world = HittableList::default();
-
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        pertext_mat.clone(),
-    )));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0.0, 2.0, 0.0),
-        2.0,
-        pertext_mat.clone(),
-    )));
-
-    world
-}
+struct IndicatifProgressTracker(ProgressBar);
 
-fn quads() -> anyhow::Result<HittableList> {
-    let mut world = HittableList::default();
-
-    let earth_texture = Arc::new(ImageTexture::new("textures/earthmap.jpg")?);
-    let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
-
-    let left_red = Arc::new(Lambertian::new("left_red", Color::new(1.0, 0.2, 0.2)));
-    let back_green = Arc::new(Lambertian::new("back_green", Color::new(0.2, 1.0, 0.2)));
-    let right_blue = Arc::new(Lambertian::new("right_blue", Color::new(0.2, 0.2, 1.0)));
-    let upper_orange = Arc::new(Lambertian::new("upper_orange", Color::new(1.0, 0.5, 0.0)));
-    let lower_teal = Arc::new(Lambertian::new("lower_teal", Color::new(0.2, 0.8, 0.8)));
-
-    world.add(Arc::new(Quad::new(
-        Point3::new(-3.0, -2.0, 5.0),
-        Vec3::new(0.0, 0.0, -4.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        left_red.clone(),
-    )));
-    world.add(Arc::new(Quad::new(
-        Point3::new(-2.0, -2.0, 0.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        earth_surface.clone(),
-    )));
-    world.add(Arc::new(Quad::new(
-        Point3::new(3.0, -2.0, 1.0),
-        Vec3::new(0.0, 0.0, 4.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        right_blue.clone(),
-    )));
-    world.add(Arc::new(Quad::new(
-        Point3::new(-2.0, 3.0, 1.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 0.0, 4.0),
-        upper_orange.clone(),
-    )));
-    world.add(Arc::new(Quad::new(
-        Point3::new(-2.0, -3.0, 5.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 0.0, -4.0),
-        lower_teal.clone(),
-    )));
-
-    Ok(world)
+impl RenderProgressTracker for IndicatifProgressTracker {
+    fn init(&self, total: usize) {
+        self.0.set_length(total as u64);
+    }
+
+    fn tick(&self, _current: usize) {
+        self.0.inc(1);
+    }
 }
@@ -1,6 +1,5 @@
 use std::{
-    fs::File,
-    io::BufReader,
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
@@ -11,7 +10,8 @@ use std::{
 use eframe::egui::{self, ImageSource};
 use log::error;
 use ray_tracer::{
-    camera::{Camera, PPMRenderWriter, RenderProgressTracker},
+    camera::{Camera, PPMRenderWriter, RenderProgressTracker, ShutterCurve},
+    denoise::Denoiser,
     hittable::HittableList,
     scene_loader::SceneFile,
     vec::{Point3, Vec3},
@@ -53,11 +53,16 @@ struct JobRequest {
 
 struct JobResult {
     id: JobId,
-    image: Arc<[u8]>,
+    /// The rendered image, or why the scene couldn't be loaded
+    image: Result<Arc<[u8]>, String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 struct RenderJob {
+    /// Scene file in json, toml, yaml or ron
+    scene_path: PathBuf,
+    /// Bumped to read the scene file again after it changed on disk
+    scene_version: u64,
     aspect_ratio: f64,
     image_width: i32,
     samples_per_pixel: i32,
@@ -68,11 +73,17 @@ struct RenderJob {
     vup: Vec3,
     defocus_angle: f64,
     focus_dist: f64,
+    denoise: bool,
+    shutter_open: f64,
+    shutter_close: f64,
+    shutter_curve: ShutterCurve,
 }
 
 impl Default for RenderJob {
     fn default() -> Self {
         Self {
+            scene_path: PathBuf::from(DEFAULT_SCENE),
+            scene_version: 0,
             aspect_ratio: 16.0 / 9.0,
             image_width: 400,
             samples_per_pixel: 50,
@@ -83,6 +94,10 @@ impl Default for RenderJob {
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            denoise: false,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            shutter_curve: ShutterCurve::Box,
         }
     }
 }
@@ -119,6 +134,9 @@ impl RenderProgressTracker for RenderProgressState {
 }
 
 struct RtiowApp {
+    /// Scene path being typed, loaded once submitted
+    scene_input: String,
+    scene_error: Option<String>,
     job_params: RenderJob,
     last_sent_params: RenderJob,
     render_progress: Option<Arc<RenderProgressState>>,
@@ -131,18 +149,18 @@ struct RtiowApp {
 }
 
 const IMAGE_URI: &str = "bytes://rendered.ppm";
+const DEFAULT_SCENE: &str = "scenes/cover.json";
 
 impl RtiowApp {
     pub fn new() -> Self {
         let (job_tx, job_rx) = channel::<(JobRequest, Arc<RenderProgressState>)>();
         let (result_tx, result_rx) = channel::<JobResult>();
 
-        let file = File::open("scenes/cover.json").unwrap();
-        let reader = BufReader::new(file);
-        let scene: SceneFile = serde_json::from_reader(reader).unwrap();
-        let world = scene.into_list().unwrap();
-
         std::thread::spawn(move || {
+            // Scene, or the error loading it, for the path and version it
+            // was read for
+            let mut scene: Option<((PathBuf, u64), Result<HittableList, String>)> = None;
+
             while let Ok(mut job) = job_rx.recv() {
                 // Drain any queued jobs so we only render latest
                 while let Ok(next) = job_rx.try_recv() {
@@ -150,7 +168,22 @@ impl RtiowApp {
                 }
 
                 let (request, progress) = job;
-                let image = render_scene(&request.params, &world, progress);
+                let key = (
+                    request.params.scene_path.clone(),
+                    request.params.scene_version,
+                );
+                if scene.as_ref().is_some_and(|(loaded, _)| *loaded != key) {
+                    scene = None;
+                }
+                let (_, world) = scene.get_or_insert_with(|| {
+                    let world = load_scene(&key.0).map_err(|e| format!("{e:#}"));
+                    (key.clone(), world)
+                });
+
+                let image = match world {
+                    Ok(world) => Ok(render_scene(&request.params, world, progress)),
+                    Err(e) => Err(e.clone()),
+                };
 
                 if let Err(e) = result_tx.send(JobResult {
                     id: request.id,
@@ -162,6 +195,8 @@ impl RtiowApp {
         });
 
         Self {
+            scene_input: DEFAULT_SCENE.to_owned(),
+            scene_error: None,
             job_params: RenderJob::default(),
             last_sent_params: RenderJob::default(),
             render_progress: None,
@@ -180,6 +215,21 @@ impl RtiowApp {
 
         ui.spacing_mut().item_spacing = egui::vec2(10.0, 15.0);
 
+        ui.horizontal(|ui| {
+            let label = ui.label("scene");
+            let input = ui
+                .text_edit_singleline(&mut self.scene_input)
+                .labelled_by(label.id);
+            let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("load").clicked() || submitted {
+                self.job_params.scene_path = PathBuf::from(self.scene_input.trim());
+                self.job_params.scene_version += 1;
+            }
+        });
+        if let Some(error) = &self.scene_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
         ui.horizontal(|ui| {
             let label = ui.label("aspect ratio");
             ui.add(
@@ -249,6 +299,36 @@ impl RtiowApp {
             .labelled_by(label.id);
         });
 
+        ui.checkbox(&mut self.job_params.denoise, "denoise");
+
+        ui.horizontal(|ui| {
+            let label = ui.label("shutter");
+            ui.add(
+                egui::DragValue::new(&mut self.job_params.shutter_open)
+                    .speed(0.01)
+                    .range(0.0..=self.job_params.shutter_close),
+            )
+            .labelled_by(label.id);
+            ui.add(
+                egui::DragValue::new(&mut self.job_params.shutter_close)
+                    .speed(0.01)
+                    .range(self.job_params.shutter_open..=1.0),
+            )
+            .labelled_by(label.id);
+
+            egui::ComboBox::from_id_salt("shutter curve")
+                .selected_text(format!("{:?}", self.job_params.shutter_curve))
+                .show_ui(ui, |ui| {
+                    for curve in [ShutterCurve::Box, ShutterCurve::Triangle] {
+                        ui.selectable_value(
+                            &mut self.job_params.shutter_curve,
+                            curve,
+                            format!("{curve:?}"),
+                        );
+                    }
+                });
+        });
+
         ui.separator();
 
         if let Some(progress) = &self.render_progress {
@@ -272,8 +352,14 @@ impl eframe::App for RtiowApp {
             if Some(result.id) >= self.newest_requested_job {
                 self.render_progress = None;
 
-                ctx.forget_image(IMAGE_URI);
-                self.image_bytes = Some(result.image);
+                match result.image {
+                    Ok(image) => {
+                        ctx.forget_image(IMAGE_URI);
+                        self.image_bytes = Some(image);
+                        self.scene_error = None;
+                    }
+                    Err(e) => self.scene_error = Some(e),
+                }
                 self.newest_finished_job = Some(result.id);
                 ctx.request_repaint();
             }
@@ -319,6 +405,11 @@ impl eframe::App for RtiowApp {
     }
 }
 
+/// Scene file with its includes, in any format [`SceneFile`] reads
+fn load_scene(path: &Path) -> anyhow::Result<HittableList> {
+    SceneFile::load_resolved(path, &[]).and_then(SceneFile::into_list)
+}
+
 fn render_scene(
     params: &RenderJob,
     world: &HittableList,
@@ -335,6 +426,10 @@ fn render_scene(
         .vup(params.vup.clone())
         .defocus_angle(params.defocus_angle)
         .focus_dist(params.focus_dist)
+        .denoiser(params.denoise.then(Denoiser::default))
+        .shutter_open(params.shutter_open)
+        .shutter_close(params.shutter_close)
+        .shutter_curve(params.shutter_curve)
         .build();
 
     let out: Vec<u8> = Vec::new();
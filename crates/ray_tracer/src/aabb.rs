@@ -4,7 +4,7 @@ use crate::{
     vec::{Axis, Point3},
 };
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct AABB {
     pub x: Interval,
     pub y: Interval,
@@ -53,7 +53,12 @@ impl AABB {
         }
     }
 
-    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.hit_range(r, ray_t).is_some()
+    }
+
+    /// The part of `ray_t` where the ray is inside the box, if any
+    pub fn hit_range(&self, r: &Ray, mut ray_t: Interval) -> Option<Interval> {
         let ray_orig = r.origin();
         let ray_dir = r.direction();
 
@@ -81,11 +86,18 @@ impl AABB {
             }
 
             if ray_t.max <= ray_t.min {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some(ray_t)
+    }
+
+    /// False if the box extends to infinity along any axis
+    pub fn is_bounded(&self) -> bool {
+        [&self.x, &self.y, &self.z]
+            .iter()
+            .all(|i| i.min.is_finite() && i.max.is_finite())
     }
 
     pub fn longest_axis(&self) -> Axis {
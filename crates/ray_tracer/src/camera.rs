@@ -1,16 +1,49 @@
-use std::io::Write;
+use std::{io::Write, str::FromStr};
 
 use rand::Rng;
 
 use crate::{
     color::Color,
     degrees_to_radians,
-    hittable::{Hittable, HittableList},
+    denoise::Denoiser,
+    hittable::{HitRecord, Hittable, HittableList},
     interval::Interval,
     ray::Ray,
     vec::{Point3, Vec3},
 };
 
+/// How exposure is spread over the time the shutter is open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShutterCurve {
+    /// Fully open for the whole interval
+    #[default]
+    Box,
+    /// Opens and closes linearly, fully open only half way through
+    Triangle,
+}
+
+impl ShutterCurve {
+    /// Sample a point in 0..1 distributed by the curve
+    fn sample(&self) -> f64 {
+        match self {
+            Self::Box => rand::random(),
+            Self::Triangle => 0.5 * (rand::random::<f64>() + rand::random::<f64>()),
+        }
+    }
+}
+
+impl FromStr for ShutterCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "box" => Ok(Self::Box),
+            "triangle" => Ok(Self::Triangle),
+            _ => anyhow::bail!("unknown shutter curve '{s}', expected box or triangle"),
+        }
+    }
+}
+
 pub struct CameraBuilder {
     aspect_ratio: f64,
     image_width: i32,
@@ -30,6 +63,14 @@ pub struct CameraBuilder {
     defocus_angle: f64,
     /// distance from camera lookfrom point to plane of perfect focus
     focus_dist: f64,
+    /// optional filter applied to the linear image after rendering
+    denoiser: Option<Denoiser>,
+    /// time the shutter opens, scene motion is described over 0 to 1
+    shutter_open: f64,
+    /// time the shutter closes
+    shutter_close: f64,
+    /// distribution of ray times between shutter open and close
+    shutter_curve: ShutterCurve,
 }
 
 impl Default for CameraBuilder {
@@ -45,6 +86,10 @@ impl Default for CameraBuilder {
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            denoiser: None,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            shutter_curve: ShutterCurve::Box,
         }
     }
 }
@@ -100,6 +145,26 @@ impl CameraBuilder {
         self
     }
 
+    pub fn denoiser(mut self, denoiser: Option<Denoiser>) -> Self {
+        self.denoiser = denoiser;
+        self
+    }
+
+    pub fn shutter_open(mut self, shutter_open: f64) -> Self {
+        self.shutter_open = shutter_open;
+        self
+    }
+
+    pub fn shutter_close(mut self, shutter_close: f64) -> Self {
+        self.shutter_close = shutter_close;
+        self
+    }
+
+    pub fn shutter_curve(mut self, shutter_curve: ShutterCurve) -> Self {
+        self.shutter_curve = shutter_curve;
+        self
+    }
+
     pub fn build(self) -> Camera {
         // Calculate image height, bounded below by 1
         let image_height = ((self.image_width as f64 / self.aspect_ratio) as i32).max(1);
@@ -148,6 +213,10 @@ impl CameraBuilder {
             defocus_angle: self.defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            denoiser: self.denoiser,
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
+            shutter_curve: self.shutter_curve,
         }
     }
 }
@@ -167,6 +236,10 @@ pub struct Camera {
     defocus_disk_u: Vec3,
     /// defocus disk vertical radius
     defocus_disk_v: Vec3,
+    denoiser: Option<Denoiser>,
+    shutter_open: f64,
+    shutter_close: f64,
+    shutter_curve: ShutterCurve,
 }
 
 impl Camera {
@@ -188,17 +261,26 @@ impl Camera {
 
         progress.init(self.image_height as usize);
 
-        let pixels: Vec<_> = (0..self.image_height)
+        let samples: Vec<_> = (0..self.image_height)
             .into_par_iter()
             .flat_map_iter(|j| {
                 let row = (0..self.image_width).map(move |i| {
                     let mut pixel_color = Color::ZERO;
+                    let mut albedo = Color::ZERO;
+                    let mut normal = Vec3::ZERO;
                     for _sample in 0..self.samples_per_pixel {
                         let r = self.get_ray(i, j);
-                        pixel_color += &self.ray_color(&r, self.max_depth, world);
+                        let (c, a, n) = self.trace(&r, world);
+                        pixel_color += &c;
+                        albedo += &a;
+                        normal += &n;
                     }
 
-                    pixel_color * self.pixel_samples_scale
+                    (
+                        pixel_color * self.pixel_samples_scale,
+                        albedo * self.pixel_samples_scale,
+                        normal * self.pixel_samples_scale,
+                    )
                 });
 
                 progress.tick(j as usize);
@@ -207,6 +289,28 @@ impl Camera {
             })
             .collect();
 
+        let pixels = match &self.denoiser {
+            Some(denoiser) => {
+                let mut colors = Vec::with_capacity(samples.len());
+                let mut albedo = Vec::with_capacity(samples.len());
+                let mut normal = Vec::with_capacity(samples.len());
+                for (c, a, n) in samples {
+                    colors.push(c);
+                    albedo.push(a);
+                    normal.push(n);
+                }
+
+                denoiser.denoise(
+                    self.image_width as usize,
+                    self.image_height as usize,
+                    &colors,
+                    &albedo,
+                    &normal,
+                )
+            }
+            None => samples.into_iter().map(|(c, _, _)| c).collect(),
+        };
+
         out.init(self.image_height, self.image_width)?;
 
         for j in 0..self.image_height {
@@ -234,25 +338,59 @@ impl Camera {
         };
 
         let ray_direction = pixel_sample - &ray_origin;
-        let ray_time = rand::random();
+        let ray_time = self.shutter_open
+            + self.shutter_curve.sample() * (self.shutter_close - self.shutter_open);
 
         Ray::new_with_time(ray_origin, ray_direction, ray_time)
     }
 
+    /// Color of a camera ray along with the albedo and shading normal at its
+    /// first hit, which guide the denoiser. Misses report the background
+    /// color and no normal.
+    fn trace(&self, r: &Ray, world: &HittableList) -> (Color, Color, Vec3) {
+        if self.max_depth <= 0 {
+            return (Color::ZERO, Color::ZERO, Vec3::ZERO);
+        }
+
+        match world.hit(r, Interval::new(0.001, f64::INFINITY)) {
+            Some(rec) => {
+                let albedo = rec.mat.albedo(&rec);
+                let color = self.shade(r, &rec, self.max_depth, world);
+                (color, albedo, rec.normal)
+            }
+            None => {
+                let background = Self::background(r);
+                (background.clone(), background, Vec3::ZERO)
+            }
+        }
+    }
+
     fn ray_color(&self, r: &Ray, depth: i32, world: &HittableList) -> Color {
         // If exceeded ray bounce limit, no more light is gathered
         if depth <= 0 {
             return Color::ZERO;
         }
 
-        if let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) {
-            if let Some(scatter) = rec.mat.scatter(r, &rec) {
-                return scatter.attenuation * self.ray_color(&scatter.scattered, depth - 1, world);
-            } else {
-                return Color::ZERO;
-            }
+        match world.hit(r, Interval::new(0.001, f64::INFINITY)) {
+            Some(rec) => self.shade(r, &rec, depth, world),
+            None => Self::background(r),
+        }
+    }
+
+    /// Light leaving the hit `rec` back along `r`
+    fn shade(&self, r: &Ray, rec: &HitRecord, depth: i32, world: &HittableList) -> Color {
+        let color_from_emission = rec.mat.emitted(rec.u, rec.v, &rec.p);
+
+        if let Some(scatter) = rec.mat.scatter(r, rec) {
+            let color_from_scatter =
+                scatter.attenuation * self.ray_color(&scatter.scattered, depth - 1, world);
+            color_from_emission + color_from_scatter
+        } else {
+            color_from_emission
         }
+    }
 
+    fn background(r: &Ray) -> Color {
         let unit_direction = r.direction().unit_vector();
         let a = 0.5 * (unit_direction.y() + 1.0);
 
@@ -24,6 +24,15 @@ impl Color {
     }
 }
 
+/// Decode an sRGB encoded component in [0, 1] to linear
+pub fn srgb_to_linear(component: f64) -> f64 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 fn linear_to_gamma(linear_component: f64) -> f64 {
     if linear_component > 0.0 {
         linear_component.sqrt()
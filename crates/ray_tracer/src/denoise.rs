@@ -0,0 +1,147 @@
+use rayon::prelude::*;
+
+use crate::{color::Color, vec::Vec3};
+
+/// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010).
+///
+/// Repeatedly applies a 5x5 B3-spline kernel with an increasing step size,
+/// weighting each tap by how similar its color, albedo and normal are to the
+/// center pixel so that geometric and texture edges are preserved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denoiser {
+    iterations: u32,
+    sigma_color: f64,
+    sigma_normal: f64,
+    sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.1,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+    /// Steps double every iteration, past this they skip the whole image
+    const MAX_ITERATIONS: u32 = 16;
+
+    /// Filter with `iterations` passes, at most 16
+    pub fn new(iterations: u32) -> Self {
+        Self {
+            iterations: iterations.min(Self::MAX_ITERATIONS),
+            ..Default::default()
+        }
+    }
+
+    pub fn sigma_color(mut self, sigma_color: f64) -> Self {
+        self.sigma_color = sigma_color;
+        self
+    }
+
+    pub fn sigma_normal(mut self, sigma_normal: f64) -> Self {
+        self.sigma_normal = sigma_normal;
+        self
+    }
+
+    pub fn sigma_albedo(mut self, sigma_albedo: f64) -> Self {
+        self.sigma_albedo = sigma_albedo;
+        self
+    }
+
+    /// Filter a linear color buffer guided by first hit albedo and normal AOVs.
+    /// All buffers are row major and `width * height` long.
+    pub fn denoise(
+        &self,
+        width: usize,
+        height: usize,
+        color: &[Color],
+        albedo: &[Color],
+        normal: &[Vec3],
+    ) -> Vec<Color> {
+        let mut current = color.to_vec();
+
+        for iteration in 0..self.iterations {
+            let step = 1_isize << iteration;
+            // Shrink the color tolerance as the filter footprint grows so later
+            // passes don't blur across edges the earlier passes kept intact
+            let sigma_color = self.sigma_color / f64::from(1 << iteration);
+
+            current = (0..height)
+                .into_par_iter()
+                .flat_map_iter(|j| {
+                    let current = &current;
+                    (0..width).map(move |i| {
+                        self.filter_pixel(
+                            (i, j),
+                            (width, height),
+                            step,
+                            sigma_color,
+                            current,
+                            albedo,
+                            normal,
+                        )
+                    })
+                })
+                .collect();
+        }
+
+        current
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn filter_pixel(
+        &self,
+        (i, j): (usize, usize),
+        (width, height): (usize, usize),
+        step: isize,
+        sigma_color: f64,
+        color: &[Color],
+        albedo: &[Color],
+        normal: &[Vec3],
+    ) -> Color {
+        let center = j * width + i;
+        let mut sum = Color::ZERO;
+        let mut weight_sum = 0.0;
+
+        for (dy, ky) in Self::KERNEL.iter().enumerate() {
+            let y = j as isize + (dy as isize - 2) * step;
+            if y < 0 || y >= height as isize {
+                continue;
+            }
+
+            for (dx, kx) in Self::KERNEL.iter().enumerate() {
+                let x = i as isize + (dx as isize - 2) * step;
+                if x < 0 || x >= width as isize {
+                    continue;
+                }
+
+                let tap = y as usize * width + x as usize;
+                let w_color = edge_weight(&color[center], &color[tap], sigma_color);
+                let w_normal = edge_weight(&normal[center], &normal[tap], self.sigma_normal);
+                let w_albedo = edge_weight(&albedo[center], &albedo[tap], self.sigma_albedo);
+
+                let weight = kx * ky * w_color * w_normal * w_albedo;
+                sum += &(weight * &color[tap]);
+                weight_sum += weight;
+            }
+        }
+
+        if weight_sum > 0.0 {
+            sum / weight_sum
+        } else {
+            color[center].clone()
+        }
+    }
+}
+
+fn edge_weight(a: &Vec3, b: &Vec3, sigma: f64) -> f64 {
+    let dist = (a - b).length_squared();
+    (-dist / (sigma * sigma)).exp()
+}
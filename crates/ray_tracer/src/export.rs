@@ -0,0 +1,695 @@
+//! Writers for formats that other tools can view. Every shape is tessellated
+//! into a triangle mesh in world space and materials are approximated by the
+//! common diffuse, metallic, glass and emissive parameters.
+
+pub mod gltf;
+pub mod obj;
+
+use std::{
+    f64::consts::PI,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    hittable::{heightfield::Heightfield, mesh::MeshData, quadric::check_size},
+    import::{ply, stl},
+    material::Lambertian,
+    matrix::Affine,
+    onb::Onb,
+    scene_loader::{CameraSpec, MaterialSpec, SceneFile, ShapeSpec, TextureSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Half the side of the square an infinite plane is cut down to
+const PLANE_EXTENT: f64 = 1000.0;
+
+/// Scene flattened into triangle meshes
+pub struct Tessellation {
+    pub parts: Vec<Part>,
+    /// Materials the parts use, in order of first use
+    pub surfaces: Vec<Surface>,
+    pub camera: Option<CameraSpec>,
+    /// What was skipped or only approximated, each reported once
+    pub warnings: Vec<String>,
+}
+
+/// Mesh of one shape, in world space
+pub struct Part {
+    /// Name of the enclosing named shape, or the shape's kind
+    pub name: String,
+    pub mesh: MeshData,
+    /// Index into [`Tessellation::surfaces`]
+    pub surface: usize,
+}
+
+/// Material reduced to what OBJ and glTF have in common
+pub struct Surface {
+    pub name: String,
+    pub base_color: Color,
+    /// Image multiplied with `base_color`
+    pub texture: Option<PathBuf>,
+    pub metallic: f64,
+    pub roughness: f64,
+    /// Light given off, may be brighter than 1
+    pub emission: Color,
+    /// Index of refraction of glass, which lets light through
+    pub ior: Option<f64>,
+}
+
+impl Tessellation {
+    /// Write the meshes, picking the format from the file extension. Files
+    /// the format keeps apart, like OBJ's materials, are written next to it.
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("obj") => obj::write(self, path),
+            Some("gltf" | "glb") => gltf::write(self, path),
+            _ => anyhow::bail!("unknown export format: {path:?}, expected .obj, .gltf or .glb"),
+        }
+    }
+}
+
+/// Turn every shape of the scene into triangles. Curved shapes get
+/// `segments` steps around and half as many along their other direction.
+pub fn tessellate(scene: &SceneFile, segments: usize) -> anyhow::Result<Tessellation> {
+    anyhow::ensure!(segments >= 3, "need at least 3 segments, got {segments}");
+
+    let mut tessellator = Tessellator {
+        scene,
+        segments,
+        tessellation: Tessellation {
+            parts: Vec::new(),
+            surfaces: Vec::new(),
+            camera: scene.camera().cloned(),
+            warnings: Vec::new(),
+        },
+    };
+    for shape in scene.shapes() {
+        tessellator.shape(shape, &Affine::IDENTITY, None)?;
+    }
+
+    Ok(tessellator.tessellation)
+}
+
+struct Tessellator<'a> {
+    scene: &'a SceneFile,
+    segments: usize,
+    tessellation: Tessellation,
+}
+
+impl Tessellator<'_> {
+    fn warn(&mut self, warning: String) {
+        let warnings = &mut self.tessellation.warnings;
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    fn shape(
+        &mut self,
+        spec: &ShapeSpec,
+        to_world: &Affine,
+        name: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let segments = self.segments;
+        let mesh = match spec {
+            ShapeSpec::Sphere {
+                center,
+                radius,
+                motion,
+                ..
+            } => {
+                if motion.is_some() {
+                    self.warn("moving shapes are exported where they start".to_owned());
+                }
+                sphere(center, radius.max(0.0), segments)
+            }
+            ShapeSpec::Circle { center, radius, .. } => {
+                sphere(center.origin(), radius.max(0.0), segments)
+            }
+            ShapeSpec::Quad { q, u, v, .. } => quad(q, u, v),
+            ShapeSpec::Triangle { q, u, v, .. } => MeshData {
+                positions: vec![q.clone(), q + u, q + v],
+                indices: vec![[0, 1, 2]],
+                normals: vec![u.cross(v).unit_vector(); 3],
+                uvs: vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+                colors: Vec::new(),
+            },
+            ShapeSpec::Ellipse { center, u, v, .. } => {
+                ellipse(center, u, v, 0.0, &u.cross(v), segments)
+            }
+            ShapeSpec::Annulus {
+                center,
+                u,
+                v,
+                inner,
+                ..
+            } => ellipse(center, u, v, inner.clamp(0.0, 1.0), &u.cross(v), segments),
+            ShapeSpec::Disk {
+                center,
+                normal,
+                radius,
+                ..
+            } => {
+                // The same axes the renderer uses, so textures line up
+                let basis = Onb::new(normal);
+                let (u, v) = (*radius * basis.u(), *radius * basis.v());
+                ellipse(center, &u, &v, 0.0, normal, segments)
+            }
+            ShapeSpec::Box { a, b, .. } => cuboid(a, b),
+            ShapeSpec::Cylinder {
+                center,
+                radius,
+                height,
+                capped,
+                ..
+            } => revolve(
+                center,
+                *radius,
+                *height,
+                *capped,
+                Profile::Cylinder,
+                segments,
+            )?,
+            ShapeSpec::Cone {
+                center,
+                radius,
+                height,
+                capped,
+                ..
+            } => revolve(center, *radius, *height, *capped, Profile::Cone, segments)?,
+            ShapeSpec::Paraboloid {
+                center,
+                radius,
+                height,
+                capped,
+                ..
+            } => revolve(
+                center,
+                *radius,
+                *height,
+                *capped,
+                Profile::Paraboloid,
+                segments,
+            )?,
+            ShapeSpec::Hyperboloid {
+                center,
+                radius,
+                end_radius,
+                height,
+                capped,
+                ..
+            } => {
+                let profile = Profile::Hyperboloid {
+                    end_radius: end_radius.max(radius + 1e-6),
+                };
+                revolve(center, *radius, *height, *capped, profile, segments)?
+            }
+            ShapeSpec::Torus {
+                center,
+                major_radius,
+                minor_radius,
+                ..
+            } => torus(center, *major_radius, *minor_radius, segments),
+            ShapeSpec::Plane { point, normal, .. } => {
+                self.warn(format!(
+                    "infinite planes are cut down to {} unit squares",
+                    2.0 * PLANE_EXTENT
+                ));
+                let basis = Onb::new(normal);
+                let (u, v) = (
+                    2.0 * PLANE_EXTENT * basis.v(),
+                    2.0 * PLANE_EXTENT * basis.u(),
+                );
+                quad(&(point - 0.5 * (&u + &v)), &u, &v)
+            }
+            ShapeSpec::Sdf { .. } => {
+                self.warn("signed distance shapes are skipped".to_owned());
+                return Ok(());
+            }
+            ShapeSpec::Heightfield {
+                path,
+                origin,
+                size_x,
+                size_z,
+                height,
+                material,
+            } => {
+                // Only the geometry is used, the material is exported separately
+                let placeholder = Arc::new(Lambertian::new(material.as_str(), Color::ZERO));
+                Heightfield::new(path, origin.clone(), *size_x, *size_z, *height, placeholder)?
+                    .to_mesh()
+            }
+            ShapeSpec::Mesh {
+                positions,
+                indices,
+                normals,
+                uvs,
+                colors,
+                ..
+            } => MeshData {
+                positions: positions.clone(),
+                indices: indices.clone(),
+                normals: normals.clone(),
+                uvs: uvs.clone(),
+                colors: colors.clone(),
+            },
+            ShapeSpec::Ply { path, .. } => ply::read(path)?,
+            ShapeSpec::Stl { path, .. } => stl::read(path)?,
+            ShapeSpec::Csg { left, right, .. } => {
+                self.warn("boolean shapes are exported as their unmerged operands".to_owned());
+                self.shape(left, to_world, name)?;
+                return self.shape(right, to_world, name);
+            }
+            ShapeSpec::List(shapes) => {
+                for shape in shapes {
+                    self.shape(shape, to_world, name)?;
+                }
+                return Ok(());
+            }
+            ShapeSpec::BVH { left, right } => {
+                self.shape(left, to_world, name)?;
+                return self.shape(right, to_world, name);
+            }
+            ShapeSpec::ConstantMedium {
+                boundary, material, ..
+            }
+            | ShapeSpec::HeterogeneousMedium {
+                boundary, material, ..
+            } => {
+                self.warn("media are exported as their boundary surface".to_owned());
+                return self.medium(boundary, material, to_world, name);
+            }
+            ShapeSpec::Transform { shape, transform } => {
+                return self.shape(shape, &transform.affine().then(to_world), name);
+            }
+            ShapeSpec::Motion { shape, keyframes } => {
+                self.warn("moving shapes are exported where they start".to_owned());
+                let start = keyframes
+                    .iter()
+                    .min_by(|a, b| a.time.total_cmp(&b.time))
+                    .map_or(Affine::IDENTITY, |keyframe| keyframe.transform.affine());
+                return self.shape(shape, &start.then(to_world), name);
+            }
+            ShapeSpec::Named { name, shape } => {
+                return self.shape(shape, to_world, Some(name));
+            }
+            ShapeSpec::Instance {
+                prototype,
+                transform,
+            } => {
+                let shape = self
+                    .scene
+                    .prototypes()
+                    .iter()
+                    .find(|(key, _)| key == prototype)
+                    .map(|(_, shape)| shape)
+                    .ok_or_else(|| anyhow::anyhow!("unknown prototype '{prototype}'"))?;
+                return self.shape(shape, &transform.affine().then(to_world), name);
+            }
+        };
+
+        let material = spec.material().expect("every mesh has a material");
+        self.add_part(mesh, material, to_world, name.unwrap_or(spec.kind()))
+    }
+
+    /// Boundary of a medium, drawn with the medium's material
+    fn medium(
+        &mut self,
+        boundary: &ShapeSpec,
+        material: &str,
+        to_world: &Affine,
+        name: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let first = self.tessellation.parts.len();
+        self.shape(boundary, to_world, name)?;
+
+        let surface = self.surface(material)?;
+        for part in &mut self.tessellation.parts[first..] {
+            part.surface = surface;
+        }
+
+        Ok(())
+    }
+
+    fn add_part(
+        &mut self,
+        mut mesh: MeshData,
+        material: &str,
+        to_world: &Affine,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        mesh.transform(to_world);
+        let surface = self.surface(material)?;
+        self.tessellation.parts.push(Part {
+            name: name.to_owned(),
+            mesh,
+            surface,
+        });
+
+        Ok(())
+    }
+
+    /// Index of the surface approximating `material`, added on first use
+    fn surface(&mut self, material: &str) -> anyhow::Result<usize> {
+        let surfaces = &self.tessellation.surfaces;
+        if let Some(index) = surfaces.iter().position(|s| s.name == material) {
+            return Ok(index);
+        }
+
+        let spec = self
+            .scene
+            .materials()
+            .iter()
+            .find(|(key, _)| key == material)
+            .map(|(_, spec)| spec)
+            .ok_or_else(|| anyhow::anyhow!("unknown material '{material}'"))?;
+
+        let mut surface = Surface {
+            name: material.to_owned(),
+            base_color: Color::new(1.0, 1.0, 1.0),
+            texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            emission: Color::ZERO,
+            ior: None,
+        };
+        match spec {
+            MaterialSpec::Lambertian { texture } => {
+                (surface.base_color, surface.texture) = self.texture(texture)?;
+            }
+            MaterialSpec::Metal { albedo, fuzz } => {
+                surface.base_color = albedo.clone();
+                surface.metallic = 1.0;
+                surface.roughness = fuzz.clamp(0.0, 1.0);
+            }
+            MaterialSpec::Conductor {
+                ior,
+                roughness,
+                roughness_v,
+            } => {
+                surface.base_color = ior.fresnel(1.0);
+                surface.metallic = 1.0;
+                surface.roughness = *roughness;
+                if let Some(roughness_v) = roughness_v {
+                    self.warn(
+                        "anisotropic metals are exported with their mean roughness".to_owned(),
+                    );
+                    surface.roughness = 0.5 * (roughness + roughness_v);
+                }
+            }
+            MaterialSpec::Dielectric { refraction_index } => {
+                surface.roughness = 0.0;
+                surface.ior = Some(*refraction_index);
+            }
+            MaterialSpec::Isotropic { texture }
+            | MaterialSpec::HenyeyGreenstein { texture, .. } => {
+                self.warn("volume materials are exported as diffuse surfaces".to_owned());
+                (surface.base_color, surface.texture) = self.texture(texture)?;
+            }
+            MaterialSpec::DiffuseLight { texture } => {
+                surface.base_color = Color::ZERO;
+                let (emission, image) = self.texture(texture)?;
+                if image.is_some() {
+                    self.warn("image textures on lights are exported as white".to_owned());
+                }
+                surface.emission = emission;
+            }
+        }
+
+        self.tessellation.surfaces.push(surface);
+        Ok(self.tessellation.surfaces.len() - 1)
+    }
+
+    /// Color standing in for a texture, and its image if it has one
+    fn texture(&mut self, texture: &str) -> anyhow::Result<(Color, Option<PathBuf>)> {
+        let spec = self
+            .scene
+            .textures()
+            .iter()
+            .find(|(key, _)| key == texture)
+            .map(|(_, spec)| spec)
+            .ok_or_else(|| anyhow::anyhow!("unknown texture '{texture}'"))?;
+
+        Ok(match spec {
+            TextureSpec::SolidColor { albedo } => (albedo.clone(), None),
+            TextureSpec::Image { path } => (Color::new(1.0, 1.0, 1.0), Some(path.clone())),
+            TextureSpec::Checker { even, odd, .. } => {
+                self.warn(
+                    "checker textures are exported as the average of their colors".to_owned(),
+                );
+                let (even, _) = self.texture(even)?;
+                let (odd, _) = self.texture(odd)?;
+                (0.5 * (even + odd), None)
+            }
+            TextureSpec::Perlin { .. } => {
+                self.warn("noise textures are exported as their average grey".to_owned());
+                (Color::new(0.5, 0.5, 0.5), None)
+            }
+        })
+    }
+}
+
+/// Grid of `nu` by `nv` quads over `f(u, v)`, which gives the position,
+/// normal and texture coordinates. Faces point along `df/du x df/dv`.
+fn grid(nu: usize, nv: usize, f: impl Fn(f64, f64) -> (Point3, Vec3, [f64; 2])) -> MeshData {
+    let mut mesh = MeshData::default();
+    for j in 0..=nv {
+        for i in 0..=nu {
+            let (position, normal, uv) = f(i as f64 / nu as f64, j as f64 / nv as f64);
+            mesh.positions.push(position);
+            mesh.normals.push(normal);
+            mesh.uvs.push(uv);
+        }
+    }
+
+    let index = |i: usize, j: usize| j * (nu + 1) + i;
+    for j in 0..nv {
+        for i in 0..nu {
+            mesh.indices
+                .push([index(i, j), index(i + 1, j), index(i + 1, j + 1)]);
+            mesh.indices
+                .push([index(i, j), index(i + 1, j + 1), index(i, j + 1)]);
+        }
+    }
+
+    mesh
+}
+
+/// Offset from the y axis at angle `2 pi u`, in the direction the renderer's
+/// texture coordinates of round shapes use
+fn around(u: f64) -> Vec3 {
+    let phi = 2.0 * PI * u;
+    Vec3::new(-phi.cos(), 0.0, phi.sin())
+}
+
+fn sphere(center: &Point3, radius: f64, segments: usize) -> MeshData {
+    grid(segments, segments / 2, |u, v| {
+        let theta = PI * v;
+        let normal = theta.sin() * around(u) + Vec3::new(0.0, -theta.cos(), 0.0);
+        (center + radius * &normal, normal, [u, v])
+    })
+}
+
+fn quad(q: &Point3, u: &Vec3, v: &Vec3) -> MeshData {
+    let normal = u.cross(v).unit_vector();
+    grid(1, 1, |a, b| (q + a * u + b * v, normal.clone(), [a, b]))
+}
+
+/// Ellipse around `center` with axes `u` and `v`, with a hole `inner` of its
+/// size, facing `normal`
+fn ellipse(
+    center: &Point3,
+    u: &Vec3,
+    v: &Vec3,
+    inner: f64,
+    normal: &Vec3,
+    segments: usize,
+) -> MeshData {
+    let normal = normal.unit_vector();
+    let mut mesh = grid(1, segments, |r, t| {
+        let r = inner + (1.0 - inner) * r;
+        let (a, b) = (r * (2.0 * PI * t).cos(), r * (2.0 * PI * t).sin());
+        let p = center + a * u + b * v;
+        (p, normal.clone(), [0.5 * a + 0.5, 0.5 * b + 0.5])
+    });
+    if u.cross(v).dot(&normal) < 0.0 {
+        mesh.reverse();
+    }
+
+    mesh
+}
+
+/// The six faces of the box between corners `a` and `b`, split apart so
+/// each has its own normals and texture coordinates
+fn cuboid(a: &Point3, b: &Point3) -> MeshData {
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    // Same sides as `make_box`
+    let sides = [
+        (
+            Point3::new(min.x(), min.y(), max.z()),
+            dx.clone(),
+            dy.clone(),
+        ),
+        (Point3::new(max.x(), min.y(), max.z()), -&dz, dy.clone()),
+        (Point3::new(max.x(), min.y(), min.z()), -&dx, dy.clone()),
+        (
+            Point3::new(min.x(), min.y(), min.z()),
+            dz.clone(),
+            dy.clone(),
+        ),
+        (Point3::new(min.x(), max.y(), max.z()), dx.clone(), -&dz),
+        (Point3::new(min.x(), min.y(), min.z()), dx, dz),
+    ];
+
+    let mut mesh = MeshData::default();
+    for (q, u, v) in sides {
+        append(&mut mesh, quad(&q, &u, &v));
+    }
+
+    mesh
+}
+
+fn append(mesh: &mut MeshData, other: MeshData) {
+    let offset = mesh.positions.len();
+    mesh.positions.extend(other.positions);
+    mesh.normals.extend(other.normals);
+    mesh.uvs.extend(other.uvs);
+    mesh.indices
+        .extend(other.indices.into_iter().map(|t| t.map(|i| i + offset)));
+}
+
+/// Lateral surfaces of the quadrics, see
+/// [`QuadricShape`](crate::hittable::quadric::QuadricShape)
+#[derive(Clone, Copy)]
+enum Profile {
+    Cylinder,
+    Cone,
+    Paraboloid,
+    Hyperboloid { end_radius: f64 },
+}
+
+/// Quadric standing on `center`, built by sweeping its profile around the
+/// y axis
+fn revolve(
+    center: &Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    profile: Profile,
+    segments: usize,
+) -> anyhow::Result<MeshData> {
+    check_size(radius, height)?;
+    let (r, h) = (radius, height);
+    // `1/c^2` of the hyperboloid `(x^2 + z^2)/r^2 - y^2/c^2 = 1`
+    let inv_c2 = match profile {
+        Profile::Hyperboloid { end_radius } => {
+            let half_h = 0.5 * h;
+            (end_radius * end_radius - r * r) / (half_h * half_h * r * r)
+        }
+        _ => 0.0,
+    };
+
+    // Distance from the axis at height y, and the outward normal there split
+    // into its parts away from the axis and along it
+    let section = |y: f64| -> (f64, f64, f64) {
+        match profile {
+            Profile::Cylinder => (r, r, 0.0),
+            Profile::Cone => {
+                let k = r / h;
+                // The slope, not the radius, so the apex gets a normal too
+                (r - k * y, 1.0, k)
+            }
+            Profile::Paraboloid => {
+                let rho = (r * r * y / h).max(0.0).sqrt();
+                (rho, rho, -0.5 * r * r / h)
+            }
+            Profile::Hyperboloid { .. } => {
+                let dy = y - 0.5 * h;
+                let rho = r * (1.0 + dy * dy * inv_c2).sqrt();
+                (rho, rho / (r * r), -dy * inv_c2)
+            }
+        }
+    };
+
+    let mut mesh = grid(segments, (segments / 2).max(1), |u, v| {
+        let y = v * h;
+        let (rho, radial, normal_y) = section(y);
+        let direction = around(u);
+        let p = center + rho * &direction + Vec3::new(0.0, y, 0.0);
+        let normal = (radial * direction + Vec3::new(0.0, normal_y, 0.0)).unit_vector();
+        (p, normal, [u, v])
+    });
+
+    if capped {
+        let caps: &[(f64, f64, f64)] = match profile {
+            Profile::Cylinder => &[(0.0, r, -1.0), (h, r, 1.0)],
+            Profile::Cone => &[(0.0, r, -1.0)],
+            Profile::Paraboloid => &[(h, r, 1.0)],
+            Profile::Hyperboloid { end_radius } => &[(0.0, end_radius, -1.0), (h, end_radius, 1.0)],
+        };
+        for &(y, radius, normal_y) in caps {
+            // Axes picked so the texture coordinates match the renderer's caps
+            let cap = ellipse(
+                &(center + Vec3::new(0.0, y, 0.0)),
+                &Vec3::new(radius, 0.0, 0.0),
+                &Vec3::new(0.0, 0.0, normal_y * radius),
+                0.0,
+                &Vec3::new(0.0, normal_y, 0.0),
+                segments,
+            );
+            append(&mut mesh, cap);
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn torus(center: &Point3, major_radius: f64, minor_radius: f64, segments: usize) -> MeshData {
+    grid(segments, (segments / 2).max(3), |u, v| {
+        let theta = 2.0 * PI * v;
+        let direction = around(u);
+        let normal = -theta.cos() * &direction + Vec3::new(0.0, -theta.sin(), 0.0);
+        let p = center + major_radius * &direction + minor_radius * &normal;
+        (p, normal, [u, v])
+    })
+}
+
+/// `path`, taken relative to the working directory, as seen from the
+/// directory of the file at `output`
+fn relative_path(path: &Path, output: &Path) -> anyhow::Result<PathBuf> {
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => normalize(dir)?,
+        _ => std::env::current_dir()?,
+    };
+    let absolute = normalize(path)?;
+
+    Ok(pathdiff::diff_paths(&absolute, &dir).unwrap_or(absolute))
+}
+
+/// Absolute `path` without `.` or `..`, resolving links when it exists so
+/// both sides of a relative path agree
+fn normalize(path: &Path) -> anyhow::Result<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Ok(path);
+    }
+
+    let mut normal = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::CurDir => {}
+            component => normal.push(component),
+        }
+    }
+    Ok(normal)
+}
@@ -0,0 +1,319 @@
+//! glTF 2.0, either as `.gltf` text with the geometry embedded as a data URI
+//! and textures referenced by path, or as a single binary `.glb` that
+//! carries PNG and JPEG textures too. Materials use the metallic-roughness
+//! model with the transmission, IOR and emissive strength extensions.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use base64::Engine;
+use serde_json::{Value, json};
+
+use crate::{
+    export::{Tessellation, relative_path},
+    matrix::{Mat3, Quat},
+    scene_loader::CameraSpec,
+    vec::{Point3, Vec3},
+};
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Binary data of the file and the views and accessors into it
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffer {
+    /// Append `bytes` as a new view, keeping every view 4 byte aligned
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        self.data.extend_from_slice(bytes);
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, bytes: &[u8], count: usize, kind: &str, component: u32) -> usize {
+        let target = match component {
+            UNSIGNED_INT => ELEMENT_ARRAY_BUFFER,
+            _ => ARRAY_BUFFER,
+        };
+        let view = self.view(bytes, Some(target));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": component,
+            "count": count,
+            "type": kind,
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn vec3s(&mut self, values: &[Vec3]) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| [v.x(), v.y(), v.z()])
+            .flat_map(|c| (c as f32).to_le_bytes())
+            .collect();
+        self.accessor(&bytes, values.len(), "VEC3", FLOAT)
+    }
+
+    /// Positions need their bounds
+    fn positions(&mut self, positions: &[Point3]) -> usize {
+        let accessor = self.vec3s(positions);
+        let bound = |pick: fn(f32, f32) -> f32, start: f32| {
+            positions.iter().fold([start; 3], |b, p| {
+                [
+                    pick(b[0], p.x() as f32),
+                    pick(b[1], p.y() as f32),
+                    pick(b[2], p.z() as f32),
+                ]
+            })
+        };
+        self.accessors[accessor]["min"] = json!(bound(f32::min, f32::INFINITY));
+        self.accessors[accessor]["max"] = json!(bound(f32::max, f32::NEG_INFINITY));
+        accessor
+    }
+
+    /// glTF puts the texture origin at the top left, we use the bottom left
+    fn uvs(&mut self, uvs: &[[f64; 2]]) -> usize {
+        let bytes: Vec<u8> = uvs
+            .iter()
+            .flat_map(|&[u, v]| [u, 1.0 - v])
+            .flat_map(|c| (c as f32).to_le_bytes())
+            .collect();
+        self.accessor(&bytes, uvs.len(), "VEC2", FLOAT)
+    }
+
+    fn indices(&mut self, indices: &[[usize; 3]]) -> usize {
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flatten()
+            .flat_map(|&i| (i as u32).to_le_bytes())
+            .collect();
+        self.accessor(&bytes, 3 * indices.len(), "SCALAR", UNSIGNED_INT)
+    }
+}
+
+pub fn write(tessellation: &Tessellation, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let binary = path.extension().is_some_and(|ext| ext == "glb");
+
+    let mut buffer = Buffer::default();
+    let mut nodes = Vec::new();
+    let mut meshes = Vec::new();
+    for part in &tessellation.parts {
+        let mesh = &part.mesh;
+        let mut attributes = json!({ "POSITION": buffer.positions(&mesh.positions) });
+        if !mesh.normals.is_empty() {
+            attributes["NORMAL"] = json!(buffer.vec3s(&mesh.normals));
+        }
+        if !mesh.uvs.is_empty() {
+            attributes["TEXCOORD_0"] = json!(buffer.uvs(&mesh.uvs));
+        }
+        if !mesh.colors.is_empty() {
+            attributes["COLOR_0"] = json!(buffer.vec3s(&mesh.colors));
+        }
+
+        meshes.push(json!({
+            "name": part.name,
+            "primitives": [{
+                "attributes": attributes,
+                "indices": buffer.indices(&mesh.indices),
+                "material": part.surface,
+            }],
+        }));
+        nodes.push(json!({ "name": part.name, "mesh": meshes.len() - 1 }));
+    }
+
+    // One texture per distinct image
+    let mut image_paths: Vec<&PathBuf> = Vec::new();
+    let mut extensions: Vec<&str> = Vec::new();
+    let mut materials = Vec::new();
+    for surface in &tessellation.surfaces {
+        let color = &surface.base_color;
+        let mut material = json!({
+            "name": surface.name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [color.x(), color.y(), color.z(), 1.0],
+                "metallicFactor": surface.metallic,
+                "roughnessFactor": surface.roughness,
+            },
+            // Every surface can be hit from both sides in the renderer
+            "doubleSided": true,
+        });
+
+        if let Some(texture) = &surface.texture {
+            let index = image_paths
+                .iter()
+                .position(|p| *p == texture)
+                .unwrap_or_else(|| {
+                    image_paths.push(texture);
+                    image_paths.len() - 1
+                });
+            material["pbrMetallicRoughness"]["baseColorTexture"] = json!({ "index": index });
+        }
+
+        // Factors stop at 1, brighter light goes into the strength
+        let e = &surface.emission;
+        let strength = e.x().max(e.y()).max(e.z());
+        if strength > 0.0 {
+            let factor = (1.0 / strength.max(1.0)) * e;
+            material["emissiveFactor"] = json!([factor.x(), factor.y(), factor.z()]);
+            if strength > 1.0 {
+                material["extensions"]["KHR_materials_emissive_strength"] =
+                    json!({ "emissiveStrength": strength });
+                extensions.push("KHR_materials_emissive_strength");
+            }
+        }
+        if let Some(ior) = surface.ior {
+            material["extensions"]["KHR_materials_transmission"] =
+                json!({ "transmissionFactor": 1.0 });
+            material["extensions"]["KHR_materials_ior"] = json!({ "ior": ior });
+            extensions.extend(["KHR_materials_transmission", "KHR_materials_ior"]);
+        }
+
+        materials.push(material);
+    }
+    extensions.sort_unstable();
+    extensions.dedup();
+
+    let mut images = Vec::new();
+    for image in &image_paths {
+        let mime = match image.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => Some("image/png"),
+            Some("jpg" | "jpeg") => Some("image/jpeg"),
+            _ => None,
+        };
+        // Binary files carry the formats glTF supports, anything else is
+        // left for the viewer to find
+        images.push(match mime {
+            Some(mime) if binary => {
+                let bytes = std::fs::read(image)
+                    .with_context(|| format!("Failed to read texture: {image:?}"))?;
+                json!({ "bufferView": buffer.view(&bytes, None), "mimeType": mime })
+            }
+            _ => {
+                let relative = relative_path(image, path)?;
+                let uri: Vec<_> = relative
+                    .iter()
+                    .map(|part| urlencoding::encode(&part.to_string_lossy()).into_owned())
+                    .collect();
+                json!({ "uri": uri.join("/") })
+            }
+        });
+    }
+    let textures: Vec<Value> = (0..images.len()).map(|i| json!({ "source": i })).collect();
+
+    let mut cameras = Vec::new();
+    if let Some(camera) = &tessellation.camera {
+        let (camera, node) = camera_node(camera);
+        cameras.push(camera);
+        nodes.push(node);
+    }
+
+    let mut buffer_json = json!({ "byteLength": buffer.data.len() });
+    if !binary {
+        let data = base64::engine::general_purpose::STANDARD.encode(&buffer.data);
+        buffer_json["uri"] = json!(format!("data:application/octet-stream;base64,{data}"));
+    }
+
+    let mut root = json!({
+        "asset": { "version": "2.0", "generator": "ray_tracer" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "accessors": buffer.accessors,
+    });
+    // Buffers can't be empty, as they are when there is nothing to export
+    if !buffer.data.is_empty() {
+        root["bufferViews"] = json!(buffer.views);
+        root["buffers"] = json!([buffer_json]);
+    }
+    for (key, values) in [
+        ("images", images),
+        ("textures", textures),
+        ("cameras", cameras),
+    ] {
+        if !values.is_empty() {
+            root[key] = json!(values);
+        }
+    }
+    if !extensions.is_empty() {
+        root["extensionsUsed"] = json!(extensions);
+    }
+
+    let bytes = match binary {
+        true => glb(&serde_json::to_vec(&root)?, &buffer.data),
+        false => serde_json::to_vec_pretty(&root)?,
+    };
+    std::fs::write(path, bytes).with_context(|| format!("Failed to write glTF file: {path:?}"))
+}
+
+/// Camera and the node placing it. glTF cameras look down -z with y up.
+fn camera_node(camera: &CameraSpec) -> (Value, Value) {
+    let lookfrom = camera.lookfrom.clone().unwrap_or(Point3::ZERO);
+    let lookat = camera.lookat.clone().unwrap_or(Point3::new(0.0, 0.0, -1.0));
+    let vup = camera.vup.clone().unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+
+    let back = (&lookfrom - &lookat).unit_vector();
+    let right = vup.cross(&back).unit_vector();
+    let up = back.cross(&right);
+    let columns = [right, up, back].map(|v| [v.x(), v.y(), v.z()]);
+    let q = Quat::from_mat3(&Mat3::new(columns).transpose());
+
+    let camera = json!({
+        "type": "perspective",
+        "perspective": {
+            "yfov": camera.vfov.unwrap_or(90.0).to_radians(),
+            "aspectRatio": camera.aspect_ratio.unwrap_or(16.0 / 9.0),
+            "znear": 0.01,
+        },
+    });
+    let node = json!({
+        "name": "camera",
+        "camera": 0,
+        "translation": [lookfrom.x(), lookfrom.y(), lookfrom.z()],
+        "rotation": [q.x, q.y, q.z, q.w],
+    });
+
+    (camera, node)
+}
+
+/// Pack the JSON and binary chunks into a binary glTF
+fn glb(json: &[u8], bin: &[u8]) -> Vec<u8> {
+    let chunk = |kind: &[u8; 4], data: &[u8], pad: u8| {
+        let mut chunk = Vec::with_capacity(data.len() + 11);
+        let length = data.len().next_multiple_of(4);
+        chunk.extend_from_slice(&(length as u32).to_le_bytes());
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.resize(8 + length, pad);
+        chunk
+    };
+    let json = chunk(b"JSON", json, b' ');
+    let bin = chunk(b"BIN\0", bin, 0);
+
+    let mut glb = Vec::with_capacity(12 + json.len() + bin.len());
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&((12 + json.len() + bin.len()) as u32).to_le_bytes());
+    glb.extend_from_slice(&json);
+    glb.extend_from_slice(&bin);
+    glb
+}
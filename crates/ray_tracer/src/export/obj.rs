@@ -0,0 +1,113 @@
+//! Wavefront OBJ with its materials in an MTL file of the same name. Vertex
+//! colors use the common `v x y z r g b` extension and metals the PBR `Pm`
+//! and `Pr` statements that Blender reads.
+
+use std::{fmt::Write as _, path::Path};
+
+use anyhow::Context;
+
+use crate::export::{Tessellation, relative_path};
+
+pub fn write(tessellation: &Tessellation, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .context("export path has no file name")?
+        .to_string_lossy();
+
+    let mut obj = String::new();
+    writeln!(obj, "mtllib {mtl_name}")?;
+
+    // Indices count from 1 across the whole file, separately for each kind
+    let (mut positions, mut uvs, mut normals) = (1, 1, 1);
+    for part in &tessellation.parts {
+        let mesh = &part.mesh;
+        writeln!(obj, "o {}", identifier(&part.name))?;
+        writeln!(
+            obj,
+            "usemtl {}",
+            identifier(&tessellation.surfaces[part.surface].name)
+        )?;
+
+        for (i, p) in mesh.positions.iter().enumerate() {
+            write!(obj, "v {} {} {}", p.x(), p.y(), p.z())?;
+            if let Some(c) = mesh.colors.get(i) {
+                write!(obj, " {} {} {}", c.x(), c.y(), c.z())?;
+            }
+            writeln!(obj)?;
+        }
+        for [u, v] in &mesh.uvs {
+            writeln!(obj, "vt {u} {v}")?;
+        }
+        for n in &mesh.normals {
+            writeln!(obj, "vn {} {} {}", n.x(), n.y(), n.z())?;
+        }
+
+        let (has_uvs, has_normals) = (!mesh.uvs.is_empty(), !mesh.normals.is_empty());
+        for triangle in &mesh.indices {
+            write!(obj, "f")?;
+            for i in triangle {
+                let (p, t, n) = (positions + i, uvs + i, normals + i);
+                match (has_uvs, has_normals) {
+                    (true, true) => write!(obj, " {p}/{t}/{n}")?,
+                    (true, false) => write!(obj, " {p}/{t}")?,
+                    (false, true) => write!(obj, " {p}//{n}")?,
+                    (false, false) => write!(obj, " {p}")?,
+                }
+            }
+            writeln!(obj)?;
+        }
+
+        positions += mesh.positions.len();
+        uvs += mesh.uvs.len();
+        normals += mesh.normals.len();
+    }
+
+    let mut mtl = String::new();
+    for surface in &tessellation.surfaces {
+        let color = &surface.base_color;
+        writeln!(mtl, "newmtl {}", identifier(&surface.name))?;
+        writeln!(mtl, "Kd {} {} {}", color.x(), color.y(), color.z())?;
+        if let Some(texture) = &surface.texture {
+            let texture = relative_path(texture, path)?;
+            writeln!(mtl, "map_Kd {}", texture.display())?;
+        }
+
+        if surface.metallic > 0.0 {
+            // Metals reflect in their own color
+            writeln!(mtl, "Ks {} {} {}", color.x(), color.y(), color.z())?;
+        }
+        let shininess = 1000.0 * (1.0 - surface.roughness).powi(2);
+        writeln!(mtl, "Ns {shininess}")?;
+        writeln!(mtl, "Pm {}", surface.metallic)?;
+        writeln!(mtl, "Pr {}", surface.roughness)?;
+
+        let e = &surface.emission;
+        if e.length_squared() > 0.0 {
+            writeln!(mtl, "Ke {} {} {}", e.x(), e.y(), e.z())?;
+        }
+
+        // illum 2 is plain highlights, 3 adds reflection, 7 refraction
+        let illum = match surface.ior {
+            Some(ior) => {
+                writeln!(mtl, "Ni {ior}")?;
+                writeln!(mtl, "d 0")?;
+                7
+            }
+            None if surface.metallic > 0.0 => 3,
+            None => 2,
+        };
+        writeln!(mtl, "illum {illum}")?;
+        writeln!(mtl)?;
+    }
+
+    std::fs::write(path, obj).with_context(|| format!("Failed to write OBJ file: {path:?}"))?;
+    std::fs::write(&mtl_path, mtl)
+        .with_context(|| format!("Failed to write MTL file: {mtl_path:?}"))
+}
+
+/// OBJ names end at the first space
+fn identifier(name: &str) -> String {
+    name.replace(char::is_whitespace, "_")
+}
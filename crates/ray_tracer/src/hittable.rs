@@ -1,11 +1,25 @@
 use std::sync::Arc;
 
 pub mod bvh;
+pub mod constant_medium;
+pub mod csg;
+pub mod heightfield;
+pub mod mesh;
+pub mod motion;
+pub mod named;
+pub mod plane;
 pub mod quad;
+pub mod quadric;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
+pub mod transform;
+pub mod volume;
 
 use crate::{
     aabb::AABB,
+    color::Color,
+    hittable::{bvh::BVHNode, named::ObjectId},
     interval::Interval,
     material::DynMaterial,
     ray::Ray,
@@ -13,6 +27,7 @@ use crate::{
     vec::{Point3, Vec3},
 };
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
@@ -21,6 +36,10 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    /// Innermost named object the hit belongs to
+    pub object: Option<ObjectId>,
+    /// Vertex color interpolated at the hit, multiplied into the albedo
+    pub color: Option<Color>,
 }
 
 impl HitRecord {
@@ -33,6 +52,16 @@ impl HitRecord {
             u: 0.0,
             v: 0.0,
             front_face: false,
+            object: None,
+            color: None,
+        }
+    }
+
+    /// Apply the vertex color, if any, to an albedo
+    pub fn tint(&self, albedo: Color) -> Color {
+        match &self.color {
+            Some(color) => albedo * color.clone(),
+            None => albedo,
         }
     }
 
@@ -49,12 +78,60 @@ impl HitRecord {
     }
 }
 
+/// Part of a ray that lies inside a closed shape
+#[derive(Clone)]
+pub struct Span {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
 pub trait Hittable {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
 
     fn bounding_box(&self) -> &AABB;
 
     fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec;
+
+    /// Every span of the whole line through `r` that is inside the shape,
+    /// ordered along the ray. None for shapes that don't enclose a volume.
+    fn spans(&self, _r: &Ray) -> Option<Vec<Span>> {
+        None
+    }
+
+    /// Name given to the shape in the scene, see [`named::Named`]
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Shapes this one is built from, for walking the scene
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        Vec::new()
+    }
+}
+
+/// Collect the spans of a closed shape by repeatedly intersecting the ray
+/// beyond the previous hit and pairing entering with exiting hits
+pub fn collect_spans<H: Hittable + ?Sized>(shape: &H, r: &Ray) -> Vec<Span> {
+    const MAX_CROSSINGS: usize = 64;
+
+    let mut spans = Vec::new();
+    let mut enter: Option<HitRecord> = None;
+    let mut t_min = f64::NEG_INFINITY;
+
+    for _ in 0..MAX_CROSSINGS {
+        let Some(rec) = shape.hit(r, Interval::new(t_min, f64::INFINITY)) else {
+            break;
+        };
+        t_min = rec.t + 1e-7;
+
+        if rec.front_face {
+            enter = Some(rec);
+        } else if let Some(enter) = enter.take() {
+            spans.push(Span { enter, exit: rec });
+        }
+    }
+
+    spans
 }
 
 pub type DynHittable = dyn Hittable + Send + Sync;
@@ -71,6 +148,18 @@ impl Hittable for Arc<DynHittable> {
     fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
         (**self).to_spec(registry)
     }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        (**self).spans(r)
+    }
+
+    fn name(&self) -> Option<&str> {
+        (**self).name()
+    }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        (**self).children()
+    }
 }
 
 #[derive(Default)]
@@ -92,6 +181,61 @@ impl HittableList {
     pub fn objects_mut(&mut self) -> &mut [Arc<DynHittable>] {
         &mut self.objects
     }
+
+    /// Build a BVH over every bounded object. Unbounded objects such as
+    /// infinite planes would give every node an infinite box, so they are
+    /// kept next to the tree and tested on their own.
+    pub fn into_bvh(self) -> HittableList {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = self
+            .objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_bounded());
+
+        let mut list = HittableList::default();
+        if !bounded.is_empty() {
+            list.add(Arc::new(BVHNode::from_slice(&mut bounded)));
+        }
+        for object in unbounded {
+            list.add(object);
+        }
+
+        list
+    }
+
+    /// First object named `name`, searching depth first through groups,
+    /// BVH nodes and transforms
+    pub fn find_by_name(&self, name: &str) -> Option<Arc<DynHittable>> {
+        self.find(|object| object.name() == Some(name))
+    }
+
+    /// Named object with the id reported in [`HitRecord::object`]
+    pub fn find_by_id(&self, id: ObjectId) -> Option<Arc<DynHittable>> {
+        self.find(|object| object.name().map(ObjectId::from_name) == Some(id))
+    }
+
+    /// Names of every named object, in depth first order
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut stack: Vec<&Arc<DynHittable>> = self.objects.iter().rev().collect();
+        while let Some(object) = stack.pop() {
+            if let Some(name) = object.name() {
+                names.push(name.to_owned());
+            }
+            stack.extend(object.children().into_iter().rev());
+        }
+        names
+    }
+
+    fn find(&self, predicate: impl Fn(&Arc<DynHittable>) -> bool) -> Option<Arc<DynHittable>> {
+        let mut stack: Vec<&Arc<DynHittable>> = self.objects.iter().rev().collect();
+        while let Some(object) = stack.pop() {
+            if predicate(object) {
+                return Some(object.clone());
+            }
+            stack.extend(object.children().into_iter().rev());
+        }
+        None
+    }
 }
 
 impl Hittable for HittableList {
@@ -121,4 +265,8 @@ impl Hittable for HittableList {
 
         ShapeSpec::List(specs)
     }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        self.objects.iter().collect()
+    }
 }
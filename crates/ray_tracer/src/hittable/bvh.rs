@@ -20,13 +20,30 @@ impl BVHNode {
         Self::from_slice(list.objects_mut())
     }
 
+    /// Unbounded objects such as infinite planes hang off the root, so only
+    /// its box is infinite and the tree below still culls
     pub fn from_slice(objects: &mut [Arc<DynHittable>]) -> Self {
         let mut bbox = AABB::EMPTY;
         for object in objects.iter() {
             bbox = AABB::from_boxes(&bbox, object.bounding_box());
         }
 
-        let (left, right) = if objects.len() == 1 {
+        // Stable, so bounded objects keep their order in front
+        objects.sort_by_key(|object| !object.bounding_box().is_bounded());
+        let bounded = objects
+            .iter()
+            .take_while(|object| object.bounding_box().is_bounded())
+            .count();
+
+        let (left, right) = if bounded > 0 && bounded < objects.len() {
+            let (bounded, unbounded) = objects.split_at_mut(bounded);
+            let left: Arc<DynHittable> = Arc::new(Self::from_slice(bounded));
+            let right: Arc<DynHittable> = match unbounded {
+                [object] => object.clone(),
+                unbounded => Arc::new(Self::from_slice(unbounded)),
+            };
+            (left, right)
+        } else if objects.len() == 1 {
             (objects[0].clone(), objects[0].clone())
         } else if objects.len() == 2 {
             (objects[0].clone(), objects[1].clone())
@@ -78,6 +95,10 @@ impl Hittable for BVHNode {
             right: Box::new(self.right.to_spec(registry)),
         }
     }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        vec![&self.left, &self.right]
+    }
 }
 
 fn box_compare<A: Hittable, B: Hittable>(a: &A, b: &B, axis: Axis) -> Ordering {
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{DynHittable, HitRecord, Hittable},
+    interval::Interval,
+    material::{DynMaterial, Isotropic},
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    texture::DynTexture,
+    vec::Vec3,
+};
+
+/// Volume of uniform density bounded by a closed (convex) hittable
+pub struct ConstantMedium {
+    boundary: Arc<DynHittable>,
+    density: f64,
+    neg_inv_density: f64,
+    phase_function: Arc<DynMaterial>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<DynHittable>,
+        density: f64,
+        phase_function: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        // Free flight distances are drawn with -ln(x) / density
+        anyhow::ensure!(
+            density > 0.0 && density.is_finite(),
+            "medium density must be positive, got {density}"
+        );
+
+        Ok(Self {
+            boundary,
+            density,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        })
+    }
+
+    pub fn from_texture(
+        name: impl Into<String>,
+        boundary: Arc<DynHittable>,
+        density: f64,
+        texture: Arc<DynTexture>,
+    ) -> anyhow::Result<Self> {
+        Self::new(
+            boundary,
+            density,
+            Arc::new(Isotropic::from_texture(name, texture)),
+        )
+    }
+
+    pub fn from_color(
+        name: impl Into<String>,
+        boundary: Arc<DynHittable>,
+        density: f64,
+        albedo: Color,
+    ) -> anyhow::Result<Self> {
+        Self::new(boundary, density, Arc::new(Isotropic::new(name, albedo)))
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        // Find where the ray enters and leaves the boundary, even if the ray
+        // starts inside of it
+        let rec1 = self.boundary.hit(r, Interval::UNIVERSE)?;
+        let rec2 = self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY))?;
+
+        let mut t_enter = rec1.t.max(ray_t.min);
+        let t_exit = rec2.t.min(ray_t.max);
+
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        if t_enter < 0.0 {
+            t_enter = 0.0;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * rand::random::<f64>().ln();
+
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+
+        // Normal and face are arbitrary, the phase function ignores them
+        let mut rec = HitRecord::new(
+            r.at(t),
+            Vec3::new(1.0, 0.0, 0.0),
+            self.phase_function.clone(),
+            t,
+        );
+        rec.front_face = true;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.boundary.bounding_box()
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.phase_function.to_spec(registry);
+        registry.register_material(self.phase_function.name().to_owned(), material_spec);
+
+        ShapeSpec::ConstantMedium {
+            boundary: Box::new(self.boundary.to_spec(registry)),
+            density: self.density,
+            material: self.phase_function.name().to_owned(),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aabb::AABB,
+    hittable::{DynHittable, HitRecord, Hittable, Span},
+    interval::Interval,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// Left with the right carved out of it
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Self::Union => in_left || in_right,
+            Self::Intersection => in_left && in_right,
+            Self::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Boolean combination of two closed shapes. Both children must report their
+/// spans, anything open (quads, planes, uncapped quadrics) is never hit.
+pub struct Csg {
+    op: CsgOp,
+    left: Arc<DynHittable>,
+    right: Arc<DynHittable>,
+    bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: Arc<DynHittable>, right: Arc<DynHittable>) -> Self {
+        let bbox = match op {
+            CsgOp::Union => AABB::from_boxes(left.bounding_box(), right.bounding_box()),
+            // The result never leaves the left shape
+            CsgOp::Intersection | CsgOp::Difference => left.bounding_box().clone(),
+        };
+
+        Self {
+            op,
+            left,
+            right,
+            bbox,
+        }
+    }
+
+    pub fn union(left: Arc<DynHittable>, right: Arc<DynHittable>) -> Self {
+        Self::new(CsgOp::Union, left, right)
+    }
+
+    pub fn intersection(left: Arc<DynHittable>, right: Arc<DynHittable>) -> Self {
+        Self::new(CsgOp::Intersection, left, right)
+    }
+
+    pub fn difference(left: Arc<DynHittable>, right: Arc<DynHittable>) -> Self {
+        Self::new(CsgOp::Difference, left, right)
+    }
+}
+
+/// Boundary crossing of one child along the ray
+struct Event {
+    rec: HitRecord,
+    from_left: bool,
+    entering: bool,
+}
+
+/// Merge the span lists of both children into the spans of `op`
+fn combine(op: CsgOp, left: Vec<Span>, right: Vec<Span>) -> Vec<Span> {
+    let mut events = Vec::with_capacity(2 * (left.len() + right.len()));
+    for (spans, from_left) in [(left, true), (right, false)] {
+        for span in spans {
+            events.push(Event {
+                rec: span.enter,
+                from_left,
+                entering: true,
+            });
+            events.push(Event {
+                rec: span.exit,
+                from_left,
+                entering: false,
+            });
+        }
+    }
+    events.sort_by(|a, b| a.rec.t.total_cmp(&b.rec.t));
+
+    let (mut in_left, mut in_right) = (false, false);
+    let mut enter: Option<HitRecord> = None;
+    let mut spans = Vec::new();
+
+    for event in events {
+        let was_inside = op.inside(in_left, in_right);
+        if event.from_left {
+            in_left = event.entering;
+        } else {
+            in_right = event.entering;
+        }
+        let is_inside = op.inside(in_left, in_right);
+
+        if was_inside == is_inside {
+            continue;
+        }
+
+        let mut rec = event.rec;
+        // The surface of a subtracted shape faces the other way on the result.
+        // The stored normal already opposes the ray, only the side flips.
+        if op == CsgOp::Difference && !event.from_left {
+            rec.front_face = !rec.front_face;
+        }
+
+        if is_inside {
+            enter = Some(rec);
+        } else if let Some(enter) = enter.take() {
+            spans.push(Span { enter, exit: rec });
+        }
+    }
+
+    spans
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return None;
+        }
+
+        self.spans(r)?
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|rec| ray_t.surrounds(rec.t))
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        ShapeSpec::Csg {
+            op: self.op,
+            left: Box::new(self.left.to_spec(registry)),
+            right: Box::new(self.right.to_spec(registry)),
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        let left = self.left.spans(r)?;
+        let right = self.right.spans(r)?;
+
+        Some(combine(self.op, left, right))
+    }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        vec![&self.left, &self.right]
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use image::ImageReader;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, mesh::MeshData},
+    interval::Interval,
+    material::DynMaterial,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Terrain surface sampled from a grayscale heightmap. The image covers
+/// `size_x` by `size_z` starting at `origin`, with image rows running along z,
+/// and black to white maps to `0..height` above the origin.
+///
+/// Every grid cell is split into two triangles and rays walk the grid cells
+/// they cross in order, so the first triangle hit is the closest one.
+pub struct Heightfield {
+    path: PathBuf,
+    origin: Point3,
+    size_x: f64,
+    size_z: f64,
+    height: f64,
+    /// Samples along x and z
+    nx: usize,
+    nz: usize,
+    /// Height above the origin of each sample, x fastest
+    heights: Vec<f64>,
+    /// Lowest and highest sample of each cell, for skipping cells quickly
+    cell_ranges: Vec<Interval>,
+    normals: Vec<Vec3>,
+    mat: Arc<DynMaterial>,
+    bbox: AABB,
+}
+
+impl Heightfield {
+    /// Heights are read at 16 bit precision so DEM exports keep their detail
+    pub fn new(
+        path: impl AsRef<Path>,
+        origin: Point3,
+        size_x: f64,
+        size_z: f64,
+        height: f64,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_owned();
+        anyhow::ensure!(
+            size_x > 0.0 && size_z > 0.0,
+            "heightfield size must be positive, got {size_x} by {size_z}"
+        );
+        let image = ImageReader::open(&path)
+            .with_context(|| format!("Failed to open heightmap: {path:?}"))?
+            .decode()
+            .with_context(|| format!("Failed to decode heightmap: {path:?}"))?
+            .into_luma16();
+
+        let (nx, nz) = (image.width() as usize, image.height() as usize);
+        anyhow::ensure!(
+            nx >= 2 && nz >= 2,
+            "heightmap must be at least 2x2 pixels: {path:?}"
+        );
+
+        let heights = image
+            .pixels()
+            .map(|p| height * p.0[0] as f64 / u16::MAX as f64)
+            .collect();
+
+        Ok(Self::from_heights(
+            path, origin, size_x, size_z, height, nx, nz, heights, mat,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_heights(
+        path: PathBuf,
+        origin: Point3,
+        size_x: f64,
+        size_z: f64,
+        height: f64,
+        nx: usize,
+        nz: usize,
+        heights: Vec<f64>,
+        mat: Arc<DynMaterial>,
+    ) -> Self {
+        let mut field = Self {
+            path,
+            origin,
+            size_x,
+            size_z,
+            height,
+            nx,
+            nz,
+            heights,
+            cell_ranges: Vec::new(),
+            normals: Vec::new(),
+            mat,
+            bbox: AABB::EMPTY,
+        };
+
+        field.cell_ranges = (0..nz - 1)
+            .flat_map(|j| (0..nx - 1).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let corners = [
+                    field.sample(i, j),
+                    field.sample(i + 1, j),
+                    field.sample(i, j + 1),
+                    field.sample(i + 1, j + 1),
+                ];
+                let min = corners.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                Interval::new(min, max)
+            })
+            .collect();
+
+        // Central differences give smooth shading normals at every sample
+        let (dx, dz) = field.cell_size();
+        field.normals = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let run_x = (i1 - i0) as f64 * dx;
+                let run_z = (j1 - j0) as f64 * dz;
+                let slope_x = (field.sample(i1, j) - field.sample(i0, j)) / run_x;
+                let slope_z = (field.sample(i, j1) - field.sample(i, j0)) / run_z;
+                Vec3::new(-slope_x, 1.0, -slope_z).unit_vector()
+            })
+            .collect();
+
+        // A negative height digs below the origin
+        let min_height = field.heights.iter().copied().fold(0.0, f64::min);
+        let max_height = field.heights.iter().copied().fold(0.0, f64::max);
+        field.bbox = AABB::from_points(
+            &field.origin + Vec3::new(0.0, min_height, 0.0),
+            &field.origin + Vec3::new(size_x, max_height, size_z),
+        );
+
+        field
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size_x / (self.nx - 1) as f64,
+            self.size_z / (self.nz - 1) as f64,
+        )
+    }
+
+    fn sample(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.nx + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        let (dx, dz) = self.cell_size();
+        &self.origin + Vec3::new(i as f64 * dx, self.sample(i, j), j as f64 * dz)
+    }
+
+    /// The surface as a triangle mesh, split the same way rays see it
+    pub fn to_mesh(&self) -> MeshData {
+        let index = |i: usize, j: usize| j * self.nx + i;
+        let indices = (0..self.nz - 1)
+            .flat_map(|j| (0..self.nx - 1).map(move |i| (i, j)))
+            .flat_map(|(i, j)| {
+                [
+                    [index(i, j), index(i, j + 1), index(i + 1, j)],
+                    [index(i + 1, j + 1), index(i + 1, j), index(i, j + 1)],
+                ]
+            })
+            .collect();
+
+        let samples = || (0..self.nz).flat_map(|j| (0..self.nx).map(move |i| (i, j)));
+        MeshData {
+            positions: samples().map(|(i, j)| self.vertex(i, j)).collect(),
+            indices,
+            normals: self.normals.clone(),
+            uvs: samples()
+                .map(|(i, j)| {
+                    [
+                        i as f64 / (self.nx - 1) as f64,
+                        j as f64 / (self.nz - 1) as f64,
+                    ]
+                })
+                .collect(),
+            colors: Vec::new(),
+        }
+    }
+
+    /// Closest hit with one of the cell's two triangles inside `ray_t`
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, ray_t: &Interval) -> Option<HitRecord> {
+        let triangles = [
+            [(i, j), (i + 1, j), (i, j + 1)],
+            [(i + 1, j + 1), (i, j + 1), (i + 1, j)],
+        ];
+
+        // Closest hit as (t, triangle, barycentric beta, gamma)
+        let mut closest: Option<(f64, usize, f64, f64)> = None;
+        let mut closest_t = ray_t.max;
+
+        for (triangle, corners) in triangles.iter().enumerate() {
+            let [a, b, c] = corners.map(|(i, j)| self.vertex(i, j));
+            let e1 = &b - &a;
+            let e2 = &c - &a;
+
+            // Möller-Trumbore
+            let pvec = r.direction().cross(&e2);
+            let det = e1.dot(&pvec);
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let inv_det = 1.0 / det;
+
+            let tvec = r.origin() - &a;
+            let beta = tvec.dot(&pvec) * inv_det;
+            if !(0.0..=1.0).contains(&beta) {
+                continue;
+            }
+
+            let qvec = tvec.cross(&e1);
+            let gamma = r.direction().dot(&qvec) * inv_det;
+            if gamma < 0.0 || beta + gamma > 1.0 {
+                continue;
+            }
+
+            let t = e2.dot(&qvec) * inv_det;
+            if !Interval::new(ray_t.min, closest_t).surrounds(t) {
+                continue;
+            }
+
+            closest_t = t;
+            closest = Some((t, triangle, beta, gamma));
+        }
+
+        let (t, triangle, beta, gamma) = closest?;
+        let [a, b, c] = triangles[triangle];
+        let alpha = 1.0 - beta - gamma;
+        let normal = (alpha * &self.normals[a.1 * self.nx + a.0]
+            + beta * &self.normals[b.1 * self.nx + b.0]
+            + gamma * &self.normals[c.1 * self.nx + c.0])
+            .unit_vector();
+
+        let p = r.at(t);
+        let mut rec = HitRecord::new(p.clone(), normal.clone(), self.mat.clone(), t);
+        rec.set_face_normal(r, &normal);
+        rec.u = (p.x() - self.origin.x()) / self.size_x;
+        rec.v = (p.z() - self.origin.z()) / self.size_z;
+
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let range = self.bbox.hit_range(r, ray_t.clone())?;
+
+        let (dx, dz) = self.cell_size();
+        let (cells_x, cells_z) = (self.nx - 1, self.nz - 1);
+        let o = r.origin() - &self.origin;
+        let d = r.direction();
+
+        // Grid coordinates where the ray enters the bounds
+        let entry = &o + range.min * d;
+        let mut i = ((entry.x() / dx).floor().max(0.0) as usize).min(cells_x - 1);
+        let mut j = ((entry.z() / dz).floor().max(0.0) as usize).min(cells_z - 1);
+
+        // Ray parameter at the next cell boundary along x and z, and the step
+        // between boundaries
+        let axis_setup = |origin: f64, dir: f64, cell: usize, size: f64| {
+            if dir.abs() < 1e-12 {
+                (f64::INFINITY, f64::INFINITY)
+            } else {
+                let boundary = if dir > 0.0 { cell + 1 } else { cell } as f64 * size;
+                ((boundary - origin) / dir, size / dir.abs())
+            }
+        };
+        let (mut t_next_x, t_delta_x) = axis_setup(o.x(), d.x(), i, dx);
+        let (mut t_next_z, t_delta_z) = axis_setup(o.z(), d.z(), j, dz);
+
+        let mut t_cell = range.min;
+        loop {
+            let t_exit = t_next_x.min(t_next_z).min(range.max);
+
+            // Only test triangles when the ray's height over the cell overlaps
+            // the cell's heights
+            let (y0, y1) = (o.y() + t_cell * d.y(), o.y() + t_exit * d.y());
+            let cell_range = &self.cell_ranges[j * cells_x + i];
+            if y0.min(y1) <= cell_range.max + 1e-9 && y0.max(y1) >= cell_range.min - 1e-9 {
+                let cell_t = Interval::new(ray_t.min, ray_t.max.min(t_exit + 1e-9));
+                if let Some(rec) = self.hit_cell(r, i, j, &cell_t) {
+                    return Some(rec);
+                }
+            }
+
+            if t_exit >= range.max {
+                return None;
+            }
+
+            if t_next_x < t_next_z {
+                if d.x() > 0.0 {
+                    i += 1;
+                    if i >= cells_x {
+                        return None;
+                    }
+                } else {
+                    i = i.checked_sub(1)?;
+                }
+                t_next_x += t_delta_x;
+            } else {
+                if d.z() > 0.0 {
+                    j += 1;
+                    if j >= cells_z {
+                        return None;
+                    }
+                } else {
+                    j = j.checked_sub(1)?;
+                }
+                t_next_z += t_delta_z;
+            }
+            t_cell = t_exit;
+        }
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        ShapeSpec::Heightfield {
+            path: self.path.clone(),
+            origin: self.origin.clone(),
+            size_x: self.size_x,
+            size_z: self.size_z,
+            height: self.height,
+            material: self.mat.name().to_owned(),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    import::{ply, stl},
+    interval::Interval,
+    material::DynMaterial,
+    matrix::Affine,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Triangles most leaves of a mesh's BVH hold
+const LEAF_SIZE: usize = 4;
+
+/// Indexed triangles with optional per-vertex normals, texture coordinates
+/// and colors
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    /// Vertex indices of each triangle, counter-clockwise seen from the front
+    pub indices: Vec<[usize; 3]>,
+    /// One per position when present
+    pub normals: Vec<Vec3>,
+    /// One per position when present
+    pub uvs: Vec<[f64; 2]>,
+    /// One per position when present, tinting the material
+    pub colors: Vec<Color>,
+}
+
+impl MeshData {
+    /// Check that every index and attribute refers to an existing vertex
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let count = self.positions.len();
+        if let Some(index) = self.indices.iter().flatten().find(|&&i| i >= count) {
+            anyhow::bail!("mesh index {index} is out of range for {count} vertices");
+        }
+        anyhow::ensure!(
+            self.normals.is_empty() || self.normals.len() == count,
+            "mesh has {} normals for {count} vertices",
+            self.normals.len()
+        );
+        anyhow::ensure!(
+            self.uvs.is_empty() || self.uvs.len() == count,
+            "mesh has {} uvs for {count} vertices",
+            self.uvs.len()
+        );
+        anyhow::ensure!(
+            self.colors.is_empty() || self.colors.len() == count,
+            "mesh has {} colors for {count} vertices",
+            self.colors.len()
+        );
+
+        Ok(())
+    }
+
+    /// Move the mesh by `affine`, flipping the winding when it mirrors so
+    /// the same faces stay in front. Normals can't follow a transform that
+    /// flattens the mesh and are dropped.
+    pub fn transform(&mut self, affine: &Affine) {
+        for position in &mut self.positions {
+            *position = affine.transform_point(position);
+        }
+        match affine.linear.inverse() {
+            Some(inverse) => {
+                let normal_matrix = inverse.transpose();
+                for normal in &mut self.normals {
+                    *normal = (&normal_matrix * &*normal).unit_vector();
+                }
+            }
+            None => self.normals.clear(),
+        }
+        if affine.linear.determinant() < 0.0 {
+            self.reverse();
+        }
+    }
+
+    /// Turn the triangles around by flipping their winding
+    pub fn reverse(&mut self) {
+        for [_, b, c] in &mut self.indices {
+            std::mem::swap(b, c);
+        }
+    }
+}
+
+/// Where a mesh's data came from, so it is written back the same way
+enum MeshSource {
+    Inline,
+    Ply(PathBuf),
+    Stl(PathBuf),
+}
+
+enum Node {
+    Leaf {
+        bbox: AABB,
+        start: usize,
+        end: usize,
+    },
+    Inner {
+        bbox: AABB,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bbox(&self) -> &AABB {
+        match self {
+            Self::Leaf { bbox, .. } | Self::Inner { bbox, .. } => bbox,
+        }
+    }
+}
+
+/// Triangle mesh with its own BVH over the triangles, so large meshes don't
+/// need a hittable per triangle.
+pub struct TriangleMesh {
+    data: MeshData,
+    source: MeshSource,
+    /// Triangle indices ordered so every leaf holds a contiguous range
+    order: Vec<usize>,
+    nodes: Vec<Node>,
+    mat: Arc<DynMaterial>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData, mat: Arc<DynMaterial>) -> anyhow::Result<Self> {
+        Self::with_source(data, MeshSource::Inline, mat)
+    }
+
+    /// Load a PLY file, see [`ply::read`]
+    pub fn from_ply(path: impl AsRef<Path>, mat: Arc<DynMaterial>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = ply::read(path)?;
+        Self::with_source(data, MeshSource::Ply(path.to_owned()), mat)
+    }
+
+    /// Load an STL file, see [`stl::read`]
+    pub fn from_stl(path: impl AsRef<Path>, mat: Arc<DynMaterial>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = stl::read(path)?;
+        Self::with_source(data, MeshSource::Stl(path.to_owned()), mat)
+    }
+
+    fn with_source(
+        data: MeshData,
+        source: MeshSource,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        data.validate()?;
+        anyhow::ensure!(!data.indices.is_empty(), "mesh has no triangles");
+
+        let mut mesh = Self {
+            order: (0..data.indices.len()).collect(),
+            data,
+            source,
+            nodes: Vec::new(),
+            mat,
+        };
+        let count = mesh.order.len();
+        mesh.build(0, count);
+
+        Ok(mesh)
+    }
+
+    fn corners(&self, triangle: usize) -> [&Point3; 3] {
+        self.data.indices[triangle].map(|i| &self.data.positions[i])
+    }
+
+    fn triangle_box(&self, triangle: usize) -> AABB {
+        let [a, b, c] = self.corners(triangle);
+        AABB::from_boxes(
+            &AABB::from_points(a.clone(), b.clone()),
+            &AABB::from_points(c.clone(), c.clone()),
+        )
+    }
+
+    /// Build the subtree over `order[start..end]` and return its node index
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let bbox = self.order[start..end].iter().fold(AABB::EMPTY, |bbox, &t| {
+            AABB::from_boxes(&bbox, &self.triangle_box(t))
+        });
+
+        if end - start <= LEAF_SIZE {
+            self.nodes.push(Node::Leaf { bbox, start, end });
+            return self.nodes.len() - 1;
+        }
+
+        // Split at the median centroid along the longest axis
+        let axis = bbox.longest_axis();
+        let centroid = |mesh: &Self, t: usize| {
+            let [a, b, c] = mesh.corners(t);
+            a[axis] + b[axis] + c[axis]
+        };
+        let mid = (start + end) / 2;
+        let mut range = std::mem::take(&mut self.order);
+        range[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+            centroid(self, a).total_cmp(&centroid(self, b))
+        });
+        self.order = range;
+
+        // Reserve this node's slot so the root ends up at index 0
+        self.nodes.push(Node::Leaf {
+            bbox: AABB::EMPTY,
+            start,
+            end,
+        });
+        let index = self.nodes.len() - 1;
+        let left = self.build(start, mid);
+        let right = self.build(mid, end);
+        self.nodes[index] = Node::Inner { bbox, left, right };
+
+        index
+    }
+
+    /// Möller-Trumbore, returning t and the barycentric coordinates of b and c
+    fn hit_triangle(&self, r: &Ray, triangle: usize, ray_t: &Interval) -> Option<(f64, f64, f64)> {
+        let [a, b, c] = self.corners(triangle);
+        let e1 = b - a;
+        let e2 = c - a;
+
+        let pvec = r.direction().cross(&e2);
+        let det = e1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin() - a;
+        let beta = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&e1);
+        let gamma = r.direction().dot(&qvec) * inv_det;
+        if gamma < 0.0 || beta + gamma > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(&qvec) * inv_det;
+        ray_t.surrounds(t).then_some((t, beta, gamma))
+    }
+
+    fn record(&self, r: &Ray, triangle: usize, t: f64, beta: f64, gamma: f64) -> HitRecord {
+        let [ia, ib, ic] = self.data.indices[triangle];
+        let [a, b, c] = self.corners(triangle);
+        let alpha = 1.0 - beta - gamma;
+
+        // The face is decided by the flat normal, smooth normals only shade
+        let geometric = (b - a).cross(&(c - a)).unit_vector();
+        let mut rec = HitRecord::new(r.at(t), geometric.clone(), self.mat.clone(), t);
+        rec.set_face_normal(r, &geometric);
+
+        if !self.data.normals.is_empty() {
+            let n = &self.data.normals;
+            let shading = (alpha * &n[ia] + beta * &n[ib] + gamma * &n[ic]).unit_vector();
+            rec.normal = if rec.front_face { shading } else { -shading };
+        }
+
+        (rec.u, rec.v) = if self.data.uvs.is_empty() {
+            (beta, gamma)
+        } else {
+            let uv = &self.data.uvs;
+            (
+                alpha * uv[ia][0] + beta * uv[ib][0] + gamma * uv[ic][0],
+                alpha * uv[ia][1] + beta * uv[ib][1] + gamma * uv[ic][1],
+            )
+        };
+
+        if !self.data.colors.is_empty() {
+            let c = &self.data.colors;
+            rec.color = Some(alpha * &c[ia] + beta * &c[ib] + gamma * &c[ic]);
+        }
+
+        rec
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut closest: Option<(usize, f64, f64, f64)> = None;
+        let mut closest_t = ray_t.max;
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bbox().hit(r, Interval::new(ray_t.min, closest_t)) {
+                continue;
+            }
+
+            match node {
+                Node::Leaf { start, end, .. } => {
+                    for &triangle in &self.order[*start..*end] {
+                        let range = Interval::new(ray_t.min, closest_t);
+                        if let Some((t, beta, gamma)) = self.hit_triangle(r, triangle, &range) {
+                            closest_t = t;
+                            closest = Some((triangle, t, beta, gamma));
+                        }
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+
+        let (triangle, t, beta, gamma) = closest?;
+        Some(self.record(r, triangle, t, beta, gamma))
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.nodes[0].bbox()
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+        let material = self.mat.name().to_owned();
+
+        match &self.source {
+            MeshSource::Inline => ShapeSpec::Mesh {
+                positions: self.data.positions.clone(),
+                indices: self.data.indices.clone(),
+                normals: self.data.normals.clone(),
+                uvs: self.data.uvs.clone(),
+                colors: self.data.colors.clone(),
+                material,
+            },
+            MeshSource::Ply(path) => ShapeSpec::Ply {
+                path: path.clone(),
+                material,
+            },
+            MeshSource::Stl(path) => ShapeSpec::Stl {
+                path: path.clone(),
+                material,
+            },
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{DynHittable, HitRecord, Hittable, Span, transform::transform_box},
+    interval::Interval,
+    matrix::Affine,
+    ray::Ray,
+    scene_loader::{Keyframe, ResourceRegistry, ShapeSpec, TransformSpec},
+};
+
+/// Transforms sampled between consecutive keyframes when bounding the motion,
+/// rotations can sweep outside the boxes at the keyframes themselves. The
+/// boxes are padded by how far the motion strays between the samples.
+const BOUNDS_STEPS: usize = 16;
+
+/// Hittable whose transform changes over the shutter interval. The transform
+/// is interpolated between the keyframes around the ray's time and held at the
+/// first and last keyframe outside of them.
+pub struct MotionTransform {
+    object: Arc<DynHittable>,
+    keyframes: Vec<Keyframe>,
+    bbox: AABB,
+}
+
+impl MotionTransform {
+    /// Fails if `keyframes` is empty or the scale is zero on some axis at or
+    /// between keyframes
+    pub fn new(object: Arc<DynHittable>, mut keyframes: Vec<Keyframe>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!keyframes.is_empty(), "motion needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        for keyframe in &keyframes {
+            anyhow::ensure!(
+                keyframe.transform.blends_invertibly(&keyframe.transform),
+                "motion scale must be non-zero on every axis, keyframe at {}",
+                keyframe.time
+            );
+        }
+        for pair in keyframes.windows(2) {
+            anyhow::ensure!(
+                pair[0].transform.blends_invertibly(&pair[1].transform),
+                "motion scale changes sign between the keyframes at {} and {}, passing through zero",
+                pair[0].time,
+                pair[1].time
+            );
+        }
+
+        let mut motion = Self {
+            object,
+            keyframes,
+            bbox: AABB::EMPTY,
+        };
+
+        let mut bbox = transform_box(
+            motion.object.bounding_box(),
+            &motion.keyframes[0].transform.affine(),
+        );
+        for pair in motion.keyframes.windows(2) {
+            let (from, to) = (&pair[0].transform, &pair[1].transform);
+            let mut swept = AABB::EMPTY;
+            for step in 0..=BOUNDS_STEPS {
+                let t = step as f64 / BOUNDS_STEPS as f64;
+                let affine = from.interpolate(to, t);
+                swept = AABB::from_boxes(
+                    &swept,
+                    &transform_box(motion.object.bounding_box(), &affine),
+                );
+            }
+
+            // Widths grow by the error on both sides
+            let pad = 2.0 * from.chord_error(to, motion.object.bounding_box(), BOUNDS_STEPS);
+            let swept = AABB::new(
+                swept.x.expand(pad),
+                swept.y.expand(pad),
+                swept.z.expand(pad),
+            );
+            bbox = AABB::from_boxes(&bbox, &swept);
+        }
+        motion.bbox = bbox;
+
+        Ok(motion)
+    }
+
+    /// Move linearly from `from` at time 0 to `to` at time 1
+    pub fn linear(
+        object: Arc<DynHittable>,
+        from: TransformSpec,
+        to: TransformSpec,
+    ) -> anyhow::Result<Self> {
+        Self::new(
+            object,
+            vec![
+                Keyframe {
+                    time: 0.0,
+                    transform: from,
+                },
+                Keyframe {
+                    time: 1.0,
+                    transform: to,
+                },
+            ],
+        )
+    }
+
+    fn affine_at(&self, time: f64) -> Affine {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0].transform.affine();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].transform.affine();
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        a.transform.interpolate(&b.transform, t)
+    }
+
+    /// Object space ray and a function moving object space hits back out,
+    /// `None` where the transform is too close to singular to invert
+    fn localize(&self, r: &Ray) -> Option<(Ray, impl Fn(HitRecord) -> HitRecord)> {
+        let to_world = self.affine_at(r.time());
+        let to_object = to_world.inverse()?;
+        let normal_matrix = to_object.linear.transpose();
+
+        // Keep the direction unnormalized so t is the same in both spaces
+        let object_ray = Ray::new_with_time(
+            to_object.transform_point(r.origin()),
+            to_object.transform_vector(r.direction()),
+            r.time(),
+        );
+
+        let to_world_record = move |mut rec: HitRecord| {
+            rec.p = to_world.transform_point(&rec.p);
+            rec.normal = (&normal_matrix * &rec.normal).unit_vector();
+            rec
+        };
+
+        Some((object_ray, to_world_record))
+    }
+}
+
+impl Hittable for MotionTransform {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return None;
+        }
+
+        let (object_ray, to_world) = self.localize(r)?;
+        self.object.hit(&object_ray, ray_t).map(to_world)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        ShapeSpec::Motion {
+            shape: Box::new(self.object.to_spec(registry)),
+            keyframes: self.keyframes.clone(),
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        let (object_ray, to_world) = self.localize(r)?;
+        let spans = self.object.spans(&object_ray)?;
+
+        Some(
+            spans
+                .into_iter()
+                .map(|span| Span {
+                    enter: to_world(span.enter),
+                    exit: to_world(span.exit),
+                })
+                .collect(),
+        )
+    }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        vec![&self.object]
+    }
+}
@@ -0,0 +1,104 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{DynHittable, HitRecord, Hittable, Span},
+    interval::Interval,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+};
+
+/// Identifier of a named object, derived from its name alone so the same
+/// object gets the same id on every run and in every tool reading the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub u64);
+
+impl ObjectId {
+    /// 64 bit FNV-1a of the name, unlike `DefaultHasher` it is fixed across
+    /// Rust versions
+    pub fn from_name(name: &str) -> Self {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        Self(name.bytes().fold(OFFSET, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        }))
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Gives a shape a name that hits on it report through [`HitRecord::object`].
+/// Names nest, a hit keeps the id of the innermost named object.
+pub struct Named {
+    name: String,
+    id: ObjectId,
+    object: Arc<DynHittable>,
+}
+
+impl Named {
+    pub fn new(name: impl Into<String>, object: Arc<DynHittable>) -> Self {
+        let name = name.into();
+        Self {
+            id: ObjectId::from_name(&name),
+            name,
+            object,
+        }
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    pub fn object(&self) -> &Arc<DynHittable> {
+        &self.object
+    }
+
+    fn tag(&self, mut rec: HitRecord) -> HitRecord {
+        rec.object.get_or_insert(self.id);
+        rec
+    }
+}
+
+impl Hittable for Named {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.object.hit(r, ray_t).map(|rec| self.tag(rec))
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.object.bounding_box()
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        ShapeSpec::Named {
+            name: self.name.clone(),
+            shape: Box::new(self.object.to_spec(registry)),
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        let spans = self.object.spans(r)?;
+
+        Some(
+            spans
+                .into_iter()
+                .map(|span| Span {
+                    enter: self.tag(span.enter),
+                    exit: self.tag(span.exit),
+                })
+                .collect(),
+        )
+    }
+
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        vec![&self.object]
+    }
+}
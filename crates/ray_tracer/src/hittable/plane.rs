@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::DynMaterial,
+    onb::Onb,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Infinite plane through `point` facing `normal`. Its bounding box is
+/// unbounded so it should be kept out of BVH trees, see
+/// [`HittableList::into_bvh`](crate::hittable::HittableList::into_bvh).
+pub struct Plane {
+    point: Point3,
+    /// As given, written back unchanged
+    normal: Vec3,
+    unit_normal: Vec3,
+    basis: Onb,
+    d: f64,
+    mat: Arc<DynMaterial>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<DynMaterial>) -> Self {
+        let unit_normal = normal.unit_vector();
+        let basis = Onb::new(&unit_normal);
+        let d = unit_normal.dot(&point);
+
+        Self {
+            point,
+            normal,
+            unit_normal,
+            basis,
+            d,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let denom = self.unit_normal.dot(r.direction());
+
+        // no hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.unit_normal.dot(r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        // Tile the texture once per world unit along the plane
+        let planar = &p - &self.point;
+        let u = planar.dot(self.basis.u()).rem_euclid(1.0);
+        let v = planar.dot(self.basis.v()).rem_euclid(1.0);
+
+        let mut rec = HitRecord::new(p, self.unit_normal.clone(), self.mat.clone(), t);
+        rec.set_face_normal(r, &self.unit_normal);
+        rec.u = u;
+        rec.v = v;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &AABB::UNIVERSE
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        ShapeSpec::Plane {
+            point: self.point.clone(),
+            normal: self.normal.clone(),
+            material: self.mat.name().to_owned(),
+        }
+    }
+}
@@ -2,19 +2,40 @@ use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, HittableList, Span, collect_spans},
     interval::Interval,
     material::DynMaterial,
+    onb::Onb,
     ray::Ray,
     scene_loader::{ResourceRegistry, ShapeSpec},
     vec::{Point3, Vec3},
 };
 
+/// Region of the plane spanned by `u` and `v` that belongs to a [`Quad`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanarShape {
+    /// Corner at `q` with edges `u` and `v`
+    Parallelogram,
+    /// Corner at `q` with edges `u` and `v`
+    Triangle,
+    /// Centered at `q` with semi-axes `u` and `v`
+    Ellipse,
+    /// Ellipse with equal semi-axes around `normal`, which remembers the
+    /// normal and radius it was made from
+    Disk { normal: Vec3, radius: f64 },
+    /// Ellipse with a hole, `inner` is the hole's size as a fraction of the
+    /// outer semi-axes
+    Annulus { inner: f64 },
+}
+
+/// Planar primitive. The plane is spanned by `u` and `v` starting at `q` and
+/// `shape` decides which part of it is solid.
 pub struct Quad {
     q: Point3,
     u: Vec3,
     v: Vec3,
     w: Vec3,
+    shape: PlanarShape,
     mat: Arc<DynMaterial>,
     bbox: AABB,
     normal: Vec3,
@@ -23,9 +44,58 @@ pub struct Quad {
 
 impl Quad {
     pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<DynMaterial>) -> Self {
-        let bbox_diagonal_1 = AABB::from_points(q.clone(), &q + (&u + &v));
-        let bbox_diagonal_2 = AABB::from_points(&q + &u, &q + &v);
-        let bbox = AABB::from_boxes(&bbox_diagonal_1, &bbox_diagonal_2);
+        Self::with_shape(q, u, v, PlanarShape::Parallelogram, mat)
+    }
+
+    pub fn triangle(q: Point3, u: Vec3, v: Vec3, mat: Arc<DynMaterial>) -> Self {
+        Self::with_shape(q, u, v, PlanarShape::Triangle, mat)
+    }
+
+    pub fn ellipse(center: Point3, u: Vec3, v: Vec3, mat: Arc<DynMaterial>) -> Self {
+        Self::with_shape(center, u, v, PlanarShape::Ellipse, mat)
+    }
+
+    /// Circular ellipse facing `normal`
+    pub fn disk(center: Point3, normal: &Vec3, radius: f64, mat: Arc<DynMaterial>) -> Self {
+        let basis = Onb::new(normal);
+        let (u, v) = (radius * basis.u(), radius * basis.v());
+        let shape = PlanarShape::Disk {
+            normal: normal.clone(),
+            radius,
+        };
+        Self::with_shape(center, u, v, shape, mat)
+    }
+
+    pub fn annulus(center: Point3, u: Vec3, v: Vec3, inner: f64, mat: Arc<DynMaterial>) -> Self {
+        let inner = inner.clamp(0.0, 1.0);
+        Self::with_shape(center, u, v, PlanarShape::Annulus { inner }, mat)
+    }
+
+    pub fn with_shape(
+        q: Point3,
+        u: Vec3,
+        v: Vec3,
+        shape: PlanarShape,
+        mat: Arc<DynMaterial>,
+    ) -> Self {
+        let bbox = match &shape {
+            PlanarShape::Parallelogram => {
+                let bbox_diagonal_1 = AABB::from_points(q.clone(), &q + (&u + &v));
+                let bbox_diagonal_2 = AABB::from_points(&q + &u, &q + &v);
+                AABB::from_boxes(&bbox_diagonal_1, &bbox_diagonal_2)
+            }
+            // Only the corners at q, q + u and q + v
+            PlanarShape::Triangle => {
+                let bbox_edge_u = AABB::from_points(q.clone(), &q + &u);
+                let bbox_edge_v = AABB::from_points(q.clone(), &q + &v);
+                AABB::from_boxes(&bbox_edge_u, &bbox_edge_v)
+            }
+            PlanarShape::Ellipse | PlanarShape::Disk { .. } | PlanarShape::Annulus { .. } => {
+                let bbox_diagonal_1 = AABB::from_points(&q - (&u + &v), &q + (&u + &v));
+                let bbox_diagonal_2 = AABB::from_points(&q + (&u - &v), &q - (&u - &v));
+                AABB::from_boxes(&bbox_diagonal_1, &bbox_diagonal_2)
+            }
+        };
 
         let n = u.cross(&v);
         let normal = n.unit_vector();
@@ -37,6 +107,7 @@ impl Quad {
             u,
             v,
             w,
+            shape,
             mat,
             bbox,
             normal,
@@ -44,14 +115,35 @@ impl Quad {
         }
     }
 
-    fn is_interior(a: f64, b: f64) -> Option<(f64, f64)> {
+    fn is_interior(&self, a: f64, b: f64) -> Option<(f64, f64)> {
         let unit_interval = Interval::new(0.0, 1.0);
 
         // Given the hit point in plane coordinates, return None if it is outside
         // the primitive, otherwise return the UV coordinates
 
-        if !unit_interval.contains(a) || !unit_interval.contains(b) {
-            return None;
+        match &self.shape {
+            PlanarShape::Parallelogram => {
+                if !unit_interval.contains(a) || !unit_interval.contains(b) {
+                    return None;
+                }
+            }
+            PlanarShape::Triangle => {
+                if a < 0.0 || b < 0.0 || a + b > 1.0 {
+                    return None;
+                }
+            }
+            PlanarShape::Ellipse | PlanarShape::Disk { .. } | PlanarShape::Annulus { .. } => {
+                let inner = match self.shape {
+                    PlanarShape::Annulus { inner } => inner,
+                    _ => 0.0,
+                };
+                let r2 = a * a + b * b;
+                if r2 > 1.0 || r2 < inner * inner {
+                    return None;
+                }
+
+                return Some((0.5 * a + 0.5, 0.5 * b + 0.5));
+            }
         }
 
         Some((a, b))
@@ -80,7 +172,7 @@ impl Hittable for Quad {
         let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
 
         let mut rec = HitRecord::new(intersection, self.normal.clone(), self.mat.clone(), t);
-        let (u, v) = Self::is_interior(alpha, beta)?;
+        let (u, v) = self.is_interior(alpha, beta)?;
         rec.u = u;
         rec.v = v;
 
@@ -97,11 +189,106 @@ impl Hittable for Quad {
         let material_spec = self.mat.to_spec(registry);
         registry.register_material(self.mat.name().to_owned(), material_spec);
 
-        ShapeSpec::Quad {
-            q: self.q.clone(),
-            u: self.u.clone(),
-            v: self.v.clone(),
+        let q = self.q.clone();
+        let u = self.u.clone();
+        let v = self.v.clone();
+        let material = self.mat.name().to_owned();
+
+        match &self.shape {
+            PlanarShape::Parallelogram => ShapeSpec::Quad { q, u, v, material },
+            PlanarShape::Triangle => ShapeSpec::Triangle { q, u, v, material },
+            PlanarShape::Ellipse => ShapeSpec::Ellipse {
+                center: q,
+                u,
+                v,
+                material,
+            },
+            PlanarShape::Disk { normal, radius } => ShapeSpec::Disk {
+                center: q,
+                normal: normal.clone(),
+                radius: *radius,
+                material,
+            },
+            PlanarShape::Annulus { inner } => ShapeSpec::Annulus {
+                center: q,
+                u,
+                v,
+                inner: *inner,
+                material,
+            },
+        }
+    }
+}
+
+/// Closed axis aligned box made of six quads
+pub struct QuadBox {
+    /// Corners as given, for writing the box back out
+    a: Point3,
+    b: Point3,
+    mat: Arc<DynMaterial>,
+    sides: HittableList,
+}
+
+/// Returns the 3D box that contains the two opposite vertices a & b
+pub fn make_box(a: &Point3, b: &Point3, mat: Arc<DynMaterial>) -> QuadBox {
+    let mut sides = HittableList::default();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let sides_spec = [
+        // front
+        (Point3::new(min.x(), min.y(), max.z()), &dx, &dy),
+        // right
+        (Point3::new(max.x(), min.y(), max.z()), &-&dz, &dy),
+        // back
+        (Point3::new(max.x(), min.y(), min.z()), &-&dx, &dy),
+        // left
+        (Point3::new(min.x(), min.y(), min.z()), &dz, &dy),
+        // top
+        (Point3::new(min.x(), max.y(), max.z()), &dx, &-&dz),
+        // bottom
+        (Point3::new(min.x(), min.y(), min.z()), &dx, &dz),
+    ];
+
+    for (q, u, v) in sides_spec {
+        sides.add(Arc::new(Quad::new(q, u.clone(), v.clone(), mat.clone())));
+    }
+
+    QuadBox {
+        a: a.clone(),
+        b: b.clone(),
+        mat,
+        sides,
+    }
+}
+
+impl Hittable for QuadBox {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.sides.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.sides.bounding_box()
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        ShapeSpec::Box {
+            a: self.a.clone(),
+            b: self.b.clone(),
             material: self.mat.name().to_owned(),
         }
     }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        Some(collect_spans(self, r))
+    }
 }
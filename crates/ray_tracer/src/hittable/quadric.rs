@@ -0,0 +1,360 @@
+use std::{f64, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, Span, collect_spans},
+    interval::Interval,
+    material::DynMaterial,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Surface of revolution around the y axis described by a quadratic equation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuadricShape {
+    Cylinder,
+    /// Apex at the top
+    Cone,
+    /// Vertex at the bottom, `radius` wide at the top
+    Paraboloid,
+    /// Hyperboloid of one sheet, `radius` wide at its waist half way up and
+    /// `end_radius` wide at the top and bottom
+    Hyperboloid {
+        end_radius: f64,
+    },
+}
+
+/// Cones, paraboloids and hyperboloids divide by both, so neither may be
+/// zero
+pub(crate) fn check_size(radius: f64, height: f64) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        radius > 0.0 && height > 0.0,
+        "quadric radius and height must be positive, got radius {radius} and height {height}"
+    );
+    Ok(())
+}
+
+/// Quadric standing upright on `center`, spanning `height` along the y axis.
+/// Use a transform to orient it differently.
+pub struct Quadric {
+    center: Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    shape: QuadricShape,
+    mat: Arc<DynMaterial>,
+    bbox: AABB,
+}
+
+impl Quadric {
+    pub fn cylinder(
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        Self::new(center, radius, height, capped, QuadricShape::Cylinder, mat)
+    }
+
+    pub fn cone(
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        Self::new(center, radius, height, capped, QuadricShape::Cone, mat)
+    }
+
+    pub fn paraboloid(
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        Self::new(
+            center,
+            radius,
+            height,
+            capped,
+            QuadricShape::Paraboloid,
+            mat,
+        )
+    }
+
+    /// `end_radius` is clamped so it is always wider than the waist
+    pub fn hyperboloid(
+        center: Point3,
+        radius: f64,
+        end_radius: f64,
+        height: f64,
+        capped: bool,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        let end_radius = end_radius.max(radius + 1e-6);
+        let shape = QuadricShape::Hyperboloid { end_radius };
+        Self::new(center, radius, height, capped, shape, mat)
+    }
+
+    fn new(
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        shape: QuadricShape,
+        mat: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        check_size(radius, height)?;
+
+        let extent = match shape {
+            QuadricShape::Hyperboloid { end_radius } => end_radius,
+            _ => radius,
+        };
+        let bbox = AABB::from_points(
+            &center - Vec3::new(extent, 0.0, extent),
+            &center + Vec3::new(extent, height, extent),
+        );
+
+        Ok(Self {
+            center,
+            radius,
+            height,
+            capped,
+            shape,
+            mat,
+            bbox,
+        })
+    }
+
+    /// Coefficients of `a t^2 + b t + c = 0` for the lateral surface along a
+    /// ray starting at `o` (relative to the base center) with direction `d`
+    fn coefficients(&self, o: &Vec3, d: &Vec3) -> (f64, f64, f64) {
+        let (r, h) = (self.radius, self.height);
+        let radial_dd = d.x() * d.x() + d.z() * d.z();
+        let radial_od = o.x() * d.x() + o.z() * d.z();
+        let radial_oo = o.x() * o.x() + o.z() * o.z();
+
+        match self.shape {
+            QuadricShape::Cylinder => (radial_dd, 2.0 * radial_od, radial_oo - r * r),
+            QuadricShape::Cone => {
+                // radius shrinks linearly from r at the base to 0 at the apex
+                let k = r / h;
+                let s0 = r - k * o.y();
+                let s1 = -k * d.y();
+                (
+                    radial_dd - s1 * s1,
+                    2.0 * (radial_od - s0 * s1),
+                    radial_oo - s0 * s0,
+                )
+            }
+            QuadricShape::Paraboloid => {
+                let k = r * r / h;
+                (
+                    radial_dd,
+                    2.0 * radial_od - k * d.y(),
+                    radial_oo - k * o.y(),
+                )
+            }
+            QuadricShape::Hyperboloid { .. } => {
+                let (inv_a2, inv_c2) = self.hyperboloid_terms();
+                let oy = o.y() - 0.5 * h;
+                (
+                    radial_dd * inv_a2 - d.y() * d.y() * inv_c2,
+                    2.0 * (radial_od * inv_a2 - oy * d.y() * inv_c2),
+                    radial_oo * inv_a2 - oy * oy * inv_c2 - 1.0,
+                )
+            }
+        }
+    }
+
+    /// `1/a^2` and `1/c^2` of `(x^2 + z^2)/a^2 - y^2/c^2 = 1`
+    fn hyperboloid_terms(&self) -> (f64, f64) {
+        let QuadricShape::Hyperboloid { end_radius } = self.shape else {
+            unreachable!()
+        };
+        let a2 = self.radius * self.radius;
+        let half_h = 0.5 * self.height;
+        let c2 = half_h * half_h * a2 / (end_radius * end_radius - a2);
+        (1.0 / a2, 1.0 / c2)
+    }
+
+    /// Outward normal of the lateral surface at local point `p`
+    fn lateral_normal(&self, p: &Vec3) -> Vec3 {
+        let (r, h) = (self.radius, self.height);
+        let n = match self.shape {
+            QuadricShape::Cylinder => Vec3::new(p.x(), 0.0, p.z()),
+            QuadricShape::Cone => {
+                let k = r / h;
+                Vec3::new(p.x(), k * (r - k * p.y()), p.z())
+            }
+            QuadricShape::Paraboloid => Vec3::new(p.x(), -0.5 * r * r / h, p.z()),
+            QuadricShape::Hyperboloid { .. } => {
+                let (inv_a2, inv_c2) = self.hyperboloid_terms();
+                Vec3::new(p.x() * inv_a2, -(p.y() - 0.5 * h) * inv_c2, p.z() * inv_a2)
+            }
+        };
+
+        n.unit_vector()
+    }
+
+    /// Caps as (height, radius, outward normal y)
+    fn caps(&self) -> Vec<(f64, f64, f64)> {
+        if !self.capped {
+            return Vec::new();
+        }
+
+        match self.shape {
+            QuadricShape::Cylinder => {
+                vec![(0.0, self.radius, -1.0), (self.height, self.radius, 1.0)]
+            }
+            QuadricShape::Cone => vec![(0.0, self.radius, -1.0)],
+            QuadricShape::Paraboloid => vec![(self.height, self.radius, 1.0)],
+            QuadricShape::Hyperboloid { end_radius } => {
+                vec![(0.0, end_radius, -1.0), (self.height, end_radius, 1.0)]
+            }
+        }
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let o = r.origin() - &self.center;
+        let d = r.direction();
+
+        // Closest hit as (t, local point, outward normal, uv)
+        let mut closest: Option<(f64, Vec3, Vec3, (f64, f64))> = None;
+        let mut closest_t = ray_t.max;
+
+        let (a, b, c) = self.coefficients(&o, d);
+        for t in solve_quadratic(a, b, c) {
+            if !Interval::new(ray_t.min, closest_t).surrounds(t) {
+                continue;
+            }
+
+            let p = &o + t * d;
+            if !(0.0..=self.height).contains(&p.y()) {
+                continue;
+            }
+
+            let u = (f64::atan2(-p.z(), p.x()) + f64::consts::PI) / (2.0 * f64::consts::PI);
+            let v = p.y() / self.height;
+            let normal = self.lateral_normal(&p);
+            closest_t = t;
+            closest = Some((t, p, normal, (u, v)));
+        }
+
+        for (y, radius, normal_y) in self.caps() {
+            if d.y().abs() < 1e-12 {
+                continue;
+            }
+
+            let t = (y - o.y()) / d.y();
+            if !Interval::new(ray_t.min, closest_t).surrounds(t) {
+                continue;
+            }
+
+            let p = &o + t * d;
+            if p.x() * p.x() + p.z() * p.z() > radius * radius {
+                continue;
+            }
+
+            let uv = (
+                0.5 + 0.5 * p.x() / radius,
+                0.5 + 0.5 * normal_y * p.z() / radius,
+            );
+            closest_t = t;
+            closest = Some((t, p, Vec3::new(0.0, normal_y, 0.0), uv));
+        }
+
+        let (t, p, outward_normal, (u, v)) = closest?;
+        let mut rec = HitRecord::new(
+            &p + &self.center,
+            outward_normal.clone(),
+            self.mat.clone(),
+            t,
+        );
+        rec.set_face_normal(r, &outward_normal);
+        rec.u = u;
+        rec.v = v;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        let center = self.center.clone();
+        let material = self.mat.name().to_owned();
+        let (radius, height, capped) = (self.radius, self.height, self.capped);
+
+        match self.shape {
+            QuadricShape::Cylinder => ShapeSpec::Cylinder {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            },
+            QuadricShape::Cone => ShapeSpec::Cone {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            },
+            QuadricShape::Paraboloid => ShapeSpec::Paraboloid {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            },
+            QuadricShape::Hyperboloid { end_radius } => ShapeSpec::Hyperboloid {
+                center,
+                radius,
+                end_radius,
+                height,
+                capped,
+                material,
+            },
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        if !self.capped {
+            return None;
+        }
+
+        Some(collect_spans(self, r))
+    }
+}
+
+/// Real roots of `a t^2 + b t + c = 0` in ascending order
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return Vec::new();
+        }
+        return vec![-c / b];
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    // Numerically stable form that avoids cancellation
+    let sqrtd = discriminant.sqrt();
+    let q = -0.5 * (b + b.signum() * sqrtd);
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    if t0 <= t1 { vec![t0, t1] } else { vec![t1, t0] }
+}
@@ -0,0 +1,447 @@
+use std::{f64, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, Span, collect_spans},
+    interval::Interval,
+    material::DynMaterial,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Composable signed distance field. Distances are negative inside.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SdfNode {
+    Sphere {
+        radius: f64,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    RoundBox {
+        half_extents: Vec3,
+        radius: f64,
+    },
+    /// Lying in the xz plane
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    /// Power 8 gives the classic bulb. The power must be above 1.
+    Mandelbulb {
+        power: f64,
+        iterations: u32,
+    },
+    Union {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+    },
+    /// Union that blends the surfaces together over a distance of `k`
+    SmoothUnion {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        k: f64,
+    },
+    Intersect {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+    },
+    /// `a` with `b` carved out of it
+    Subtract {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+    },
+    SmoothSubtract {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        k: f64,
+    },
+    Translate {
+        offset: Vec3,
+        node: Box<SdfNode>,
+    },
+    Scale {
+        factor: f64,
+        node: Box<SdfNode>,
+    },
+    /// Rotate around the y axis by `amount` radians per unit of height
+    Twist {
+        amount: f64,
+        node: Box<SdfNode>,
+    },
+    /// Infinite copies of `node` on a grid with cell size `period`
+    Repeat {
+        period: Vec3,
+        node: Box<SdfNode>,
+    },
+}
+
+impl SdfNode {
+    pub fn distance(&self, p: &Point3) -> f64 {
+        match self {
+            Self::Sphere { radius } => p.length() - radius,
+            Self::Box { half_extents } => box_distance(p, half_extents),
+            Self::RoundBox {
+                half_extents,
+                radius,
+            } => box_distance(p, half_extents) - radius,
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let radial = (p.x() * p.x() + p.z() * p.z()).sqrt() - major_radius;
+                (radial * radial + p.y() * p.y()).sqrt() - minor_radius
+            }
+            Self::Mandelbulb { power, iterations } => mandelbulb_distance(p, *power, *iterations),
+            Self::Union { a, b } => a.distance(p).min(b.distance(p)),
+            Self::SmoothUnion { a, b, k } => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+                lerp(db, da, h) - k * h * (1.0 - h)
+            }
+            Self::Intersect { a, b } => a.distance(p).max(b.distance(p)),
+            Self::Subtract { a, b } => a.distance(p).max(-b.distance(p)),
+            Self::SmoothSubtract { a, b, k } => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (da + db) / k).clamp(0.0, 1.0);
+                lerp(da, -db, h) + k * h * (1.0 - h)
+            }
+            Self::Translate { offset, node } => node.distance(&(p - offset)),
+            Self::Scale { factor, node } => node.distance(&(p / *factor)) * factor,
+            Self::Twist { amount, node } => {
+                let (s, c) = (amount * p.y()).sin_cos();
+                let q = Point3::new(c * p.x() - s * p.z(), p.y(), s * p.x() + c * p.z());
+                node.distance(&q)
+            }
+            Self::Repeat { period, node } => {
+                let wrap = |x: f64, period: f64| {
+                    if period > 0.0 {
+                        x - period * (x / period).round()
+                    } else {
+                        x
+                    }
+                };
+                let q = Point3::new(
+                    wrap(p.x(), period.x()),
+                    wrap(p.y(), period.y()),
+                    wrap(p.z(), period.z()),
+                );
+                node.distance(&q)
+            }
+        }
+    }
+
+    /// Conservative bounds of the surface, unbounded for repetitions
+    pub fn bounds(&self) -> AABB {
+        match self {
+            Self::Sphere { radius } => symmetric_box(&Vec3::new(*radius, *radius, *radius)),
+            Self::Box { half_extents } => symmetric_box(half_extents),
+            Self::RoundBox {
+                half_extents,
+                radius,
+            } => symmetric_box(&(half_extents + Vec3::new(*radius, *radius, *radius))),
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let extent = major_radius + minor_radius;
+                symmetric_box(&Vec3::new(extent, *minor_radius, extent))
+            }
+            Self::Mandelbulb { power, .. } => {
+                let radius = mandelbulb_radius(*power);
+                symmetric_box(&Vec3::new(radius, radius, radius))
+            }
+            Self::Union { a, b } => AABB::from_boxes(&a.bounds(), &b.bounds()),
+            Self::SmoothUnion { a, b, k } => {
+                let bounds = AABB::from_boxes(&a.bounds(), &b.bounds());
+                AABB::new(
+                    bounds.x.expand(*k),
+                    bounds.y.expand(*k),
+                    bounds.z.expand(*k),
+                )
+            }
+            Self::Intersect { a, .. } | Self::Subtract { a, .. } => a.bounds(),
+            Self::SmoothSubtract { a, k, .. } => {
+                let bounds = a.bounds();
+                AABB::new(
+                    bounds.x.expand(*k),
+                    bounds.y.expand(*k),
+                    bounds.z.expand(*k),
+                )
+            }
+            Self::Translate { offset, node } => {
+                let bounds = node.bounds();
+                AABB::new(
+                    Interval::new(bounds.x.min + offset.x(), bounds.x.max + offset.x()),
+                    Interval::new(bounds.y.min + offset.y(), bounds.y.max + offset.y()),
+                    Interval::new(bounds.z.min + offset.z(), bounds.z.max + offset.z()),
+                )
+            }
+            Self::Scale { factor, node } => {
+                let bounds = node.bounds();
+                let f = factor.abs();
+                AABB::new(
+                    Interval::new(bounds.x.min * f, bounds.x.max * f),
+                    Interval::new(bounds.y.min * f, bounds.y.max * f),
+                    Interval::new(bounds.z.min * f, bounds.z.max * f),
+                )
+            }
+            Self::Twist { node, .. } => {
+                // Any rotation about y stays within the circle through the
+                // furthest corner in the xz plane
+                let bounds = node.bounds();
+                let x = bounds.x.min.abs().max(bounds.x.max.abs());
+                let z = bounds.z.min.abs().max(bounds.z.max.abs());
+                let radius = (x * x + z * z).sqrt();
+                AABB::new(
+                    Interval::new(-radius, radius),
+                    bounds.y.clone(),
+                    Interval::new(-radius, radius),
+                )
+            }
+            Self::Repeat { .. } => AABB::UNIVERSE,
+        }
+    }
+
+    /// Reject parameters the field can't be evaluated with
+    fn check(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Mandelbulb { power, iterations } => {
+                anyhow::ensure!(
+                    *power > 1.0 && power.is_finite() && *iterations > 0,
+                    "mandelbulb needs a power above 1 and at least one iteration, got power {power} and {iterations} iterations"
+                );
+            }
+            Self::Scale { factor, .. } => anyhow::ensure!(
+                *factor != 0.0 && factor.is_finite(),
+                "sdf scale factor must be non-zero, got {factor}"
+            ),
+            _ => {}
+        }
+
+        match self {
+            Self::Union { a, b }
+            | Self::SmoothUnion { a, b, .. }
+            | Self::Intersect { a, b }
+            | Self::Subtract { a, b }
+            | Self::SmoothSubtract { a, b, .. } => {
+                a.check()?;
+                b.check()
+            }
+            Self::Translate { node, .. }
+            | Self::Scale { node, .. }
+            | Self::Twist { node, .. }
+            | Self::Repeat { node, .. } => node.check(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Factor applied to each step so that fields which overestimate the
+    /// true distance don't step through the surface
+    fn step_scale(&self) -> f64 {
+        match self {
+            Self::Sphere { .. }
+            | Self::Box { .. }
+            | Self::RoundBox { .. }
+            | Self::Torus { .. }
+            | Self::Mandelbulb { .. } => 1.0,
+            Self::Union { a, b }
+            | Self::SmoothUnion { a, b, .. }
+            | Self::Intersect { a, b }
+            | Self::Subtract { a, b }
+            | Self::SmoothSubtract { a, b, .. } => a.step_scale().min(b.step_scale()),
+            Self::Translate { node, .. } | Self::Scale { node, .. } | Self::Repeat { node, .. } => {
+                node.step_scale()
+            }
+            Self::Twist { amount, node } => {
+                let bounds = node.bounds();
+                let x = bounds.x.min.abs().max(bounds.x.max.abs());
+                let z = bounds.z.min.abs().max(bounds.z.max.abs());
+                let radius = (x * x + z * z).sqrt();
+                node.step_scale() / (1.0 + (amount * radius).powi(2)).sqrt()
+            }
+        }
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn symmetric_box(half_extents: &Vec3) -> AABB {
+    AABB::from_points(-half_extents, half_extents.clone())
+}
+
+fn box_distance(p: &Point3, half_extents: &Vec3) -> f64 {
+    let q = Vec3::new(
+        p.x().abs() - half_extents.x(),
+        p.y().abs() - half_extents.y(),
+        p.z().abs() - half_extents.z(),
+    );
+    let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+    let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+    outside + inside
+}
+
+/// Radius the bulb fits in. Further out `|z|^power - |p|` outgrows `|z|` on
+/// every iteration so the point escapes, and the escape test stops any point
+/// beyond 2.
+fn mandelbulb_radius(power: f64) -> f64 {
+    2f64.powf(1.0 / (power - 1.0)).min(2.0)
+}
+
+fn mandelbulb_distance(p: &Point3, power: f64, iterations: u32) -> f64 {
+    let mut z = p.clone();
+    let mut dr = 1.0;
+    let mut r = 0.0;
+
+    for _ in 0..iterations {
+        r = z.length();
+        if r > 2.0 || r == 0.0 {
+            break;
+        }
+
+        // Raise z to the power in spherical coordinates
+        let theta = (z.z() / r).acos() * power;
+        let phi = z.y().atan2(z.x()) * power;
+        let zr = r.powf(power);
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+
+        let direction = Vec3::new(
+            theta.sin() * phi.cos(),
+            phi.sin() * theta.sin(),
+            theta.cos(),
+        );
+        z = p + zr * direction;
+    }
+
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// Hittable that sphere traces a signed distance field
+pub struct SdfShape {
+    root: SdfNode,
+    mat: Arc<DynMaterial>,
+    bbox: AABB,
+    step_scale: f64,
+}
+
+impl SdfShape {
+    const MAX_STEPS: usize = 512;
+    const EPSILON: f64 = 1e-5;
+    /// Marching limit for unbounded fields
+    const MAX_DISTANCE: f64 = 1e3;
+
+    pub fn new(root: SdfNode, mat: Arc<DynMaterial>) -> anyhow::Result<Self> {
+        root.check()?;
+        let bbox = root.bounds();
+        let step_scale = root.step_scale();
+
+        Ok(Self {
+            root,
+            mat,
+            bbox,
+            step_scale,
+        })
+    }
+
+    /// Outward normal from the gradient, using the tetrahedron technique
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let h = 1e-5;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+
+        let mut n = Vec3::ZERO;
+        for k in &k {
+            n += &(k * self.root.distance(&(p + h * k)));
+        }
+        n.unit_vector()
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let range = self.bbox.hit_range(r, ray_t)?;
+        let dir_length = r.direction().length();
+        let unit_direction = r.direction() / dir_length;
+        let t_end = range.max.min(Self::MAX_DISTANCE / dir_length);
+
+        let mut t = range.min;
+        // Which side of the surface the ray starts on, so rays leaving the
+        // surface or travelling inside it march towards the next crossing
+        let mut side = None;
+
+        for step in 0..Self::MAX_STEPS {
+            let p = r.at(t);
+            let d = self.root.distance(&p);
+            let side = *side.get_or_insert_with(|| {
+                if d.abs() > Self::EPSILON {
+                    d.signum()
+                } else if self.normal(&p).dot(&unit_direction) > 0.0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            });
+
+            let signed = side * d;
+            if signed < Self::EPSILON && step > 0 {
+                let outward_normal = self.normal(&p);
+                let mut rec = HitRecord::new(p, outward_normal.clone(), self.mat.clone(), t);
+                rec.set_face_normal(r, &outward_normal);
+
+                let (u, v) = sphere_uv(&outward_normal);
+                rec.u = u;
+                rec.v = v;
+                return Some(rec);
+            }
+
+            t += signed.max(Self::EPSILON) * self.step_scale / dir_length;
+            if t > t_end {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        ShapeSpec::Sdf {
+            root: self.root.clone(),
+            material: self.mat.name().to_owned(),
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        if !self.bbox.is_bounded() {
+            return None;
+        }
+
+        Some(collect_spans(self, r))
+    }
+}
+
+/// Spherical mapping of the normal, there is no natural parameterisation of
+/// an arbitrary field
+fn sphere_uv(n: &Vec3) -> (f64, f64) {
+    let theta = f64::acos(-n.y().clamp(-1.0, 1.0));
+    let phi = f64::atan2(-n.z(), n.x()) + f64::consts::PI;
+    (phi / (2.0 * f64::consts::PI), theta / f64::consts::PI)
+}
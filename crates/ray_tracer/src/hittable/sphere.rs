@@ -2,7 +2,7 @@ use std::{f64, sync::Arc};
 
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable},
+    hittable::{HitRecord, Hittable, Span, collect_spans},
     interval::Interval,
     material::DynMaterial,
     ray::Ray,
@@ -38,8 +38,14 @@ impl Sphere {
         radius: f64,
         mat: Arc<DynMaterial>,
     ) -> Self {
+        let motion = center_2 - &center_1;
+        Self::with_motion(center_1, motion, radius, mat)
+    }
+
+    /// Sphere centered at `center` at time 0 that moves by `motion` by time 1
+    pub fn with_motion(center: Point3, motion: Vec3, radius: f64, mat: Arc<DynMaterial>) -> Self {
         let radius = radius.max(0.0);
-        let center = Ray::new(center_1.clone(), center_2 - center_1);
+        let center = Ray::new(center, motion);
 
         let rvec = Vec3::new(radius, radius, radius);
         let box1 = AABB::from_points(center.at(0.0) - &rvec, &center.at(0.0) + &rvec);
@@ -58,9 +64,11 @@ impl Sphere {
     /// u: returned value [0, 1] of angle around the Y axis from X=-1
     /// v: returned value [0, 1] of angle from Y=-1 to Y=+1
     ///
-    ///     <1 0 0> yields <0.5 0.5>   <-1 0 0> yields <0.0 0.5>
-    ///     <0 1 0> yields <0.5 1.0>   <0 -1 0> yields <0.5 0.0>
-    ///     <0 0 1> yields <0.25 0.5>  <0 0 -1> yields <0.75 0.5>
+    /// ```text
+    /// <1 0 0> yields <0.5 0.5>   <-1 0 0> yields <0.0 0.5>
+    /// <0 1 0> yields <0.5 1.0>   <0 -1 0> yields <0.5 0.0>
+    /// <0 0 1> yields <0.25 0.5>  <0 0 -1> yields <0.75 0.5>
+    /// ```
     fn get_sphere_uv(p: &Point3) -> (f64, f64) {
         let theta = f64::acos(-p.y());
         let phi = f64::atan2(-p.z(), p.x()) + f64::consts::PI;
@@ -116,10 +124,17 @@ impl Hittable for Sphere {
         let material_spec = self.mat.to_spec(registry);
         registry.register_material(self.mat.name().to_owned(), material_spec);
 
-        ShapeSpec::Circle {
+        let motion = self.center.direction();
+
+        ShapeSpec::Sphere {
+            center: self.center.origin().clone(),
             radius: self.radius,
-            center: self.center.clone(),
             material: self.mat.name().to_owned(),
+            motion: (*motion != Vec3::ZERO).then(|| motion.clone()),
         }
     }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        Some(collect_spans(self, r))
+    }
 }
@@ -0,0 +1,194 @@
+use std::{f64, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, Span, collect_spans},
+    interval::Interval,
+    material::DynMaterial,
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Torus lying in the xz plane around `center`. `major_radius` is the distance
+/// from the center to the middle of the tube, `minor_radius` the tube radius.
+pub struct Torus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Arc<DynMaterial>,
+    bbox: AABB,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Arc<DynMaterial>,
+    ) -> Self {
+        let major_radius = major_radius.max(0.0);
+        let minor_radius = minor_radius.max(0.0);
+
+        let extent = major_radius + minor_radius;
+        let rvec = Vec3::new(extent, minor_radius, extent);
+        let bbox = AABB::from_points(&center - &rvec, &center + &rvec);
+
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        // Skip the quartic solve entirely for rays that miss the bounds
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return None;
+        }
+
+        // Solve in units of a normalized direction to keep the quartic well
+        // conditioned, then scale back to the ray's parameter
+        let dir_length = r.direction().length();
+        let d = r.direction() / dir_length;
+        let o = r.origin() - &self.center;
+
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+
+        let beta = 2.0 * o.dot(&d);
+        let gamma = o.length_squared() + big_r2 - small_r2;
+        let four_r2 = 4.0 * big_r2;
+
+        // (|o + td|^2 + R^2 - r^2)^2 = 4R^2 ((ox + t dx)^2 + (oz + t dz)^2)
+        let coeffs = [
+            gamma * gamma - four_r2 * (o.x() * o.x() + o.z() * o.z()),
+            2.0 * beta * gamma - 2.0 * four_r2 * (o.x() * d.x() + o.z() * d.z()),
+            beta * beta + 2.0 * gamma - four_r2 * (d.x() * d.x() + d.z() * d.z()),
+            2.0 * beta,
+            1.0,
+        ];
+
+        let t = polynomial_roots(&coeffs)
+            .into_iter()
+            .map(|t| t / dir_length)
+            .find(|t| ray_t.surrounds(*t))?;
+
+        let p = r.at(t);
+        let local = &p - &self.center;
+        let radial = (local.x() * local.x() + local.z() * local.z()).sqrt();
+
+        // Direction from the closest point on the tube's center circle
+        let ring_point = if radial > 0.0 {
+            Vec3::new(
+                local.x() * self.major_radius / radial,
+                0.0,
+                local.z() * self.major_radius / radial,
+            )
+        } else {
+            Vec3::ZERO
+        };
+        let outward_normal = (&local - &ring_point).unit_vector();
+
+        let mut rec = HitRecord::new(p, outward_normal.clone(), self.mat.clone(), t);
+        rec.set_face_normal(r, &outward_normal);
+
+        let phi = f64::atan2(-local.z(), local.x()) + f64::consts::PI;
+        let theta = f64::atan2(local.y(), radial - self.major_radius) + f64::consts::PI;
+        rec.u = phi / (2.0 * f64::consts::PI);
+        rec.v = theta / (2.0 * f64::consts::PI);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.mat.to_spec(registry);
+        registry.register_material(self.mat.name().to_owned(), material_spec);
+
+        ShapeSpec::Torus {
+            center: self.center.clone(),
+            major_radius: self.major_radius,
+            minor_radius: self.minor_radius,
+            material: self.mat.name().to_owned(),
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        Some(collect_spans(self, r))
+    }
+}
+
+/// Real roots, ascending, of the polynomial with `coeffs` ordered from the
+/// constant term upwards.
+///
+/// Roots of the derivative split the real line into monotonic pieces, each of
+/// which contains at most one root that is then found by bisection.
+fn polynomial_roots(coeffs: &[f64]) -> Vec<f64> {
+    let degree = coeffs.len() - 1;
+    let lead = coeffs[degree];
+    if degree == 0 || lead == 0.0 {
+        return Vec::new();
+    }
+
+    if degree == 1 {
+        return vec![-coeffs[0] / lead];
+    }
+
+    // Cauchy's bound contains every real root
+    let bound = 1.0
+        + coeffs[..degree]
+            .iter()
+            .map(|c| (c / lead).abs())
+            .fold(0.0, f64::max);
+
+    let derivative: Vec<f64> = coeffs
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| i as f64 * c)
+        .collect();
+
+    let mut breaks = vec![-bound];
+    breaks.extend(
+        polynomial_roots(&derivative)
+            .into_iter()
+            .filter(|x| x.abs() < bound),
+    );
+    breaks.push(bound);
+
+    let eval = |x: f64| coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c);
+
+    let mut roots = Vec::new();
+    for window in breaks.windows(2) {
+        let (mut lo, mut hi) = (window[0], window[1]);
+        let (f_lo, f_hi) = (eval(lo), eval(hi));
+
+        if f_lo == 0.0 {
+            roots.push(lo);
+            continue;
+        }
+        if f_lo.signum() == f_hi.signum() {
+            continue;
+        }
+
+        for _ in 0..64 {
+            let mid = 0.5 * (lo + hi);
+            if eval(mid).signum() == f_lo.signum() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        roots.push(0.5 * (lo + hi));
+    }
+
+    roots
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    aabb::AABB,
+    hittable::{DynHittable, HitRecord, Hittable, Span},
+    interval::Interval,
+    matrix::{Affine, Mat3},
+    ray::Ray,
+    scene_loader::{ResourceRegistry, ShapeSpec, TransformSpec},
+    vec::Point3,
+};
+
+/// Places a hittable in the world with an affine transform. Rays are moved
+/// into object space for intersection and the hit is moved back out, so the
+/// same object can be shared between many transforms.
+pub struct Transform {
+    object: Arc<DynHittable>,
+    spec: TransformSpec,
+    /// Name the object is shared under when this transform is an instance
+    prototype: Option<String>,
+    to_world: Affine,
+    to_object: Affine,
+    /// Inverse transpose of the linear part, for transforming normals
+    normal_matrix: Mat3,
+    bbox: AABB,
+}
+
+impl Transform {
+    /// Fails if the transform has a zero scale component
+    pub fn new(object: Arc<DynHittable>, spec: TransformSpec) -> anyhow::Result<Self> {
+        let to_world = spec.affine();
+        let to_object = to_world
+            .inverse()
+            .context("transform scale must be non-zero on every axis")?;
+        let normal_matrix = to_object.linear.transpose();
+        let bbox = transform_box(object.bounding_box(), &to_world);
+
+        Ok(Self {
+            object,
+            spec,
+            prototype: None,
+            to_world,
+            to_object,
+            normal_matrix,
+            bbox,
+        })
+    }
+
+    /// Transform of a named object that is shared with other instances
+    pub fn instance(
+        prototype: impl Into<String>,
+        object: Arc<DynHittable>,
+        spec: TransformSpec,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            prototype: Some(prototype.into()),
+            ..Self::new(object, spec)?
+        })
+    }
+}
+
+impl Transform {
+    fn object_ray(&self, r: &Ray) -> Ray {
+        // Keep the direction unnormalized so t is the same in both spaces
+        Ray::new_with_time(
+            self.to_object.transform_point(r.origin()),
+            self.to_object.transform_vector(r.direction()),
+            r.time(),
+        )
+    }
+
+    fn to_world_record(&self, mut rec: HitRecord) -> HitRecord {
+        rec.p = self.to_world.transform_point(&rec.p);
+        // The inverse transpose preserves the sign of the normal's dot product
+        // with the ray direction, so front_face stays valid
+        rec.normal = (&self.normal_matrix * &rec.normal).unit_vector();
+        rec
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let rec = self.object.hit(&self.object_ray(r), ray_t)?;
+        Some(self.to_world_record(rec))
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        match &self.prototype {
+            Some(name) => {
+                if !registry.has_prototype(name) {
+                    let spec = self.object.to_spec(registry);
+                    registry.register_prototype(name.clone(), spec);
+                }
+
+                ShapeSpec::Instance {
+                    prototype: name.clone(),
+                    transform: self.spec.clone(),
+                }
+            }
+            None => ShapeSpec::Transform {
+                shape: Box::new(self.object.to_spec(registry)),
+                transform: self.spec.clone(),
+            },
+        }
+    }
+
+    fn spans(&self, r: &Ray) -> Option<Vec<Span>> {
+        let spans = self.object.spans(&self.object_ray(r))?;
+
+        Some(
+            spans
+                .into_iter()
+                .map(|span| Span {
+                    enter: self.to_world_record(span.enter),
+                    exit: self.to_world_record(span.exit),
+                })
+                .collect(),
+        )
+    }
+
+    fn children(&self) -> Vec<&Arc<DynHittable>> {
+        vec![&self.object]
+    }
+}
+
+/// Bounding box of the eight transformed corners of `bbox`
+pub(crate) fn transform_box(bbox: &AABB, transform: &Affine) -> AABB {
+    if !bbox.is_bounded() {
+        return AABB::UNIVERSE;
+    }
+
+    let mut result = AABB::EMPTY;
+    for corner in 0..8 {
+        let x = if corner & 1 == 0 {
+            bbox.x.min
+        } else {
+            bbox.x.max
+        };
+        let y = if corner & 2 == 0 {
+            bbox.y.min
+        } else {
+            bbox.y.max
+        };
+        let z = if corner & 4 == 0 {
+            bbox.z.min
+        } else {
+            bbox.z.max
+        };
+
+        let p = transform.transform_point(&Point3::new(x, y, z));
+        result = AABB::from_boxes(&result, &AABB::from_points(p.clone(), p));
+    }
+
+    result
+}
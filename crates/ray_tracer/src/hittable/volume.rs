@@ -0,0 +1,374 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{DynHittable, HitRecord, Hittable},
+    interval::Interval,
+    material::{DiffuseLight, DynMaterial},
+    perlin::Perlin,
+    ray::Ray,
+    scene_loader::{DensitySpec, ResourceRegistry, ShapeSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Spatially varying density used by a [`HeterogeneousMedium`]
+pub trait DensityField {
+    fn density(&self, p: &Point3) -> f64;
+
+    /// Upper bound of `density` anywhere in the field, used as the majorant
+    /// for delta tracking
+    fn max_density(&self) -> f64;
+
+    fn to_spec(&self) -> DensitySpec;
+}
+
+pub type DynDensityField = dyn DensityField + Send + Sync;
+
+/// How a [`VoxelGrid`] was read from disk, kept so it can be written back out
+#[derive(Debug, Clone)]
+enum GridSource {
+    Dense(PathBuf),
+    Sparse(PathBuf),
+}
+
+/// Density samples on a regular grid stretched over a world space box, looked
+/// up with trilinear interpolation
+pub struct VoxelGrid {
+    source: GridSource,
+    resolution: [usize; 3],
+    data: Vec<f32>,
+    bounds: AABB,
+    max: f64,
+}
+
+impl VoxelGrid {
+    /// Load a raw grid of little endian `f32` values with x varying fastest,
+    /// then y, then z
+    pub fn from_dense_file(
+        path: impl AsRef<Path>,
+        resolution: [usize; 3],
+        bounds: AABB,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|| format!("Failed to open density grid: {path:?}"))?;
+
+        let [nx, ny, nz] = resolution;
+        let expected = nx * ny * nz * size_of::<f32>();
+        if bytes.len() != expected {
+            anyhow::bail!(
+                "density grid {path:?} is {} bytes, expected {expected} for resolution {nx}x{ny}x{nz}",
+                bytes.len()
+            );
+        }
+
+        let data = bytes
+            .chunks_exact(size_of::<f32>())
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        Self::new(GridSource::Dense(path.to_owned()), resolution, data, bounds)
+    }
+
+    /// Load a text grid. The first non comment line holds the resolution
+    /// `nx ny nz`, every following line a single voxel `i j k density`.
+    /// Voxels that are not listed are empty.
+    pub fn from_sparse_file(path: impl AsRef<Path>, bounds: AABB) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to open density grid: {path:?}"))?;
+
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (_, header) = lines
+            .next()
+            .ok_or_else(|| anyhow::format_err!("density grid {path:?} is missing a header"))?;
+        let header: Vec<usize> = header
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("invalid density grid header in {path:?}"))?;
+        let [nx, ny, nz] = header[..] else {
+            anyhow::bail!("density grid header in {path:?} must be 'nx ny nz'");
+        };
+
+        let mut data = vec![0.0; nx * ny * nz];
+        for (n, line) in lines {
+            let mut fields = line.split_whitespace();
+            let mut index = || -> anyhow::Result<usize> {
+                Ok(fields
+                    .next()
+                    .ok_or_else(|| anyhow::format_err!("{path:?}:{n}: expected 'i j k density'"))?
+                    .parse()?)
+            };
+            let (i, j, k) = (index()?, index()?, index()?);
+            let value: f32 = fields
+                .next()
+                .ok_or_else(|| anyhow::format_err!("{path:?}:{n}: missing density value"))?
+                .parse()
+                .with_context(|| format!("{path:?}:{n}: invalid density value"))?;
+
+            if i >= nx || j >= ny || k >= nz {
+                anyhow::bail!("{path:?}:{n}: voxel ({i}, {j}, {k}) is outside the grid");
+            }
+            data[(k * ny + j) * nx + i] = value;
+        }
+
+        Self::new(
+            GridSource::Sparse(path.to_owned()),
+            [nx, ny, nz],
+            data,
+            bounds,
+        )
+    }
+
+    fn new(
+        source: GridSource,
+        resolution: [usize; 3],
+        data: Vec<f32>,
+        bounds: AABB,
+    ) -> anyhow::Result<Self> {
+        if let Some(value) = data.iter().find(|v| !(**v >= 0.0 && v.is_finite())) {
+            let (GridSource::Dense(path) | GridSource::Sparse(path)) = &source;
+            anyhow::bail!("density grid {path:?} holds the invalid density {value}");
+        }
+
+        let max = data.iter().copied().fold(0.0, f32::max) as f64;
+        Ok(Self {
+            source,
+            resolution,
+            data,
+            bounds,
+            max,
+        })
+    }
+
+    fn voxel(&self, i: usize, j: usize, k: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(k * ny + j) * nx + i] as f64
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point3) -> f64 {
+        let local = [
+            (p.x() - self.bounds.x.min) / self.bounds.x.size(),
+            (p.y() - self.bounds.y.min) / self.bounds.y.size(),
+            (p.z() - self.bounds.z.min) / self.bounds.z.size(),
+        ];
+
+        // Voxel centers sit at (i + 0.5) / n, find the lower corner of the
+        // surrounding cell and the fractional offset inside it
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.resolution[axis];
+            if !(0.0..=1.0).contains(&local[axis]) || n == 0 {
+                return 0.0;
+            }
+
+            let x = (local[axis] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x.floor() as usize).min(n.saturating_sub(2));
+            base[axis] = i;
+            frac[axis] = if n > 1 { x - i as f64 } else { 0.0 };
+        }
+
+        let mut accum = 0.0;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut weight = 1.0;
+            let mut idx = [0; 3];
+            for axis in 0..3 {
+                let o = offset[axis];
+                weight *= if o == 1 { frac[axis] } else { 1.0 - frac[axis] };
+                idx[axis] = (base[axis] + o).min(self.resolution[axis] - 1);
+            }
+
+            accum += weight * self.voxel(idx[0], idx[1], idx[2]);
+        }
+
+        accum
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+
+    fn to_spec(&self) -> DensitySpec {
+        match &self.source {
+            GridSource::Dense(path) => DensitySpec::DenseGrid {
+                path: path.clone(),
+                resolution: self.resolution,
+            },
+            GridSource::Sparse(path) => DensitySpec::SparseGrid { path: path.clone() },
+        }
+    }
+}
+
+/// Procedural density from Perlin turbulence
+pub struct PerlinDensity {
+    noise: Perlin,
+    scale: f64,
+    octaves: i32,
+    seed: u64,
+}
+
+impl PerlinDensity {
+    pub fn new(scale: f64, octaves: i32, seed: u64) -> Self {
+        Self {
+            noise: Perlin::from_seed(seed),
+            scale,
+            octaves: octaves.max(1),
+            seed,
+        }
+    }
+}
+
+impl DensityField for PerlinDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        self.noise.turb(&(self.scale * p), self.octaves)
+    }
+
+    fn max_density(&self) -> f64 {
+        // Each octave contributes at most its weight, which halves every octave
+        2.0 * (1.0 - 0.5_f64.powi(self.octaves))
+    }
+
+    fn to_spec(&self) -> DensitySpec {
+        DensitySpec::Perlin {
+            scale: self.scale,
+            octaves: self.octaves,
+            seed: self.seed,
+        }
+    }
+}
+
+/// Participating medium with spatially varying density, bounded by a closed
+/// (convex) hittable and sampled with delta tracking.
+///
+/// At every density unit the medium absorbs with coefficient `sigma_a`,
+/// scatters with coefficient `sigma_s` according to its phase function and
+/// emits `emission` wherever light is absorbed.
+pub struct HeterogeneousMedium {
+    boundary: Arc<DynHittable>,
+    density: Arc<DynDensityField>,
+    sigma_a: f64,
+    sigma_s: f64,
+    emission: Color,
+    majorant: f64,
+    phase_function: Arc<DynMaterial>,
+    emitter: Arc<DynMaterial>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        boundary: Arc<DynHittable>,
+        density: Arc<DynDensityField>,
+        sigma_a: f64,
+        sigma_s: f64,
+        emission: Color,
+        phase_function: Arc<DynMaterial>,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            sigma_a >= 0.0 && sigma_s >= 0.0,
+            "medium coefficients can't be negative, got sigma_a {sigma_a} and sigma_s {sigma_s}"
+        );
+        let majorant = (sigma_a + sigma_s) * density.max_density();
+        let emitter = Arc::new(DiffuseLight::new(
+            format!("{}_emission", phase_function.name()),
+            emission.clone(),
+        ));
+
+        Ok(Self {
+            boundary,
+            density,
+            sigma_a,
+            sigma_s,
+            emission,
+            majorant,
+            phase_function,
+            emitter,
+        })
+    }
+
+    /// Parametric range of the ray inside the boundary clipped to `ray_t`
+    fn segment(&self, r: &Ray, ray_t: &Interval) -> Option<(f64, f64)> {
+        let rec1 = self.boundary.hit(r, Interval::UNIVERSE)?;
+        let rec2 = self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY))?;
+
+        let t_enter = rec1.t.max(ray_t.min).max(0.0);
+        let t_exit = rec2.t.min(ray_t.max);
+
+        (t_enter < t_exit).then_some((t_enter, t_exit))
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        let (mut t, t_exit) = self.segment(r, &ray_t)?;
+        let ray_length = r.direction().length();
+
+        loop {
+            // Tentative collision against the homogenized majorant medium
+            t -= (1.0 - rand::random::<f64>()).ln() / (self.majorant * ray_length);
+            if t >= t_exit {
+                return None;
+            }
+
+            let p = r.at(t);
+            let density = self.density.density(&p);
+            let p_absorb = self.sigma_a * density / self.majorant;
+            let p_scatter = self.sigma_s * density / self.majorant;
+
+            let xi: f64 = rand::random();
+            let mat = if xi < p_absorb {
+                self.emitter.clone()
+            } else if xi < p_absorb + p_scatter {
+                self.phase_function.clone()
+            } else {
+                // Null collision, keep tracking
+                continue;
+            };
+
+            // Normal and face are arbitrary, the phase function ignores them
+            let mut rec = HitRecord::new(p, Vec3::new(1.0, 0.0, 0.0), mat, t);
+            rec.front_face = true;
+            return Some(rec);
+        }
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.boundary.bounding_box()
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> ShapeSpec {
+        let material_spec = self.phase_function.to_spec(registry);
+        registry.register_material(self.phase_function.name().to_owned(), material_spec);
+
+        ShapeSpec::HeterogeneousMedium {
+            boundary: Box::new(self.boundary.to_spec(registry)),
+            density: self.density.to_spec(),
+            sigma_a: self.sigma_a,
+            sigma_s: self.sigma_s,
+            emission: self.emission.clone(),
+            material: self.phase_function.name().to_owned(),
+        }
+    }
+}
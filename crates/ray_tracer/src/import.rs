@@ -0,0 +1,152 @@
+//! Readers for scene and mesh formats of other tools
+
+pub mod gltf;
+pub mod pbrt;
+pub mod ply;
+pub mod stl;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::{
+    hittable::mesh::MeshData,
+    matrix::{Affine, Mat3, Quat, Rotation},
+    scene_loader::{SceneFile, ShapeSpec, TransformSpec},
+    vec::Vec3,
+};
+
+/// Scene translated from another tool's format
+pub struct Imported {
+    pub scene: SceneFile,
+    /// What was skipped or only approximated, each reported once
+    pub warnings: Vec<String>,
+    /// Files embedded in the source that the scene refers to by path
+    pub assets: Vec<Asset>,
+}
+
+/// Embedded file, such as a texture packed into a binary glTF
+pub struct Asset {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+}
+
+impl Imported {
+    /// Write the embedded files out so the scene can be loaded
+    pub fn write_assets(&self) -> anyhow::Result<()> {
+        for asset in &self.assets {
+            std::fs::write(&asset.path, &asset.data)
+                .with_context(|| format!("Failed to write asset: {:?}", asset.path))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Import a scene, picking the format from the file extension
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Imported> {
+    let path = path.as_ref();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pbrt") => pbrt::read(path),
+        Some("gltf" | "glb") => gltf::read(path),
+        _ => anyhow::bail!("unknown scene format: {path:?}, expected .pbrt, .gltf or .glb"),
+    }
+}
+
+fn warn(warnings: &mut Vec<String>, warning: String) {
+    if !warnings.contains(&warning) {
+        warnings.push(warning);
+    }
+}
+
+/// First of `prefix`, `prefix_2`, `prefix_3`... that isn't taken
+fn unique_key(prefix: &str, taken: impl Fn(&str) -> bool) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => prefix.to_owned(),
+            n => format!("{prefix}_{n}"),
+        })
+        .find(|key| !taken(key))
+        .expect("some key is free")
+}
+
+fn mesh_spec(mesh: MeshData, material: String) -> ShapeSpec {
+    ShapeSpec::Mesh {
+        positions: mesh.positions,
+        indices: mesh.indices,
+        normals: mesh.normals,
+        uvs: mesh.uvs,
+        colors: mesh.colors,
+        material,
+    }
+}
+
+/// Split a transform into translation, rotation and per axis scale. Shear
+/// can't be represented and is dropped with a warning, transforms that
+/// flatten a shape give `None`.
+fn decompose(affine: &Affine, warnings: &mut Vec<String>) -> Option<TransformSpec> {
+    let columns = affine.linear.transpose();
+    let mut axes = [0, 1, 2].map(|i| columns.row(i));
+    let mut scale = axes.each_ref().map(Vec3::length);
+    if scale.iter().any(|&s| s < 1e-12) {
+        warn(warnings, "shapes with a zero scale skipped".to_owned());
+        return None;
+    }
+
+    for (axis, scale) in axes.iter_mut().zip(&scale) {
+        *axis = &*axis / *scale;
+    }
+    if affine.linear.determinant() < 0.0 {
+        scale[0] = -scale[0];
+        axes[0] = -&axes[0];
+    }
+
+    let sheared = (0..3).any(|i| (i + 1..3).any(|j| axes[i].dot(&axes[j]).abs() > 1e-6));
+    if sheared {
+        warn(
+            warnings,
+            "sheared transforms are approximated without the shear".to_owned(),
+        );
+    }
+
+    let q = Quat::from_mat3(&from_columns(&axes));
+    let axis = Vec3::new(q.x, q.y, q.z);
+    let rotate = if axis.length() < 1e-12 {
+        Rotation::default()
+    } else {
+        Rotation::AxisAngle {
+            axis: axis.unit_vector(),
+            degrees: (2.0 * q.w.clamp(-1.0, 1.0).acos()).to_degrees(),
+        }
+    };
+
+    Some(
+        TransformSpec::default()
+            .translate(affine.translation.clone())
+            .rotate(rotate)
+            .scale(Vec3::new(scale[0], scale[1], scale[2])),
+    )
+}
+
+fn from_columns([a, b, c]: &[Vec3; 3]) -> Mat3 {
+    Mat3::new([
+        [a.x(), b.x(), c.x()],
+        [a.y(), b.y(), c.y()],
+        [a.z(), b.z(), c.z()],
+    ])
+}
+
+/// Uniform scale of a linear map that preserves angles
+fn similarity_scale(linear: &Mat3) -> Option<f64> {
+    let columns = linear.transpose();
+    let axes = [0, 1, 2].map(|i| columns.row(i));
+    let scale = axes[0].length();
+
+    let uniform = axes
+        .iter()
+        .all(|axis| (axis.length() - scale).abs() <= 1e-9 * scale);
+    let orthogonal =
+        (0..3).all(|i| (i + 1..3).all(|j| axes[i].dot(&axes[j]).abs() <= 1e-9 * scale * scale));
+
+    (scale > 0.0 && uniform && orthogonal).then_some(scale)
+}
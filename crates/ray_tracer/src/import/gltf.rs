@@ -0,0 +1,512 @@
+//! glTF 2.0 scenes, as `.gltf` with separate or embedded buffers or as
+//! binary `.glb`. The node hierarchy, triangle meshes, metallic-roughness
+//! materials, base color textures and the first perspective camera are
+//! translated. Materials are mapped onto the closest existing material:
+//! emissive ones become lights, transmissive ones glass, metallic ones metal
+//! and the rest diffuse.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use ::gltf::{
+    Document, Gltf, Node, buffer::Data, camera::Projection, image::Source, material::AlphaMode,
+    mesh::Mode,
+};
+use anyhow::Context;
+use base64::Engine;
+
+use crate::{
+    color::Color,
+    hittable::mesh::MeshData,
+    import::{self, Asset, Imported, decompose, mesh_spec, unique_key},
+    matrix::{Affine, Mat3},
+    scene_loader::{CameraSpec, MaterialSpec, SceneFile, ShapeSpec, TextureSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Extensions whose settings are used for materials
+const EXTENSIONS: [&str; 3] = [
+    "KHR_materials_transmission",
+    "KHR_materials_ior",
+    "KHR_materials_emissive_strength",
+];
+
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Imported> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read glTF file: {path:?}"))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let stem = path
+        .file_stem()
+        .map_or("gltf".into(), |stem| stem.to_string_lossy().into_owned());
+    load(&bytes, dir, stem).with_context(|| format!("Failed to load glTF file: {path:?}"))
+}
+
+/// Import a `.gltf` or `.glb` held in memory, external buffers and images
+/// are taken relative to `dir`
+pub fn parse(bytes: &[u8], dir: impl AsRef<Path>) -> anyhow::Result<Imported> {
+    load(bytes, dir.as_ref(), "gltf".to_owned())
+}
+
+fn load(bytes: &[u8], dir: &Path, stem: String) -> anyhow::Result<Imported> {
+    let Gltf { document, blob } = Gltf::from_slice(bytes)?;
+    let buffers = ::gltf::import_buffers(&document, Some(dir), blob)?;
+
+    let mut importer = Importer {
+        dir: dir.to_owned(),
+        stem,
+        buffers,
+        scene: SceneFile::new(),
+        texture_keys: HashMap::new(),
+        material_keys: HashMap::new(),
+        prototype_keys: HashMap::new(),
+        mesh_uses: HashMap::new(),
+        camera: None,
+        assets: Vec::new(),
+        warnings: Vec::new(),
+    };
+    importer.document(&document)?;
+
+    let mut scene = importer.scene;
+    scene.set_camera(importer.camera);
+    Ok(Imported {
+        scene,
+        warnings: importer.warnings,
+        assets: importer.assets,
+    })
+}
+
+struct Importer {
+    dir: PathBuf,
+    /// File name of the source without its extension, for naming assets
+    stem: String,
+    buffers: Vec<Data>,
+    scene: SceneFile,
+    /// Our key of each glTF image
+    texture_keys: HashMap<usize, String>,
+    /// Our key of each glTF material, `None` for the default material
+    material_keys: HashMap<Option<usize>, String>,
+    /// Our key of each glTF mesh that is shared by several nodes
+    prototype_keys: HashMap<usize, String>,
+    /// Nodes that use each glTF mesh
+    mesh_uses: HashMap<usize, usize>,
+    camera: Option<CameraSpec>,
+    assets: Vec<Asset>,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    fn warn(&mut self, warning: String) {
+        import::warn(&mut self.warnings, warning);
+    }
+
+    fn document(&mut self, document: &Document) -> anyhow::Result<()> {
+        for extension in document.extensions_used() {
+            if !EXTENSIONS.contains(&extension) {
+                self.warn(format!("extension '{extension}' is not supported"));
+            }
+        }
+        if document.animations().next().is_some() {
+            self.warn("animations are ignored".to_owned());
+        }
+
+        let Some(scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            self.warn("the file has no scene".to_owned());
+            return Ok(());
+        };
+        if document.scenes().count() > 1 {
+            let name = scene.name().unwrap_or("the default scene").to_owned();
+            self.warn(format!("only {name} was imported"));
+        }
+
+        // Meshes used once are baked into the world, shared ones are instanced
+        for node in scene.nodes() {
+            self.count_meshes(&node);
+        }
+        for node in scene.nodes() {
+            self.node(&node, &Affine::IDENTITY)?;
+        }
+
+        Ok(())
+    }
+
+    fn count_meshes(&mut self, node: &Node) {
+        if let Some(mesh) = node.mesh() {
+            *self.mesh_uses.entry(mesh.index()).or_default() += 1;
+        }
+        for child in node.children() {
+            self.count_meshes(&child);
+        }
+    }
+
+    fn node(&mut self, node: &Node, parent: &Affine) -> anyhow::Result<()> {
+        let to_world = local_transform(node).then(parent);
+
+        if let Some(mesh) = node.mesh() {
+            // Exporters hide nodes by scaling them to nothing
+            let shape = if to_world.linear.inverse().is_none() {
+                let name = node
+                    .name()
+                    .map_or_else(|| format!("node{}", node.index()), str::to_owned);
+                self.warn(format!("node '{name}' skipped, its transform flattens it"));
+                None
+            } else if self.mesh_uses[&mesh.index()] > 1 {
+                let prototype = self.prototype(&mesh)?;
+                decompose(&to_world, &mut self.warnings).zip(prototype).map(
+                    |(transform, prototype)| ShapeSpec::Instance {
+                        prototype,
+                        transform,
+                    },
+                )
+            } else {
+                self.mesh(&mesh, &to_world)?
+            };
+
+            if let Some(shape) = shape {
+                let shape = match node.name() {
+                    Some(name) => ShapeSpec::Named {
+                        name: name.to_owned(),
+                        shape: Box::new(shape),
+                    },
+                    None => shape,
+                };
+                self.scene.add_shape(shape);
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.camera(&camera, &to_world);
+        }
+        if node.skin().is_some() {
+            self.warn("skins are ignored, meshes keep their bind pose".to_owned());
+        }
+
+        for child in node.children() {
+            self.node(&child, &to_world)?;
+        }
+
+        Ok(())
+    }
+
+    /// Key of a shared mesh, adding it as a prototype the first time
+    fn prototype(&mut self, mesh: &::gltf::Mesh) -> anyhow::Result<Option<String>> {
+        if let Some(key) = self.prototype_keys.get(&mesh.index()) {
+            return Ok(Some(key.clone()));
+        }
+
+        let Some(shape) = self.mesh(mesh, &Affine::IDENTITY)? else {
+            return Ok(None);
+        };
+        let name = mesh
+            .name()
+            .map_or_else(|| format!("mesh{}", mesh.index()), str::to_owned);
+        let prototypes = self.scene.prototypes();
+        let key = unique_key(&name, |key| prototypes.iter().any(|(k, _)| k == key));
+
+        self.scene.add_prototype(key.clone(), shape);
+        self.prototype_keys.insert(mesh.index(), key.clone());
+        Ok(Some(key))
+    }
+
+    /// Primitives of a mesh in world space, `None` when none are triangles
+    fn mesh(
+        &mut self,
+        mesh: &::gltf::Mesh,
+        to_world: &Affine,
+    ) -> anyhow::Result<Option<ShapeSpec>> {
+        let mut shapes = Vec::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                self.warn(format!("{:?} primitives skipped", primitive.mode()));
+                continue;
+            }
+            if primitive.morph_targets().next().is_some() {
+                self.warn("morph targets are ignored".to_owned());
+            }
+
+            let reader =
+                primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|d| &d[..]));
+            let positions: Vec<Point3> = reader
+                .read_positions()
+                .context("mesh primitive has no positions")?
+                .map(|[x, y, z]| Point3::new(x as f64, y as f64, z as f64))
+                .collect();
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            let normals = reader
+                .read_normals()
+                .into_iter()
+                .flatten()
+                .map(|[x, y, z]| Vec3::new(x as f64, y as f64, z as f64))
+                .collect();
+            // glTF puts the texture origin at the top left, we use the bottom left
+            let uvs = reader
+                .read_tex_coords(0)
+                .into_iter()
+                .flat_map(|uvs| uvs.into_f32())
+                .map(|[u, v]| [u as f64, 1.0 - v as f64])
+                .collect();
+            let colors = reader
+                .read_colors(0)
+                .into_iter()
+                .flat_map(|colors| colors.into_rgb_f32())
+                .map(|[r, g, b]| Color::new(r as f64, g as f64, b as f64))
+                .collect();
+
+            let mut data = MeshData {
+                positions,
+                indices: indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+                normals,
+                uvs,
+                colors,
+            };
+            data.validate()?;
+            if data.indices.is_empty() {
+                continue;
+            }
+            data.transform(to_world);
+
+            let material = self.material(&primitive.material());
+            shapes.push(mesh_spec(data, material));
+        }
+
+        Ok(match shapes.len() {
+            0 => None,
+            1 => shapes.pop(),
+            _ => Some(ShapeSpec::List(shapes)),
+        })
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> String {
+        if let Some(key) = self.material_keys.get(&material.index()) {
+            return key.clone();
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor().map(decimal);
+        let base_color = Color::new(r, g, b);
+        let emissive = material.emissive_factor().map(decimal);
+        let strength = decimal(material.emissive_strength().unwrap_or(1.0));
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |t| t.transmission_factor());
+        let metallic = pbr.metallic_factor();
+
+        if material.alpha_mode() != AlphaMode::Opaque {
+            self.warn("transparency from alpha is ignored".to_owned());
+        }
+        if material.normal_texture().is_some() || material.occlusion_texture().is_some() {
+            self.warn("normal and occlusion textures are ignored".to_owned());
+        }
+        if pbr.metallic_roughness_texture().is_some() {
+            self.warn("metallic-roughness textures are ignored".to_owned());
+        }
+
+        let spec = if emissive.iter().any(|&e| e > 0.0) {
+            if material.emissive_texture().is_some() {
+                self.warn("emissive textures are ignored".to_owned());
+            }
+            let [r, g, b] = emissive.map(|e| e * strength);
+            let texture = self.color_texture(Color::new(r, g, b));
+            MaterialSpec::DiffuseLight { texture }
+        } else if transmission > 0.5 {
+            MaterialSpec::Dielectric {
+                refraction_index: decimal(material.ior().unwrap_or(1.5)),
+            }
+        } else if metallic >= 0.5 {
+            if pbr.base_color_texture().is_some() {
+                self.warn("textured metals use their base color factor".to_owned());
+            }
+            MaterialSpec::Metal {
+                albedo: base_color,
+                fuzz: decimal(pbr.roughness_factor()).clamp(0.0, 1.0),
+            }
+        } else {
+            let texture = match pbr.base_color_texture() {
+                Some(info) => {
+                    if info.tex_coord() != 0 {
+                        self.warn("only the first texture coordinate set is used".to_owned());
+                    }
+                    if base_color != Color::new(1.0, 1.0, 1.0) {
+                        self.warn(
+                            "base color factors of textured materials are ignored".to_owned(),
+                        );
+                    }
+                    self.image(&info.texture().source())
+                        .unwrap_or_else(|| self.color_texture(base_color))
+                }
+                None => self.color_texture(base_color),
+            };
+            MaterialSpec::Lambertian { texture }
+        };
+        if metallic > 0.0 && metallic < 1.0 {
+            self.warn(format!(
+                "partially metallic materials are rendered as {}",
+                spec.kind()
+            ));
+        }
+
+        let name = match (material.name(), material.index()) {
+            (Some(name), _) => name.to_owned(),
+            (None, Some(index)) => format!("material{index}"),
+            (None, None) => "default".to_owned(),
+        };
+        let materials = self.scene.materials();
+        let key = unique_key(&name, |key| materials.iter().any(|(k, _)| k == key));
+
+        self.scene.add_material(key.clone(), spec);
+        self.material_keys.insert(material.index(), key.clone());
+        key
+    }
+
+    fn color_texture(&mut self, albedo: Color) -> String {
+        let spec = TextureSpec::SolidColor { albedo };
+        let textures = self.scene.textures();
+        if let Some((key, _)) = textures.iter().find(|(_, other)| *other == spec) {
+            return key.clone();
+        }
+
+        let key = unique_key("color", |key| textures.iter().any(|(k, _)| k == key));
+        self.scene.add_texture(key.clone(), spec);
+        key
+    }
+
+    /// Texture of an image, extracting embedded images as assets
+    fn image(&mut self, image: &::gltf::Image) -> Option<String> {
+        if let Some(key) = self.texture_keys.get(&image.index()) {
+            return Some(key.clone());
+        }
+
+        let embedded = match image.source() {
+            Source::Uri { uri, mime_type } => match uri.strip_prefix("data:") {
+                Some(data) => {
+                    let decoded = data.split_once(";base64,").and_then(|(_, base64)| {
+                        base64::engine::general_purpose::STANDARD
+                            .decode(base64)
+                            .ok()
+                    });
+                    match decoded {
+                        Some(bytes) => Some((bytes, mime_type)),
+                        None => {
+                            self.warn(format!("image {} has an invalid data URI", image.index()));
+                            return None;
+                        }
+                    }
+                }
+                None => {
+                    let path = urlencoding::decode(uri).map_or_else(
+                        |_| PathBuf::from(uri),
+                        |path| PathBuf::from(path.into_owned()),
+                    );
+                    self.add_image(image, self.dir.join(path));
+                    return self.texture_keys.get(&image.index()).cloned();
+                }
+            },
+            Source::View { view, mime_type } => {
+                let range = view.offset()..view.offset() + view.length();
+                let bytes = self
+                    .buffers
+                    .get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(range));
+                match bytes {
+                    Some(bytes) => Some((bytes.to_vec(), Some(mime_type))),
+                    None => {
+                        self.warn(format!(
+                            "image {} lies outside of its buffer",
+                            image.index()
+                        ));
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let (data, mime_type) = embedded?;
+        let extension = match mime_type {
+            Some("image/jpeg") => "jpg",
+            Some("image/png") => "png",
+            _ => match image::guess_format(&data) {
+                Ok(format) => format.extensions_str()[0],
+                Err(_) => {
+                    self.warn(format!("image {} has an unknown format", image.index()));
+                    return None;
+                }
+            },
+        };
+        let path = self
+            .dir
+            .join(format!("{}_image{}.{extension}", self.stem, image.index()));
+
+        self.assets.push(Asset {
+            path: path.clone(),
+            data,
+        });
+        self.add_image(image, path);
+        self.texture_keys.get(&image.index()).cloned()
+    }
+
+    fn add_image(&mut self, image: &::gltf::Image, path: PathBuf) {
+        let name = image
+            .name()
+            .map_or_else(|| format!("image{}", image.index()), str::to_owned);
+        let textures = self.scene.textures();
+        let key = unique_key(&name, |key| textures.iter().any(|(k, _)| k == key));
+
+        self.scene
+            .add_texture(key.clone(), TextureSpec::Image { path });
+        self.texture_keys.insert(image.index(), key);
+    }
+
+    fn camera(&mut self, camera: &::gltf::Camera, to_world: &Affine) {
+        let Projection::Perspective(perspective) = camera.projection() else {
+            self.warn("orthographic cameras are skipped".to_owned());
+            return;
+        };
+        if self.camera.is_some() {
+            self.warn("only the first camera is used".to_owned());
+            return;
+        }
+
+        // glTF cameras look down -z with y up, like ours
+        let lookfrom = to_world.transform_point(&Point3::ZERO);
+        let direction = to_world
+            .transform_vector(&Vec3::new(0.0, 0.0, -1.0))
+            .unit_vector();
+        self.camera = Some(CameraSpec {
+            aspect_ratio: perspective.aspect_ratio().map(decimal),
+            vfov: Some(decimal(perspective.yfov()).to_degrees()),
+            lookat: Some(&lookfrom + &direction),
+            lookfrom: Some(lookfrom),
+            vup: Some(to_world.transform_vector(&Vec3::new(0.0, 1.0, 0.0))),
+            ..Default::default()
+        });
+    }
+}
+
+/// Transform of a node relative to its parent
+fn local_transform(node: &Node) -> Affine {
+    // Column major
+    let m = node
+        .transform()
+        .matrix()
+        .map(|column| column.map(f64::from));
+    Affine::new(
+        Mat3::new([0, 1, 2].map(|i| [m[0][i], m[1][i], m[2][i]])),
+        Vec3::new(m[3][0], m[3][1], m[3][2]),
+    )
+}
+
+/// Widen to the shortest decimal that reads back as the same `f32`, so a
+/// factor of 0.78 is written as 0.78 rather than 0.7799999713897705
+fn decimal(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
@@ -0,0 +1,1170 @@
+//! PBRT-v3 and PBRT-v4 scene descriptions. The camera, spheres, triangle and
+//! PLY meshes, diffuse, conductor and dielectric materials, image textures,
+//! area lights, transforms and object instances are translated. Everything
+//! else is skipped or approximated with a warning.
+//!
+//! PBRT renders with a left handed camera, so the scene is mirrored across
+//! the camera's vertical plane to give the same image with our camera.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    color::Color,
+    hittable::mesh::MeshData,
+    import::{self, Imported, decompose, from_columns, mesh_spec, similarity_scale, unique_key},
+    material::ComplexIor,
+    matrix::{Affine, Mat3},
+    scene_loader::{CameraSpec, MaterialSpec, SceneFile, ShapeSpec, TextureSpec},
+    vec::{Point3, Vec3},
+};
+
+/// Presets for the metals PBRT ships spectra for
+const METALS: [(&str, ComplexIor); 4] = [
+    ("Au", ComplexIor::Gold),
+    ("Cu", ComplexIor::Copper),
+    ("Ag", ComplexIor::Silver),
+    ("Al", ComplexIor::Aluminium),
+];
+
+/// Index of refraction of PBRT's named glass spectra at 550nm
+const GLASSES: [(&str, f64); 7] = [
+    ("glass-BK7", 1.5168),
+    ("glass-BAF10", 1.6700),
+    ("glass-FK51A", 1.4866),
+    ("glass-LASF9", 1.8503),
+    ("glass-F5", 1.6034),
+    ("glass-F10", 1.6200),
+    ("glass-F11", 1.6210),
+];
+
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Imported> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut importer = Importer::new(dir);
+    importer.include(path)?;
+    importer.finish()
+}
+
+/// Import a scene held in memory, relative file names are taken relative to
+/// `dir`
+pub fn parse(source: &str, dir: impl AsRef<Path>) -> anyhow::Result<Imported> {
+    let mut importer = Importer::new(dir.as_ref());
+    importer.run(source)?;
+    importer.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Num(f64),
+    Open,
+    Close,
+}
+
+/// Split PBRT source into tokens tagged with their line number
+fn tokenize(source: &str) -> anyhow::Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut line = 1;
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            '[' => tokens.push((Token::Open, line)),
+            ']' => tokens.push((Token::Close, line)),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => text.push('\n'),
+                            Some((_, 't')) => text.push('\t'),
+                            Some((_, c)) => text.push(c),
+                            None => anyhow::bail!("line {line}: unterminated string"),
+                        },
+                        Some((_, '\n')) | None => anyhow::bail!("line {line}: unterminated string"),
+                        Some((_, c)) => text.push(c),
+                    }
+                }
+                tokens.push((Token::Str(text), line));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars
+                    .next_if(|&(_, c)| !c.is_whitespace() && !matches!(c, '[' | ']' | '"' | '#'))
+                {
+                    end = i + c.len_utf8();
+                }
+
+                let word = &source[start..end];
+                let token = match word.parse() {
+                    Ok(number) => Token::Num(number),
+                    Err(_) => Token::Word(word.to_owned()),
+                };
+                tokens.push((token, line));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Num(f64),
+    Str(String),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+struct Directive {
+    name: String,
+    args: Vec<Value>,
+    line: usize,
+}
+
+/// Group tokens into directives with the values that follow them
+fn directives(tokens: Vec<(Token, usize)>) -> anyhow::Result<Vec<Directive>> {
+    let scalar = |token: Token| match token {
+        Token::Num(number) => Some(Value::Num(number)),
+        Token::Str(text) => Some(Value::Str(text)),
+        Token::Word(word) if word == "true" => Some(Value::Bool(true)),
+        Token::Word(word) if word == "false" => Some(Value::Bool(false)),
+        _ => None,
+    };
+
+    let mut directives: Vec<Directive> = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some((token, line)) = tokens.next() {
+        let name = match token {
+            Token::Word(word) if word != "true" && word != "false" => word,
+            token => match directives.last_mut() {
+                Some(directive) => {
+                    // Values continue the previous directive
+                    let value = match token {
+                        Token::Open => {
+                            let mut list = Vec::new();
+                            loop {
+                                match tokens.next() {
+                                    Some((Token::Close, _)) => break,
+                                    Some((token, line)) => {
+                                        list.push(scalar(token).with_context(|| {
+                                            format!("line {line}: unexpected token in list")
+                                        })?)
+                                    }
+                                    None => anyhow::bail!("line {line}: unterminated list"),
+                                }
+                            }
+                            Value::List(list)
+                        }
+                        Token::Close => anyhow::bail!("line {line}: unexpected ']'"),
+                        token => scalar(token).expect("only words that are directives remain"),
+                    };
+                    directive.args.push(value);
+                    continue;
+                }
+                None => anyhow::bail!("line {line}: expected a directive, found {token:?}"),
+            },
+        };
+
+        directives.push(Directive {
+            name,
+            args: Vec::new(),
+            line,
+        });
+    }
+
+    Ok(directives)
+}
+
+/// A `"type name" value` parameter with list values flattened
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<Value>,
+}
+
+#[derive(Default)]
+struct Params(Vec<Param>);
+
+impl Params {
+    fn parse(args: &[Value]) -> anyhow::Result<Self> {
+        let mut params = Vec::new();
+        let mut args = args.iter();
+        while let Some(declaration) = args.next() {
+            let Value::Str(declaration) = declaration else {
+                anyhow::bail!("expected a parameter declaration, found {declaration:?}");
+            };
+            let [ty, name] = declaration.split_whitespace().collect::<Vec<_>>()[..] else {
+                anyhow::bail!("invalid parameter declaration '{declaration}'");
+            };
+            let values = match args.next() {
+                Some(Value::List(values)) => values.clone(),
+                Some(value) => vec![value.clone()],
+                None => anyhow::bail!("parameter '{name}' has no value"),
+            };
+
+            params.push(Param {
+                ty: ty.to_owned(),
+                name: name.to_owned(),
+                values,
+            });
+        }
+
+        Ok(Self(params))
+    }
+
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.iter().rev().find(|param| param.name == name)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f64>> {
+        let param = self.get(name)?;
+        param
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Num(number) => Some(*number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn float(&self, name: &str) -> Option<f64> {
+        self.floats(name)?.first().copied()
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)?.values.first()? {
+            Value::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn strings(&self, name: &str) -> Vec<&str> {
+        let values = self.get(name).map_or(&[][..], |param| &param.values);
+        values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Str(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+}
+
+/// Leading string arguments of a directive followed by its parameters
+fn positional<const N: usize>(directive: &Directive) -> anyhow::Result<([&str; N], Params)> {
+    let mut names = [""; N];
+    for (i, name) in names.iter_mut().enumerate() {
+        match directive.args.get(i) {
+            Some(Value::Str(text)) => *name = text,
+            _ => anyhow::bail!("expected {N} string arguments"),
+        }
+    }
+
+    Ok((names, Params::parse(&directive.args[N..])?))
+}
+
+/// Numbers of a transform directive, bracketed or not
+fn numbers<const N: usize>(directive: &Directive) -> anyhow::Result<[f64; N]> {
+    let values = match directive.args.as_slice() {
+        [Value::List(values)] => values.as_slice(),
+        values => values,
+    };
+    let numbers: Vec<f64> = values
+        .iter()
+        .map(|value| match value {
+            Value::Num(number) => Ok(*number),
+            value => Err(anyhow::anyhow!("expected a number, found {value:?}")),
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    numbers.try_into().map_err(|numbers: Vec<f64>| {
+        anyhow::anyhow!("expected {N} numbers, found {}", numbers.len())
+    })
+}
+
+/// What shapes are made of when no area light is active
+#[derive(Debug, Clone, PartialEq)]
+enum Surface {
+    /// PBRT's default diffuse material
+    Default,
+    Material(String),
+    /// Boundary between media that is invisible itself
+    Interface,
+}
+
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    /// Object to PBRT world transform
+    ctm: Affine,
+    surface: Surface,
+    /// Emissive material of the active area light
+    area_light: Option<String>,
+    reverse_orientation: bool,
+}
+
+enum Saved {
+    Attributes(GraphicsState),
+    Transform(Affine),
+}
+
+struct Object {
+    name: String,
+    shapes: Vec<ShapeSpec>,
+}
+
+struct Importer {
+    /// Relative file names are taken relative to the first file read
+    dir: PathBuf,
+    /// Files currently being included, to detect cycles
+    includes: Vec<PathBuf>,
+    state: GraphicsState,
+    saved: Vec<Saved>,
+    coordinate_systems: HashMap<String, Affine>,
+    camera_type: String,
+    camera_params: Params,
+    /// Camera to PBRT world transform
+    world_from_camera: Affine,
+    /// PBRT world to our world, mirroring the scene to match the camera
+    mirror: Affine,
+    film: Params,
+    sampler: Params,
+    integrator: Params,
+    /// Our key of each PBRT texture, `None` for ones that weren't translated
+    texture_keys: HashMap<String, Option<String>>,
+    textures: Vec<(String, TextureSpec)>,
+    materials: Vec<(String, MaterialSpec)>,
+    /// Named materials that are interfaces
+    interfaces: Vec<String>,
+    object: Option<Object>,
+    prototypes: Vec<(String, ShapeSpec)>,
+    shapes: Vec<ShapeSpec>,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    fn new(dir: &Path) -> Self {
+        let mut importer = Self {
+            dir: dir.to_owned(),
+            includes: Vec::new(),
+            state: GraphicsState {
+                ctm: Affine::IDENTITY,
+                surface: Surface::Default,
+                area_light: None,
+                reverse_orientation: false,
+            },
+            saved: Vec::new(),
+            coordinate_systems: HashMap::new(),
+            camera_type: "perspective".to_owned(),
+            camera_params: Params::default(),
+            world_from_camera: Affine::IDENTITY,
+            mirror: Affine::IDENTITY,
+            film: Params::default(),
+            sampler: Params::default(),
+            integrator: Params::default(),
+            texture_keys: HashMap::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+            interfaces: Vec::new(),
+            object: None,
+            prototypes: Vec::new(),
+            shapes: Vec::new(),
+            warnings: Vec::new(),
+        };
+        importer.set_camera(Affine::IDENTITY);
+        importer
+    }
+
+    fn warn(&mut self, warning: String) {
+        import::warn(&mut self.warnings, warning);
+    }
+
+    fn include(&mut self, path: &Path) -> anyhow::Result<()> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read PBRT file: {path:?}"))?;
+        if let Some(start) = self.includes.iter().position(|p| *p == canonical) {
+            let cycle: Vec<_> = self.includes[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| format!("{p:?}"))
+                .collect();
+            anyhow::bail!("include cycle: {}", cycle.join(" -> "));
+        }
+
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read PBRT file: {path:?}"))?;
+        self.includes.push(canonical);
+        let result = self
+            .run(&source)
+            .with_context(|| format!("Failed to load PBRT file: {path:?}"));
+        self.includes.pop();
+        result
+    }
+
+    fn run(&mut self, source: &str) -> anyhow::Result<()> {
+        for directive in directives(tokenize(source)?)? {
+            self.directive(&directive)
+                .with_context(|| format!("line {}: in '{}'", directive.line, directive.name))?;
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &Directive) -> anyhow::Result<()> {
+        match directive.name.as_str() {
+            "Identity" => self.state.ctm = Affine::IDENTITY,
+            "Translate" => {
+                let [x, y, z] = numbers(directive)?;
+                self.concat(Affine::new(Mat3::IDENTITY, Vec3::new(x, y, z)));
+            }
+            "Scale" => {
+                let [x, y, z] = numbers(directive)?;
+                self.concat(Affine::new(
+                    Mat3::from_scale(&Vec3::new(x, y, z)),
+                    Vec3::ZERO,
+                ));
+            }
+            "Rotate" => {
+                let [degrees, x, y, z] = numbers(directive)?;
+                let rotation = Mat3::from_axis_angle(&Vec3::new(x, y, z), degrees);
+                self.concat(Affine::new(rotation, Vec3::ZERO));
+            }
+            "LookAt" => {
+                let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = numbers(directive)?;
+                let look_at = look_at(
+                    Point3::new(ex, ey, ez),
+                    Point3::new(lx, ly, lz),
+                    Vec3::new(ux, uy, uz),
+                )
+                .context("the up vector is parallel to the viewing direction")?;
+                self.concat(look_at);
+            }
+            "Transform" => self.state.ctm = matrix(numbers(directive)?),
+            "ConcatTransform" => self.concat(matrix(numbers(directive)?)),
+            "CoordinateSystem" => {
+                let ([name], _) = positional(directive)?;
+                self.coordinate_systems
+                    .insert(name.to_owned(), self.state.ctm.clone());
+            }
+            "CoordSysTransform" => {
+                let ([name], _) = positional(directive)?;
+                match self.coordinate_systems.get(name) {
+                    Some(ctm) => self.state.ctm = ctm.clone(),
+                    None => self.warn(format!("unknown coordinate system '{name}' ignored")),
+                }
+            }
+            "ReverseOrientation" => {
+                self.state.reverse_orientation = !self.state.reverse_orientation;
+            }
+            "AttributeBegin" => self.saved.push(Saved::Attributes(self.state.clone())),
+            "TransformBegin" => self.saved.push(Saved::Transform(self.state.ctm.clone())),
+            "AttributeEnd" | "TransformEnd" => match self.saved.pop() {
+                Some(Saved::Attributes(state)) => self.state = state,
+                Some(Saved::Transform(ctm)) => self.state.ctm = ctm,
+                None => self.warn(format!("unmatched '{}' ignored", directive.name)),
+            },
+            "Camera" => {
+                let ([ty], params) = positional(directive)?;
+                self.camera_type = ty.to_owned();
+                self.camera_params = params;
+                let camera_from_world = self.state.ctm.clone();
+                self.set_camera(camera_from_world);
+            }
+            "Film" => (_, self.film) = positional::<1>(directive)?,
+            "Sampler" => (_, self.sampler) = positional::<1>(directive)?,
+            "Integrator" => (_, self.integrator) = positional::<1>(directive)?,
+            "WorldBegin" => {
+                self.state.ctm = Affine::IDENTITY;
+                self.coordinate_systems
+                    .insert("world".to_owned(), Affine::IDENTITY);
+            }
+            "Include" | "Import" => {
+                let ([file], _) = positional(directive)?;
+                let path = self.dir.join(file);
+                self.include(&path)?;
+            }
+            "Texture" => {
+                let ([name, ty, class], params) = positional(directive)?;
+                self.texture(name, ty, class, &params);
+            }
+            "Material" => {
+                let ([ty], params) = positional(directive)?;
+                self.state.surface = match self.material(ty, &params) {
+                    Some(spec) => Surface::Material(self.add_material(spec)),
+                    None => Surface::Interface,
+                };
+            }
+            "MakeNamedMaterial" => {
+                let ([name], params) = positional(directive)?;
+                let ty = params.string("type").unwrap_or("diffuse").to_owned();
+                self.materials.retain(|(key, _)| key != name);
+                self.interfaces.retain(|key| key != name);
+                match self.material(&ty, &params) {
+                    Some(spec) => self.materials.push((name.to_owned(), spec)),
+                    None => self.interfaces.push(name.to_owned()),
+                }
+            }
+            "NamedMaterial" => {
+                let ([name], _) = positional(directive)?;
+                self.state.surface = if self.materials.iter().any(|(key, _)| key == name) {
+                    Surface::Material(name.to_owned())
+                } else if self.interfaces.iter().any(|key| key == name) {
+                    Surface::Interface
+                } else {
+                    self.warn(format!("unknown material '{name}', using the default"));
+                    Surface::Default
+                };
+            }
+            "AreaLightSource" => {
+                let ([ty], params) = positional(directive)?;
+                self.area_light(ty, &params);
+            }
+            "Shape" => {
+                let ([ty], params) = positional(directive)?;
+                self.shape(ty, &params)?;
+            }
+            "ObjectBegin" => {
+                let ([name], _) = positional(directive)?;
+                anyhow::ensure!(self.object.is_none(), "objects can't be nested");
+                self.saved.push(Saved::Attributes(self.state.clone()));
+                self.object = Some(Object {
+                    name: name.to_owned(),
+                    shapes: Vec::new(),
+                });
+            }
+            "ObjectEnd" => {
+                let object = self.object.take().context("no object to end")?;
+                if let Some(Saved::Attributes(state)) = self.saved.pop() {
+                    self.state = state;
+                }
+                self.end_object(object);
+            }
+            "ObjectInstance" => {
+                let ([name], _) = positional(directive)?;
+                self.instance(name);
+            }
+            "WorldEnd" | "PixelFilter" | "Accelerator" | "ColorSpace" | "Option" => {}
+            "LightSource" => {
+                let ([ty], _) = positional(directive)?;
+                self.warn(format!(
+                    "light source '{ty}' skipped, only area lights are supported"
+                ));
+            }
+            name => self.warn(format!(
+                "directive '{name}' is not supported and was skipped"
+            )),
+        }
+
+        Ok(())
+    }
+
+    /// Apply `transform` in object space, after the transforms so far
+    fn concat(&mut self, transform: Affine) {
+        self.state.ctm = transform.then(&self.state.ctm);
+    }
+
+    fn set_camera(&mut self, camera_from_world: Affine) {
+        let Some(world_from_camera) = camera_from_world.inverse() else {
+            self.warn("the camera transform is singular, using the default camera".to_owned());
+            return;
+        };
+
+        // The camera's x axis is on the right in PBRT's image and on the left
+        // in ours, unless the transform already mirrors the scene
+        self.mirror = if camera_from_world.linear.determinant() > 0.0 {
+            let flip = Affine::new(Mat3::from_scale(&Vec3::new(-1.0, 1.0, 1.0)), Vec3::ZERO);
+            camera_from_world.then(&flip).then(&world_from_camera)
+        } else {
+            Affine::IDENTITY
+        };
+
+        self.coordinate_systems
+            .insert("camera".to_owned(), world_from_camera.clone());
+        self.world_from_camera = world_from_camera;
+    }
+
+    fn add_texture(&mut self, prefix: &str, spec: TextureSpec) -> String {
+        if let Some((key, _)) = self.textures.iter().find(|(_, other)| *other == spec) {
+            return key.clone();
+        }
+
+        let key = unique_key(prefix, |key| self.textures.iter().any(|(k, _)| k == key));
+        self.textures.push((key.clone(), spec));
+        key
+    }
+
+    fn add_material(&mut self, spec: MaterialSpec) -> String {
+        if let Some((key, _)) = self.materials.iter().find(|(_, other)| *other == spec) {
+            return key.clone();
+        }
+
+        let key = unique_key(spec.kind(), |key| {
+            self.materials.iter().any(|(k, _)| k == key)
+        });
+        self.materials.push((key.clone(), spec));
+        key
+    }
+
+    fn texture(&mut self, name: &str, ty: &str, class: &str, params: &Params) {
+        let spec = match (ty, class) {
+            ("float", _) => {
+                self.warn(format!("float texture '{name}' skipped"));
+                None
+            }
+            (_, "imagemap") => match params.string("filename") {
+                Some(filename) => Some(TextureSpec::Image {
+                    path: self.dir.join(filename),
+                }),
+                None => {
+                    self.warn(format!("image texture '{name}' has no file name"));
+                    None
+                }
+            },
+            (_, "constant") => Some(TextureSpec::SolidColor {
+                albedo: self.color(params, "value", 1.0),
+            }),
+            (_, class) => {
+                self.warn(format!("{class} texture '{name}' skipped"));
+                None
+            }
+        };
+
+        let key = spec.map(|spec| {
+            let key = unique_key(name, |key| self.textures.iter().any(|(k, _)| k == key));
+            self.textures.push((key.clone(), spec));
+            key
+        });
+        self.texture_keys.insert(name.to_owned(), key);
+    }
+
+    /// Constant color of a parameter, textures and spectra are approximated
+    fn color(&mut self, params: &Params, name: &str, default: f64) -> Color {
+        let gray = |value: f64| Color::new(value, value, value);
+        let Some(param) = params.get(name) else {
+            return gray(default);
+        };
+
+        match (param.ty.as_str(), params.floats(name)) {
+            ("rgb" | "color", Some(rgb)) if rgb.len() == 3 => Color::new(rgb[0], rgb[1], rgb[2]),
+            ("float", Some(values)) if !values.is_empty() => gray(values[0]),
+            ("spectrum", Some(samples)) if samples.len() >= 2 => {
+                // Wavelength and value pairs, averaged to a gray
+                let values: Vec<f64> = samples.iter().skip(1).step_by(2).copied().collect();
+                gray(values.iter().sum::<f64>() / values.len() as f64)
+            }
+            ("blackbody", _) => {
+                self.warn(format!("blackbody '{name}' approximated as white"));
+                gray(1.0)
+            }
+            (ty, _) => {
+                self.warn(format!("{ty} '{name}' not supported, using {default}"));
+                gray(default)
+            }
+        }
+    }
+
+    /// Texture key of a color parameter that may name a texture
+    fn texture_param(&mut self, params: &Params, names: &[&str], default: f64) -> String {
+        let name = names
+            .iter()
+            .copied()
+            .find(|name| params.has(name))
+            .unwrap_or(names[0]);
+
+        if let Some(param) = params.get(name).filter(|param| param.ty == "texture") {
+            let texture = params.string(&param.name).unwrap_or_default().to_owned();
+            match self.texture_keys.get(&texture) {
+                Some(Some(key)) => return key.clone(),
+                _ => self.warn(format!(
+                    "texture '{texture}' is not available, using a constant {name}"
+                )),
+            }
+            let albedo = Color::new(default, default, default);
+            return self.add_texture("color", TextureSpec::SolidColor { albedo });
+        }
+
+        let albedo = self.color(params, name, default);
+        self.add_texture("color", TextureSpec::SolidColor { albedo })
+    }
+
+    /// Material for a material type, `None` for interfaces that have no surface
+    fn material(&mut self, ty: &str, params: &Params) -> Option<MaterialSpec> {
+        Some(match ty {
+            "diffuse" | "matte" => MaterialSpec::Lambertian {
+                texture: self.texture_param(params, &["reflectance", "Kd"], 0.5),
+            },
+            "conductor" | "metal" => {
+                let default = if ty == "metal" { 0.01 } else { 0.0 };
+                let (roughness, roughness_v) = self.conductor_roughness(params, default);
+                MaterialSpec::Conductor {
+                    ior: self.conductor_ior(params),
+                    roughness,
+                    roughness_v,
+                }
+            }
+            "mirror" => MaterialSpec::Metal {
+                albedo: self.color(params, "Kr", 0.9),
+                fuzz: 0.0,
+            },
+            "dielectric" | "glass" | "thindielectric" => {
+                if ty == "thindielectric" {
+                    self.warn("thin dielectrics are rendered as solid glass".to_owned());
+                }
+                if self.is_rough(params) {
+                    self.warn("rough dielectrics are rendered smooth".to_owned());
+                }
+                MaterialSpec::Dielectric {
+                    refraction_index: self.refraction_index(params),
+                }
+            }
+            "interface" | "none" | "" => return None,
+            "mix" => {
+                // Use the first of the mixed materials
+                let first = params.strings("materials").first().map(|m| m.to_string());
+                self.warn("mixed materials use their first material".to_owned());
+                match first.and_then(|name| self.materials.iter().find(|(key, _)| *key == name)) {
+                    Some((_, spec)) => spec.clone(),
+                    None => MaterialSpec::Lambertian {
+                        texture: self.texture_param(params, &["reflectance"], 0.5),
+                    },
+                }
+            }
+            ty => {
+                self.warn(format!("material '{ty}' approximated as diffuse"));
+                MaterialSpec::Lambertian {
+                    texture: self.texture_param(params, &["reflectance", "Kd"], 0.5),
+                }
+            }
+        })
+    }
+
+    /// Whether a dielectric asks for any roughness, which only gets a warning
+    /// as our glass is always smooth
+    fn is_rough(&mut self, params: &Params) -> bool {
+        self.check_roughness_textures(params);
+        ["roughness", "uroughness", "vroughness"]
+            .iter()
+            .any(|name| params.float(name).is_some_and(|r| r > 0.0))
+    }
+
+    fn check_roughness_textures(&mut self, params: &Params) {
+        if ["roughness", "uroughness", "vroughness"]
+            .iter()
+            .any(|name| params.get(name).is_some_and(|p| p.ty == "texture"))
+        {
+            self.warn("roughness textures are not supported".to_owned());
+        }
+    }
+
+    /// Roughness along u and v, `None` for v when they are the same. PBRT
+    /// takes the square root of remapped roughnesses for the GGX alpha, ours
+    /// squares it.
+    fn conductor_roughness(&mut self, params: &Params, default: f64) -> (f64, Option<f64>) {
+        self.check_roughness_textures(params);
+
+        let isotropic = params.float("roughness").unwrap_or(default);
+        let u = params.float("uroughness").unwrap_or(isotropic);
+        let v = params.float("vroughness").unwrap_or(isotropic);
+        let remap = match params.get("remaproughness").and_then(|p| p.values.first()) {
+            Some(Value::Bool(false)) => false,
+            // PBRT-v3 quotes its booleans
+            Some(Value::Str(remap)) => remap != "false",
+            _ => true,
+        };
+        let [u, v] = [u, v].map(|r| {
+            let r = r.max(0.0);
+            if remap { r.sqrt().sqrt() } else { r.sqrt() }.min(1.0)
+        });
+
+        (u, (u != v).then_some(v))
+    }
+
+    /// Complex index of refraction from named spectra, RGB values or a
+    /// reflectance at normal incidence
+    fn conductor_ior(&mut self, params: &Params) -> ComplexIor {
+        if params.has("reflectance") {
+            // A metal with eta 1 reflects k^2 / (4 + k^2) head on
+            let r = self.color(params, "reflectance", 1.0);
+            let k = |r: f64| {
+                let r = r.clamp(0.0, 0.9999);
+                2.0 * (r / (1.0 - r)).sqrt()
+            };
+            return ComplexIor::Custom {
+                eta: Color::new(1.0, 1.0, 1.0),
+                k: Color::new(k(r.x()), k(r.y()), k(r.z())),
+            };
+        }
+
+        // The preset a parameter names, if any, and its channels
+        let mut channels = |name: &str, pick: fn((Color, Color)) -> Color| match params.get(name) {
+            Some(param) if param.ty == "spectrum" && params.string(name).is_some() => {
+                let spectrum = params.string(name).unwrap_or_default();
+                let metal = spectrum
+                    .strip_prefix("metal-")
+                    .and_then(|s| s.rsplit_once('-'))
+                    .and_then(|(metal, _)| METALS.iter().find(|(m, _)| *m == metal));
+                let ior = match metal {
+                    Some((_, ior)) => ior.clone(),
+                    None => {
+                        self.warn(format!("spectrum '{spectrum}' not known, using copper"));
+                        ComplexIor::Copper
+                    }
+                };
+                let color = pick(ior.eta_k());
+                (Some(ior), color)
+            }
+            Some(_) => (None, self.color(params, name, 1.0)),
+            None => (Some(ComplexIor::Copper), pick(ComplexIor::Copper.eta_k())),
+        };
+        let (eta_preset, eta) = channels("eta", |(eta, _)| eta);
+        let (k_preset, k) = channels("k", |(_, k)| k);
+
+        match (eta_preset, k_preset) {
+            (Some(eta), Some(k)) if eta == k => eta,
+            _ => ComplexIor::Custom { eta, k },
+        }
+    }
+
+    fn refraction_index(&mut self, params: &Params) -> f64 {
+        let name = if params.has("index") { "index" } else { "eta" };
+        if let Some(index) = params.float(name) {
+            return index;
+        }
+
+        match params.string(name) {
+            Some(spectrum) => match GLASSES.iter().find(|(glass, _)| *glass == spectrum) {
+                Some((_, index)) => *index,
+                None => {
+                    self.warn(format!("spectrum '{spectrum}' not known, using 1.5"));
+                    1.5
+                }
+            },
+            None => 1.5,
+        }
+    }
+
+    fn area_light(&mut self, ty: &str, params: &Params) {
+        if ty != "diffuse" {
+            self.warn(format!("area light '{ty}' skipped"));
+            return;
+        }
+        if params.has("power") {
+            self.warn("area light power is ignored, using the radiance".to_owned());
+        }
+
+        let scale = params.float("scale").unwrap_or(1.0);
+        let albedo = scale * self.color(params, "L", 1.0);
+        let texture = self.add_texture("emission", TextureSpec::SolidColor { albedo });
+        self.state.area_light = Some(self.add_material(MaterialSpec::DiffuseLight { texture }));
+    }
+
+    fn shape(&mut self, ty: &str, params: &Params) -> anyhow::Result<()> {
+        let material = match (&self.state.area_light, self.state.surface.clone()) {
+            (Some(light), _) => light.clone(),
+            (None, Surface::Material(material)) => material,
+            (None, Surface::Default) => {
+                let texture = self.add_texture(
+                    "color",
+                    TextureSpec::SolidColor {
+                        albedo: Color::new(0.5, 0.5, 0.5),
+                    },
+                );
+                self.add_material(MaterialSpec::Lambertian { texture })
+            }
+            (None, Surface::Interface) => return Ok(()),
+        };
+
+        // Shapes of an object are mirrored with each instance
+        let to_world = match self.object {
+            Some(_) => self.state.ctm.clone(),
+            None => self.state.ctm.then(&self.mirror),
+        };
+
+        let shape = match ty {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"]
+                    .iter()
+                    .any(|name| params.has(name))
+                {
+                    self.warn("partial spheres are rendered whole".to_owned());
+                }
+                let radius = params.float("radius").unwrap_or(1.0);
+                match similarity_scale(&to_world.linear) {
+                    Some(scale) => ShapeSpec::Sphere {
+                        center: to_world.translation.clone(),
+                        radius: radius * scale,
+                        material,
+                        motion: None,
+                    },
+                    None => {
+                        let sphere = ShapeSpec::Sphere {
+                            center: Point3::ZERO,
+                            radius,
+                            material,
+                            motion: None,
+                        };
+                        match decompose(&to_world, &mut self.warnings) {
+                            Some(transform) => ShapeSpec::Transform {
+                                shape: Box::new(sphere),
+                                transform,
+                            },
+                            None => return Ok(()),
+                        }
+                    }
+                }
+            }
+            "trianglemesh" => match self.triangle_mesh(params, &to_world, material)? {
+                Some(mesh) => mesh,
+                None => return Ok(()),
+            },
+            "plymesh" => {
+                let filename = params
+                    .string("filename")
+                    .context("plymesh needs a \"string filename\"")?;
+                if filename.ends_with(".gz") {
+                    self.warn(format!("compressed PLY file '{filename}' skipped"));
+                    return Ok(());
+                }
+                if params.has("displacement") {
+                    self.warn("mesh displacement is ignored".to_owned());
+                }
+
+                let ply = ShapeSpec::Ply {
+                    path: self.dir.join(filename),
+                    material,
+                };
+                if to_world == Affine::IDENTITY {
+                    ply
+                } else {
+                    match decompose(&to_world, &mut self.warnings) {
+                        Some(transform) => ShapeSpec::Transform {
+                            shape: Box::new(ply),
+                            transform,
+                        },
+                        None => return Ok(()),
+                    }
+                }
+            }
+            ty => {
+                self.warn(format!("shape '{ty}' skipped"));
+                return Ok(());
+            }
+        };
+
+        match &mut self.object {
+            Some(object) => object.shapes.push(shape),
+            None => self.shapes.push(shape),
+        }
+
+        Ok(())
+    }
+
+    /// Inline mesh with the transform baked into its vertices, `None` when
+    /// its indices or vertex data don't fit together
+    fn triangle_mesh(
+        &mut self,
+        params: &Params,
+        to_world: &Affine,
+        material: String,
+    ) -> anyhow::Result<Option<ShapeSpec>> {
+        let points = params
+            .floats("P")
+            .context("trianglemesh needs \"point3 P\"")?;
+        anyhow::ensure!(points.len() % 3 == 0, "P has a partial point");
+        let indices = match params.floats("indices") {
+            Some(indices) => indices,
+            None if points.len() == 9 => vec![0.0, 1.0, 2.0],
+            None => anyhow::bail!("trianglemesh needs \"integer indices\""),
+        };
+        anyhow::ensure!(indices.len() % 3 == 0, "indices has a partial triangle");
+        if let Some(index) = indices.iter().find(|&&i| i < 0.0 || i.fract() != 0.0) {
+            self.warn(format!("trianglemesh with invalid index {index} skipped"));
+            return Ok(None);
+        }
+
+        let uv_name = ["uv", "st"]
+            .into_iter()
+            .find(|name| params.has(name))
+            .unwrap_or("uv");
+        let mut mesh = MeshData {
+            positions: points
+                .chunks_exact(3)
+                .map(|p| Point3::new(p[0], p[1], p[2]))
+                .collect(),
+            indices: indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]].map(|i| i as usize))
+                .collect(),
+            normals: params
+                .floats("N")
+                .unwrap_or_default()
+                .chunks_exact(3)
+                .map(|n| Vec3::new(n[0], n[1], n[2]))
+                .collect(),
+            uvs: params
+                .floats(uv_name)
+                .unwrap_or_default()
+                .chunks_exact(2)
+                .map(|uv| [uv[0], uv[1]])
+                .collect(),
+            colors: Vec::new(),
+        };
+        if let Err(e) = mesh.validate() {
+            self.warn(format!("trianglemesh skipped: {e}"));
+            return Ok(None);
+        }
+
+        if !mesh.normals.is_empty() && to_world.linear.inverse().is_none() {
+            self.warn("trianglemesh normals dropped, its transform flattens it".to_owned());
+        }
+        mesh.transform(to_world);
+        if self.state.reverse_orientation {
+            mesh.reverse();
+        }
+
+        Ok(Some(mesh_spec(mesh, material)))
+    }
+
+    fn end_object(&mut self, object: Object) {
+        let Object { name, mut shapes } = object;
+        let shape = match shapes.len() {
+            0 => {
+                self.warn(format!("object '{name}' is empty"));
+                return;
+            }
+            1 => shapes.remove(0),
+            _ => ShapeSpec::List(shapes),
+        };
+
+        self.prototypes.retain(|(key, _)| *key != name);
+        self.prototypes.push((name, shape));
+    }
+
+    fn instance(&mut self, name: &str) {
+        if !self.prototypes.iter().any(|(key, _)| key == name) {
+            self.warn(format!("unknown object '{name}' skipped"));
+            return;
+        }
+
+        let to_world = self.state.ctm.then(&self.mirror);
+        if let Some(transform) = decompose(&to_world, &mut self.warnings) {
+            self.shapes.push(ShapeSpec::Instance {
+                prototype: name.to_owned(),
+                transform,
+            });
+        }
+    }
+
+    fn camera(&mut self) -> CameraSpec {
+        if self.camera_type != "perspective" {
+            let ty = self.camera_type.clone();
+            self.warn(format!("{ty} camera rendered as a perspective camera"));
+        }
+        if self.film.has("cropwindow") || self.camera_params.has("screenwindow") {
+            self.warn("crop and screen windows are ignored".to_owned());
+        }
+
+        let width = self.film.float("xresolution").unwrap_or(1280.0);
+        let height = self.film.float("yresolution").unwrap_or(720.0);
+
+        // PBRT's field of view spans the shorter image axis
+        let fov = self.camera_params.float("fov").unwrap_or(90.0);
+        let vfov = if width >= height {
+            fov
+        } else {
+            2.0 * ((fov / 2.0).to_radians().tan() * height / width)
+                .atan()
+                .to_degrees()
+        };
+
+        let from = &self.world_from_camera;
+        let lookfrom = from.transform_point(&Point3::ZERO);
+        let direction = from
+            .transform_vector(&Vec3::new(0.0, 0.0, 1.0))
+            .unit_vector();
+
+        // Focus only matters with a lens, PBRT's default distance is too far
+        // away for our viewport
+        let lens_radius = self.camera_params.float("lensradius").unwrap_or(0.0);
+        let focus_dist =
+            (lens_radius > 0.0).then(|| self.camera_params.float("focaldistance").unwrap_or(1e6));
+
+        CameraSpec {
+            aspect_ratio: Some(width / height),
+            image_width: Some(width as i32),
+            samples_per_pixel: Some(self.sampler.float("pixelsamples").unwrap_or(16.0) as i32),
+            max_depth: Some(self.integrator.float("maxdepth").unwrap_or(5.0) as i32),
+            vfov: Some(vfov),
+            lookat: Some(&lookfrom + &direction),
+            lookfrom: Some(lookfrom),
+            vup: Some(from.transform_vector(&Vec3::new(0.0, 1.0, 0.0))),
+            defocus_angle: Some(focus_dist.map_or(0.0, |focus_dist| {
+                2.0 * (lens_radius / focus_dist).atan().to_degrees()
+            })),
+            focus_dist,
+        }
+    }
+
+    fn finish(mut self) -> anyhow::Result<Imported> {
+        if self.object.is_some() {
+            self.warn("missing 'ObjectEnd'".to_owned());
+        }
+
+        let mut scene = SceneFile::new();
+        scene.set_camera(Some(self.camera()));
+        for (name, spec) in self.textures {
+            scene.add_texture(name, spec);
+        }
+        for (name, spec) in self.materials {
+            scene.add_material(name, spec);
+        }
+        for (name, spec) in self.prototypes {
+            scene.add_prototype(name, spec);
+        }
+        for spec in self.shapes {
+            scene.add_shape(spec);
+        }
+
+        Ok(Imported {
+            scene,
+            warnings: self.warnings,
+            assets: Vec::new(),
+        })
+    }
+}
+
+/// Matrix of `Transform` and `ConcatTransform`, given column by column
+fn matrix(m: [f64; 16]) -> Affine {
+    let linear = Mat3::new([0, 1, 2].map(|i| [m[i], m[4 + i], m[8 + i]]));
+    Affine::new(linear, Vec3::new(m[12], m[13], m[14]))
+}
+
+/// PBRT's camera from world transform of `LookAt`
+fn look_at(eye: Point3, target: Point3, up: Vec3) -> Option<Affine> {
+    let direction = (&target - &eye).unit_vector();
+    let right = up.unit_vector().cross(&direction);
+    if right.length() < 1e-9 {
+        return None;
+    }
+    let right = right.unit_vector();
+    let up = direction.cross(&right);
+
+    Affine::new(from_columns(&[right, up, direction]), eye).inverse()
+}
@@ -0,0 +1,399 @@
+//! Stanford PLY meshes in ASCII or binary, either endianness. Vertices may
+//! carry normals, texture coordinates and colors, faces with more than three
+//! vertices are split into triangle fans.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    color::{Color, srgb_to_linear},
+    hittable::mesh::MeshData,
+    vec::{Point3, Vec3},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => anyhow::bail!("unknown property type '{name}'"),
+        })
+    }
+
+    /// Largest value of integer types, which colors are stored relative to
+    fn max(self) -> Option<f64> {
+        match self {
+            Self::I8 => Some(i8::MAX as f64),
+            Self::U8 => Some(u8::MAX as f64),
+            Self::I16 => Some(i16::MAX as f64),
+            Self::U16 => Some(u16::MAX as f64),
+            Self::I32 => Some(i32::MAX as f64),
+            Self::U32 => Some(u32::MAX as f64),
+            Self::F32 | Self::F64 => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(Scalar, String),
+    List {
+        count: Scalar,
+        item: Scalar,
+        name: String,
+    },
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+/// Values of the body, read one at a time in file order
+enum Body<'a> {
+    Ascii(&'a str),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> anyhow::Result<f64> {
+        match self {
+            Self::Ascii(text) => {
+                let rest = text.trim_ascii_start();
+                let end = rest.find(|c: char| c.is_ascii_whitespace());
+                let (token, rest) = rest.split_at(end.unwrap_or(rest.len()));
+                *text = rest;
+
+                anyhow::ensure!(!token.is_empty(), "unexpected end of data");
+                token
+                    .parse()
+                    .with_context(|| format!("invalid number '{token}'"))
+            }
+            Self::Binary { bytes, big_endian } => {
+                let size = scalar.size();
+                anyhow::ensure!(bytes.len() >= size, "unexpected end of data");
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let mut buf = [0; 8];
+                buf[..size].copy_from_slice(value);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                Ok(match scalar {
+                    Scalar::I8 => buf[0] as i8 as f64,
+                    Scalar::U8 => buf[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(buf[..4].try_into()?) as f64,
+                    Scalar::U32 => u32::from_le_bytes(buf[..4].try_into()?) as f64,
+                    Scalar::F32 => f32::from_le_bytes(buf[..4].try_into()?) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buf),
+                })
+            }
+        }
+    }
+
+    /// Length of a list of `item`s, checked against the data left so a
+    /// corrupt length fails before anything is allocated for it
+    fn read_len(&mut self, count: Scalar, item: Scalar) -> anyhow::Result<usize> {
+        let n = self.read(count)?;
+        anyhow::ensure!(n >= 0.0 && n.fract() == 0.0, "invalid list length {n}");
+
+        let left = match self {
+            // Every value but the last is followed by a separator
+            Self::Ascii(text) => text.len().div_ceil(2),
+            Self::Binary { bytes, .. } => bytes.len() / item.size(),
+        };
+        anyhow::ensure!(
+            n <= left as f64,
+            "list of {n} values is longer than the data left"
+        );
+        Ok(n as usize)
+    }
+}
+
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<MeshData> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read PLY file: {path:?}"))?;
+    parse(&bytes).with_context(|| format!("Failed to load PLY file: {path:?}"))
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<MeshData> {
+    let (header, body) = parse_header(bytes)?;
+
+    let mut body = match header.format {
+        Format::Ascii => {
+            Body::Ascii(std::str::from_utf8(body).context("ASCII PLY data is not valid text")?)
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            bytes: body,
+            big_endian: header.format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = MeshData::default();
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut body, &mut mesh),
+            "face" => read_faces(element, &mut body, &mut mesh),
+            _ => skip(element, &mut body),
+        }
+        .with_context(|| format!("in element '{}'", element.name))?;
+    }
+
+    mesh.validate()?;
+    Ok(mesh)
+}
+
+fn parse_header(bytes: &[u8]) -> anyhow::Result<(Header, &[u8])> {
+    const END: &[u8] = b"end_header";
+
+    let end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .context("missing 'end_header'")?;
+    // The body starts after the line break that ends the header
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| end + i + 1);
+    let text = std::str::from_utf8(&bytes[..end]).context("header is not valid text")?;
+
+    let mut lines = text.lines().map(str::trim).enumerate();
+    match lines.next() {
+        Some((_, "ply")) => {}
+        _ => anyhow::bail!("not a PLY file, expected 'ply' on the first line"),
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (number, line) in lines {
+        let fail = |message: &str| anyhow::anyhow!("header line {}: {message}", number + 1);
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", kind, version] => {
+                if *version != "1.0" {
+                    return Err(fail(&format!("unsupported version '{version}'")));
+                }
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(fail(&format!("unknown format '{kind}'"))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| fail(&format!("invalid element count '{count}'")))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| fail("property before any element"))?;
+                element.properties.push(Property::List {
+                    count: Scalar::parse(count).map_err(|e| fail(&e.to_string()))?,
+                    item: Scalar::parse(item).map_err(|e| fail(&e.to_string()))?,
+                    name: name.to_string(),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| fail("property before any element"))?;
+                let kind = Scalar::parse(kind).map_err(|e| fail(&e.to_string()))?;
+                element
+                    .properties
+                    .push(Property::Scalar(kind, name.to_string()));
+            }
+            _ => return Err(fail(&format!("unexpected '{line}'"))),
+        }
+    }
+
+    let format = format.context("missing 'format' line")?;
+    Ok((Header { format, elements }, &bytes[body_start..]))
+}
+
+fn read_vertices(element: &Element, body: &mut Body, mesh: &mut MeshData) -> anyhow::Result<()> {
+    let find = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .position(|p| matches!(p, Property::Scalar(_, name) if names.contains(&name.as_str())))
+    };
+    let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+    let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+    let uv = [
+        find(&["u", "s", "texture_u", "texture_s"]),
+        find(&["v", "t", "texture_v", "texture_t"]),
+    ];
+    let color = [
+        find(&["red", "diffuse_red", "r"]),
+        find(&["green", "diffuse_green", "g"]),
+        find(&["blue", "diffuse_blue", "b"]),
+    ];
+
+    let [Some(x), Some(y), Some(z)] = position else {
+        anyhow::bail!("vertices need x, y and z properties");
+    };
+    let normal = match normal {
+        [Some(x), Some(y), Some(z)] => Some([x, y, z]),
+        _ => None,
+    };
+    let uv = match uv {
+        [Some(u), Some(v)] => Some([u, v]),
+        _ => None,
+    };
+    let color = match color {
+        [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+        _ => None,
+    };
+    // Integer colors are 8 or 16 bit sRGB, floats are already linear
+    let decode = |index: usize, value: f64| match &element.properties[index] {
+        Property::Scalar(kind, _) => match kind.max() {
+            Some(max) => srgb_to_linear((value / max).clamp(0.0, 1.0)),
+            None => value,
+        },
+        Property::List { .. } => value,
+    };
+
+    let mut values = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            *value = match property {
+                Property::Scalar(kind, _) => body.read(*kind)?,
+                Property::List { count, item, .. } => {
+                    skip_list(body, *count, *item)?;
+                    0.0
+                }
+            };
+        }
+
+        mesh.positions
+            .push(Point3::new(values[x], values[y], values[z]));
+        if let Some([x, y, z]) = normal {
+            mesh.normals
+                .push(Vec3::new(values[x], values[y], values[z]));
+        }
+        if let Some([u, v]) = uv {
+            mesh.uvs.push([values[u], values[v]]);
+        }
+        if let Some([r, g, b]) = color {
+            mesh.colors.push(Color::new(
+                decode(r, values[r]),
+                decode(g, values[g]),
+                decode(b, values[b]),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(element: &Element, body: &mut Body, mesh: &mut MeshData) -> anyhow::Result<()> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|p| {
+            matches!(p, Property::List { name, .. } if name == "vertex_indices" || name == "vertex_index")
+        })
+        .context("faces need a 'vertex_indices' list")?;
+
+    for _ in 0..element.count {
+        for (i, property) in element.properties.iter().enumerate() {
+            match property {
+                Property::Scalar(kind, _) => {
+                    body.read(*kind)?;
+                }
+                Property::List { count, item, .. } if i == indices => {
+                    let n = body.read_len(*count, *item)?;
+                    let mut face = Vec::with_capacity(n);
+                    for _ in 0..n {
+                        let index = body.read(*item)?;
+                        anyhow::ensure!(index >= 0.0, "negative vertex index {index}");
+                        face.push(index as usize);
+                    }
+
+                    for k in 1..n.saturating_sub(1) {
+                        mesh.indices.push([face[0], face[k], face[k + 1]]);
+                    }
+                }
+                Property::List { count, item, .. } => skip_list(body, *count, *item)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip(element: &Element, body: &mut Body) -> anyhow::Result<()> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::Scalar(kind, _) => {
+                    body.read(*kind)?;
+                }
+                Property::List { count, item, .. } => skip_list(body, *count, *item)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_list(body: &mut Body, count: Scalar, item: Scalar) -> anyhow::Result<()> {
+    let n = body.read_len(count, item)?;
+    for _ in 0..n {
+        body.read(item)?;
+    }
+
+    Ok(())
+}
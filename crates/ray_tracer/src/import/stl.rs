@@ -0,0 +1,119 @@
+//! STL meshes in ASCII or binary. STL doesn't share vertices between
+//! facets, so every triangle gets its own three. Facet normals are ignored,
+//! the winding gives the same front face.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{hittable::mesh::MeshData, vec::Point3};
+
+/// Bytes of a binary file before the first triangle: header and count
+const BINARY_HEADER: usize = 84;
+/// Bytes of a binary triangle: normal, three corners and attribute count
+const BINARY_TRIANGLE: usize = 50;
+
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<MeshData> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read STL file: {path:?}"))?;
+    parse(&bytes).with_context(|| format!("Failed to load STL file: {path:?}"))
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<MeshData> {
+    // Binary files may start with "solid" too, so the size decides first
+    if let Some(count) = binary_count(bytes)
+        && BINARY_HEADER + count * BINARY_TRIANGLE == bytes.len()
+    {
+        return parse_binary(&bytes[BINARY_HEADER..], count);
+    }
+
+    let text = std::str::from_utf8(bytes).ok();
+    match text {
+        Some(text) if text.trim_start().starts_with("solid") => parse_ascii(text),
+        _ => match binary_count(bytes) {
+            Some(count) => anyhow::bail!(
+                "binary STL declares {count} triangles, which needs {} bytes but the file has {}",
+                BINARY_HEADER + count * BINARY_TRIANGLE,
+                bytes.len()
+            ),
+            None => anyhow::bail!("not an STL file, too short for a binary header"),
+        },
+    }
+}
+
+fn binary_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(80..BINARY_HEADER)?;
+    Some(u32::from_le_bytes(count.try_into().ok()?) as usize)
+}
+
+fn parse_binary(body: &[u8], count: usize) -> anyhow::Result<MeshData> {
+    let mut mesh = MeshData::default();
+    for triangle in body.chunks_exact(BINARY_TRIANGLE).take(count) {
+        let value = |i: usize| {
+            let start = 12 + 4 * i;
+            f32::from_le_bytes(triangle[start..start + 4].try_into().unwrap()) as f64
+        };
+
+        let start = mesh.positions.len();
+        for corner in 0..3 {
+            let i = 3 * corner;
+            mesh.positions
+                .push(Point3::new(value(i), value(i + 1), value(i + 2)));
+        }
+        mesh.indices.push([start, start + 1, start + 2]);
+    }
+
+    Ok(mesh)
+}
+
+fn parse_ascii(text: &str) -> anyhow::Result<MeshData> {
+    let mut mesh = MeshData::default();
+    let mut in_solid = false;
+    // Corners of the facet being read
+    let mut facet: Option<Vec<Point3>> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let fail = |message: &str| anyhow::anyhow!("line {}: {message}", number + 1);
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match (words.as_slice(), &mut facet) {
+            ([], _) => {}
+            (["solid", ..], None) if !in_solid => in_solid = true,
+            (["endsolid", ..], None) if in_solid => in_solid = false,
+            (["facet", "normal", _, _, _], None) if in_solid => facet = Some(Vec::new()),
+            (["outer", "loop"] | ["endloop"], Some(_)) => {}
+            (["vertex", coords @ ..], Some(corners)) => {
+                let [x, y, z] = coords else {
+                    return Err(fail("vertex needs three coordinates"));
+                };
+                let [x, y, z] = [x, y, z].map(|c| {
+                    c.parse::<f64>()
+                        .map_err(|_| fail(&format!("invalid number '{c}'")))
+                });
+                corners.push(Point3::new(x?, y?, z?));
+            }
+            (["endfacet"], Some(corners)) => {
+                if corners.len() < 3 {
+                    return Err(fail(&format!(
+                        "facet has {} vertices, expected 3",
+                        corners.len()
+                    )));
+                }
+
+                // Split polygons into triangle fans
+                let start = mesh.positions.len();
+                for k in 1..corners.len() - 1 {
+                    mesh.indices.push([start, start + k, start + k + 1]);
+                }
+                mesh.positions.append(corners);
+                facet = None;
+            }
+            _ => return Err(fail(&format!("unexpected '{}'", line.trim()))),
+        }
+    }
+
+    anyhow::ensure!(facet.is_none(), "missing 'endfacet' at end of file");
+    anyhow::ensure!(!in_solid, "missing 'endsolid' at end of file");
+    Ok(mesh)
+}
@@ -3,13 +3,20 @@ use core::f64;
 pub mod aabb;
 pub mod camera;
 pub mod color;
+pub mod denoise;
+pub mod export;
 pub mod hittable;
 pub mod image;
+pub mod import;
 pub mod interval;
 pub mod material;
+pub mod matrix;
+pub mod onb;
 pub mod perlin;
 pub mod ray;
 pub mod scene_loader;
+pub mod scenes;
+pub mod stats;
 pub mod texture;
 pub mod vec;
 
@@ -5,10 +5,11 @@ use serde::{Deserialize, Serialize};
 use crate::{
     color::Color,
     hittable::HitRecord,
+    onb::Onb,
     ray::Ray,
     scene_loader::{MaterialSpec, ResourceRegistry},
     texture::{DynTexture, SolidColor},
-    vec::Vec3,
+    vec::{Point3, Vec3},
 };
 
 pub struct ScatterRecord {
@@ -21,6 +22,14 @@ pub type DynMaterial = dyn Material + Send + Sync;
 pub trait Material {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
 
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::ZERO
+    }
+
+    /// Base color of the surface at the hit, without sampling. Guides the
+    /// denoiser.
+    fn albedo(&self, rec: &HitRecord) -> Color;
+
     fn to_spec(&self, registry: &mut ResourceRegistry) -> MaterialSpec;
 
     fn name(&self) -> &str;
@@ -28,16 +37,22 @@ pub trait Material {
 
 #[derive(Clone)]
 pub struct Lambertian {
+    name: String,
     tex: Arc<DynTexture>,
 }
 
 impl Lambertian {
     pub fn new(name: impl Into<String>, albedo: Color) -> Self {
-        Self::from_texture(Arc::new(SolidColor::new(name, albedo)))
+        let name = name.into();
+        let tex_name = format!("{name}_albedo");
+        Self::from_texture(name, Arc::new(SolidColor::new(tex_name, albedo)))
     }
 
-    pub fn from_texture(texture: Arc<DynTexture>) -> Self {
-        Self { tex: texture }
+    pub fn from_texture(name: impl Into<String>, texture: Arc<DynTexture>) -> Self {
+        Self {
+            name: name.into(),
+            tex: texture,
+        }
     }
 }
 
@@ -51,11 +66,15 @@ impl Material for Lambertian {
         }
 
         Some(ScatterRecord {
-            attenuation: self.tex.value(rec.u, rec.v, &rec.p),
+            attenuation: self.albedo(rec),
             scattered: Ray::new_with_time(rec.p.clone(), scatter_direction, r_in.time()),
         })
     }
 
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        rec.tint(self.tex.value(rec.u, rec.v, &rec.p))
+    }
+
     fn to_spec(&self, registry: &mut ResourceRegistry) -> MaterialSpec {
         let tex = self.tex.to_spec(registry);
         registry.register_texture(self.tex.name().to_owned(), tex);
@@ -66,7 +85,7 @@ impl Material for Lambertian {
     }
 
     fn name(&self) -> &str {
-        self.tex.name()
+        &self.name
     }
 }
 
@@ -95,7 +114,7 @@ impl Material for Metal {
         let scattered = Ray::new_with_time(rec.p.clone(), reflected, r_in.time());
         if scattered.direction().dot(&rec.normal) > 0.0 {
             Some(ScatterRecord {
-                attenuation: self.albedo.clone(),
+                attenuation: self.albedo(rec),
                 scattered,
             })
         } else {
@@ -103,6 +122,10 @@ impl Material for Metal {
         }
     }
 
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        rec.tint(self.albedo.clone())
+    }
+
     fn to_spec(&self, _registry: &mut ResourceRegistry) -> MaterialSpec {
         MaterialSpec::Metal {
             albedo: self.albedo.clone(),
@@ -115,6 +138,187 @@ impl Material for Metal {
     }
 }
 
+/// Complex index of refraction `eta + i k` of a metal, per color channel at
+/// roughly 650, 550 and 450nm
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ComplexIor {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+    Custom {
+        eta: Color,
+        /// Extinction coefficient, how quickly light dies out in the metal
+        k: Color,
+    },
+}
+
+impl ComplexIor {
+    pub fn eta_k(&self) -> (Color, Color) {
+        let rgb = |[r, g, b]: [f64; 3]| Color::new(r, g, b);
+        let (eta, k) = match self {
+            Self::Gold => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            Self::Copper => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            Self::Aluminium => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            Self::Silver => ([0.155, 0.116, 0.138], [4.828, 3.122, 2.147]),
+            Self::Custom { eta, k } => return (eta.clone(), k.clone()),
+        };
+
+        (rgb(eta), rgb(k))
+    }
+
+    /// Fraction of light reflected at `cos_theta` from the normal
+    pub fn fresnel(&self, cos_theta: f64) -> Color {
+        let (eta, k) = self.eta_k();
+        Color::new(
+            fresnel_conductor(cos_theta, eta.x(), k.x()),
+            fresnel_conductor(cos_theta, eta.y(), k.y()),
+            fresnel_conductor(cos_theta, eta.z(), k.z()),
+        )
+    }
+}
+
+/// Exact Fresnel reflectance of a conductor in air for unpolarized light
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_theta.clamp(0.0, 1.0) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rs + rp)
+}
+
+/// Metal with a GGX (Trowbridge-Reitz) distribution of microfacets. Only the
+/// visible microfacets are sampled and each bounce is weighted by the Fresnel
+/// term and the chance it isn't shadowed on the way out, so rough metals stay
+/// energy conserving up to the light lost to multiple bounces.
+///
+/// Roughness runs from mirror at 0 to 1, squared to give the width of the
+/// distribution as in glTF. Anisotropic metals are rougher along `v` of the
+/// shading frame, which follows [`Onb`] of the normal since hits carry no
+/// tangents.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Conductor {
+    name: String,
+    ior: ComplexIor,
+    roughness: f64,
+    roughness_v: Option<f64>,
+}
+
+impl Conductor {
+    pub fn new(name: impl Into<String>, ior: ComplexIor, roughness: f64) -> Self {
+        Self::anisotropic(name, ior, roughness, None)
+    }
+
+    /// Roughness `roughness` along u and `roughness_v`, if given, along v
+    pub fn anisotropic(
+        name: impl Into<String>,
+        ior: ComplexIor,
+        roughness: f64,
+        roughness_v: Option<f64>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            ior,
+            roughness: roughness.clamp(0.0, 1.0),
+            roughness_v: roughness_v.map(|r| r.clamp(0.0, 1.0)),
+        }
+    }
+
+    /// Width of the distribution along u and v
+    fn alpha(&self) -> (f64, f64) {
+        let v = self.roughness_v.unwrap_or(self.roughness);
+        (
+            (self.roughness * self.roughness).max(1e-4),
+            (v * v).max(1e-4),
+        )
+    }
+
+    /// Smith's masking function for direction `w` in the shading frame
+    fn g1(w: &Vec3, (ax, ay): (f64, f64)) -> f64 {
+        let tan2 = ((ax * w.x()).powi(2) + (ay * w.y()).powi(2)) / (w.z() * w.z());
+        let lambda = 0.5 * ((1.0 + tan2).sqrt() - 1.0);
+        1.0 / (1.0 + lambda)
+    }
+
+    /// Microfacet normal among those visible from `wo`, following Heitz,
+    /// "Sampling the GGX Distribution of Visible Normals" (2018)
+    fn sample_visible_normal(wo: &Vec3, (ax, ay): (f64, f64)) -> Vec3 {
+        // Stretch to the hemisphere configuration
+        let vh = Vec3::new(ax * wo.x(), ay * wo.y(), wo.z()).unit_vector();
+
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Point on the disk, squashed towards the part visible from `wo`
+        let r = rand::random::<f64>().sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(ax * nh.x(), ay * nh.y(), nh.z().max(0.0)).unit_vector()
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let basis = Onb::new(&rec.normal);
+        let wo = basis.to_local(&-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let alpha = self.alpha();
+        let h = Self::sample_visible_normal(&wo, alpha);
+        let wi = 2.0 * wo.dot(&h) * &h - &wo;
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // The sampling density cancels everything but the Fresnel term and
+        // the shadowing of the scattered direction
+        let attenuation = Self::g1(&wi, alpha) * rec.tint(self.ior.fresnel(wo.dot(&h)));
+        let scattered = Ray::new_with_time(rec.p.clone(), basis.transform(&wi), r_in.time());
+        Some(ScatterRecord {
+            attenuation,
+            scattered,
+        })
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        rec.tint(self.ior.fresnel(1.0))
+    }
+
+    fn to_spec(&self, _registry: &mut ResourceRegistry) -> MaterialSpec {
+        MaterialSpec::Conductor {
+            ior: self.ior.clone(),
+            roughness: self.roughness,
+            roughness_v: self.roughness_v,
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Dielectric {
     name: String,
@@ -166,6 +370,10 @@ impl Material for Dielectric {
         })
     }
 
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
     fn to_spec(&self, _registry: &mut ResourceRegistry) -> MaterialSpec {
         MaterialSpec::Dielectric {
             refraction_index: self.refraction_index,
@@ -176,3 +384,171 @@ impl Material for Dielectric {
         &self.name
     }
 }
+
+/// Phase function that scatters uniformly in all directions, used inside
+/// participating media
+pub struct Isotropic {
+    name: String,
+    tex: Arc<DynTexture>,
+}
+
+impl Isotropic {
+    pub fn new(name: impl Into<String>, albedo: Color) -> Self {
+        let name = name.into();
+        let tex_name = format!("{name}_albedo");
+        Self::from_texture(name, Arc::new(SolidColor::new(tex_name, albedo)))
+    }
+
+    pub fn from_texture(name: impl Into<String>, texture: Arc<DynTexture>) -> Self {
+        Self {
+            name: name.into(),
+            tex: texture,
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo(rec),
+            scattered: Ray::new_with_time(rec.p.clone(), Vec3::random_unit_vector(), r_in.time()),
+        })
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> MaterialSpec {
+        let tex = self.tex.to_spec(registry);
+        registry.register_texture(self.tex.name().to_owned(), tex);
+
+        MaterialSpec::Isotropic {
+            texture: self.tex.name().to_owned(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Emits light from its texture and absorbs everything that hits it
+pub struct DiffuseLight {
+    name: String,
+    tex: Arc<DynTexture>,
+}
+
+impl DiffuseLight {
+    pub fn new(name: impl Into<String>, emit: Color) -> Self {
+        let name = name.into();
+        let tex_name = format!("{name}_emit");
+        Self::from_texture(name, Arc::new(SolidColor::new(tex_name, emit)))
+    }
+
+    pub fn from_texture(name: impl Into<String>, texture: Arc<DynTexture>) -> Self {
+        Self {
+            name: name.into(),
+            tex: texture,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.tex.value(u, v, p)
+    }
+
+    /// Lights count as their color, brighter channels saturate
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        let e = self.emitted(rec.u, rec.v, &rec.p);
+        Color::new(e.x().min(1.0), e.y().min(1.0), e.z().min(1.0))
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> MaterialSpec {
+        let tex = self.tex.to_spec(registry);
+        registry.register_texture(self.tex.name().to_owned(), tex);
+
+        MaterialSpec::DiffuseLight {
+            texture: self.tex.name().to_owned(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Henyey-Greenstein phase function. `g` in (-1, 1) controls anisotropy,
+/// positive values scatter forward and negative values scatter backward.
+pub struct HenyeyGreenstein {
+    name: String,
+    tex: Arc<DynTexture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(name: impl Into<String>, albedo: Color, g: f64) -> Self {
+        let name = name.into();
+        let tex_name = format!("{name}_albedo");
+        Self::from_texture(name, Arc::new(SolidColor::new(tex_name, albedo)), g)
+    }
+
+    pub fn from_texture(name: impl Into<String>, texture: Arc<DynTexture>, g: f64) -> Self {
+        Self {
+            name: name.into(),
+            tex: texture,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    /// Sample the cosine between the incoming and scattered directions
+    fn sample_cos_theta(&self) -> f64 {
+        let xi: f64 = rand::random();
+        if self.g.abs() < 1e-3 {
+            return 1.0 - 2.0 * xi;
+        }
+
+        let g = self.g;
+        let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let cos_theta = self.sample_cos_theta().clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let direction = Onb::new(r_in.direction()).transform(&local);
+
+        Some(ScatterRecord {
+            attenuation: self.albedo(rec),
+            scattered: Ray::new_with_time(rec.p.clone(), direction, r_in.time()),
+        })
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn to_spec(&self, registry: &mut ResourceRegistry) -> MaterialSpec {
+        let tex = self.tex.to_spec(registry);
+        registry.register_texture(self.tex.name().to_owned(), tex);
+
+        MaterialSpec::HenyeyGreenstein {
+            texture: self.tex.name().to_owned(),
+            g: self.g,
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
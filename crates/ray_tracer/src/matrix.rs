@@ -0,0 +1,324 @@
+use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    degrees_to_radians,
+    vec::{Point3, Vec3},
+};
+
+/// Row major 3x3 matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mat3([[f64; 3]; 3]);
+
+impl Mat3 {
+    pub const IDENTITY: Self = Self([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+
+    pub const fn new(rows: [[f64; 3]; 3]) -> Self {
+        Self(rows)
+    }
+
+    pub fn from_scale(scale: &Vec3) -> Self {
+        Self([
+            [scale.x(), 0.0, 0.0],
+            [0.0, scale.y(), 0.0],
+            [0.0, 0.0, scale.z()],
+        ])
+    }
+
+    /// Rotation of `degrees` counter clockwise around `axis` (Rodrigues' formula)
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let theta = degrees_to_radians(degrees);
+        let (s, c) = theta.sin_cos();
+        let t = 1.0 - c;
+
+        Self([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c],
+        ])
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        let [a, b, c] = self.0[i];
+        Vec3::new(a, b, c)
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = &self.0;
+        Self(core::array::from_fn(|i| core::array::from_fn(|j| m[j][i])))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns None for singular matrices. The determinant is compared to
+    /// the cube of the Frobenius norm, so uniformly tiny or huge matrices
+    /// still invert.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        let norm = self.0.iter().flatten().map(|x| x * x).sum::<f64>().sqrt();
+        if det.abs() <= 1e-12 * norm.powi(3) {
+            return None;
+        }
+
+        let m = &self.0;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let inv_det = 1.0 / det;
+
+        Some(Self([
+            [
+                cofactor(1, 2, 1, 2) * inv_det,
+                -cofactor(0, 2, 1, 2) * inv_det,
+                cofactor(0, 1, 1, 2) * inv_det,
+            ],
+            [
+                -cofactor(1, 2, 0, 2) * inv_det,
+                cofactor(0, 2, 0, 2) * inv_det,
+                -cofactor(0, 1, 0, 2) * inv_det,
+            ],
+            [
+                cofactor(1, 2, 0, 1) * inv_det,
+                -cofactor(0, 2, 0, 1) * inv_det,
+                cofactor(0, 1, 0, 1) * inv_det,
+            ],
+        ]))
+    }
+}
+
+impl Mul<&Vec3> for &Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: &Vec3) -> Self::Output {
+        Vec3::new(
+            self.row(0).dot(rhs),
+            self.row(1).dot(rhs),
+            self.row(2).dot(rhs),
+        )
+    }
+}
+
+impl Mul for &Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let (a, b) = (&self.0, &rhs.0);
+        Mat3(core::array::from_fn(|i| {
+            core::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum())
+        }))
+    }
+}
+
+/// Linear map followed by a translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Affine {
+    pub linear: Mat3,
+    pub translation: Vec3,
+}
+
+impl Affine {
+    pub const IDENTITY: Self = Self {
+        linear: Mat3::IDENTITY,
+        translation: Vec3::ZERO,
+    };
+
+    pub fn new(linear: Mat3, translation: Vec3) -> Self {
+        Self {
+            linear,
+            translation,
+        }
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        &self.translation + &self.linear * p
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        &self.linear * v
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let linear = self.linear.inverse()?;
+        let translation = -(&linear * &self.translation);
+        Some(Self::new(linear, translation))
+    }
+
+    /// Transform that applies `self` and then `other`
+    pub fn then(&self, other: &Self) -> Self {
+        Self::new(
+            &other.linear * &self.linear,
+            other.transform_point(&self.translation),
+        )
+    }
+}
+
+/// Unit quaternion `w + xi + yj + zk` representing a rotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Self {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    /// Quaternion of a pure rotation matrix
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let m = &m.0;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        // Pick the largest diagonal term to divide by for numerical stability
+        let q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Self {
+                w: 0.25 * s,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Self {
+                w: (m[2][1] - m[1][2]) / s,
+                x: 0.25 * s,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Self {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: 0.25 * s,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Self {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: 0.25 * s,
+            }
+        };
+
+        q.normalized()
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalized(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        Self {
+            w: self.w / len,
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+        }
+    }
+
+    /// Spherical interpolation along the shortest arc, `t` in 0..1
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            other = Self {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            };
+        }
+
+        // Nearly parallel rotations interpolate linearly to avoid dividing by
+        // a vanishing sine
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Self {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        }
+        .normalized()
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        let Self { w, x, y, z } = *self;
+        Mat3([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+}
+
+/// Orientation of an instance
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Rotation {
+    /// Rotation about the x, then y, then z axis, in degrees
+    Euler(Vec3),
+    AxisAngle {
+        axis: Vec3,
+        degrees: f64,
+    },
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::Euler(Vec3::ZERO)
+    }
+}
+
+impl Rotation {
+    pub fn matrix(&self) -> Mat3 {
+        match self {
+            Self::Euler(angles) => {
+                let rx = Mat3::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), angles.x());
+                let ry = Mat3::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), angles.y());
+                let rz = Mat3::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), angles.z());
+                &(&rz * &ry) * &rx
+            }
+            Self::AxisAngle { axis, degrees } => Mat3::from_axis_angle(axis, *degrees),
+        }
+    }
+}
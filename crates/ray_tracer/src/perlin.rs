@@ -1,3 +1,5 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
 use crate::vec::{Point3, Vec3};
 
 pub struct Perlin {
@@ -9,14 +11,26 @@ pub struct Perlin {
 
 impl Default for Perlin {
     fn default() -> Self {
+        Self::from_seed(rand::random())
+    }
+}
+
+impl Perlin {
+    const POINT_COUNT: usize = 256;
+
+    /// Noise whose tables are generated from `seed`, so the same seed always
+    /// gives the same noise
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
         let mut randvec = [Vec3::ZERO; Self::POINT_COUNT];
         for v in &mut randvec {
-            *v = Vec3::random_bounded(-1.0, 1.0).unit_vector();
+            *v = Vec3::random_bounded_with(&mut rng, -1.0, 1.0).unit_vector();
         }
 
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(&mut rng);
+        let perm_y = Self::perlin_generate_perm(&mut rng);
+        let perm_z = Self::perlin_generate_perm(&mut rng);
 
         Self {
             randvec,
@@ -25,10 +39,6 @@ impl Default for Perlin {
             perm_z,
         }
     }
-}
-
-impl Perlin {
-    const POINT_COUNT: usize = 256;
 
     pub fn noise(&self, p: &Point3) -> f64 {
         let mut u = p.x() - p.x().floor();
@@ -72,14 +82,14 @@ impl Perlin {
         accum.abs()
     }
 
-    fn perlin_generate_perm() -> [i32; Self::POINT_COUNT] {
+    fn perlin_generate_perm(rng: &mut impl Rng) -> [i32; Self::POINT_COUNT] {
         let mut p = [0; Self::POINT_COUNT];
         for (i, p) in p.iter_mut().enumerate() {
             *p = i as i32;
         }
 
         for i in (1..Self::POINT_COUNT).rev() {
-            let target = rand::random_range(0..i);
+            let target = rng.random_range(0..i);
             p.swap(i, target);
         }
 
@@ -92,14 +102,15 @@ impl Perlin {
         let ww = w * w * (3.0 - 2.0 * w);
         let mut accum = 0.0;
 
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    let weight_v = Vec3::new(u - i as f64, v - j as f64, w - k as f64);
-                    accum += (i as f64 * uu + (1 - i) as f64 * (1.0 - uu))
-                        * (j as f64 * vv + (1 - j) as f64 * (1.0 - vv))
-                        * (k as f64 * ww + (1 - k) as f64 * (1.0 - ww))
-                        * c[i][j][k].dot(&weight_v)
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (i, j, k) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - i, v - j, w - k);
+                    accum += (i * uu + (1.0 - i) * (1.0 - uu))
+                        * (j * vv + (1.0 - j) * (1.0 - vv))
+                        * (k * ww + (1.0 - k) * (1.0 - ww))
+                        * corner.dot(&weight_v)
                 }
             }
         }
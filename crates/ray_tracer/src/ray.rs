@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::vec::{Point3, Vec3};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
@@ -1,11 +1,40 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    aabb::AABB,
+    camera::CameraBuilder,
     color::Color,
-    hittable::{DynHittable, HittableList, bvh::BVHNode, quad::Quad, sphere::Sphere},
-    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    hittable::{
+        DynHittable, HittableList,
+        bvh::BVHNode,
+        constant_medium::ConstantMedium,
+        csg::{Csg, CsgOp},
+        heightfield::Heightfield,
+        mesh::{MeshData, TriangleMesh},
+        motion::MotionTransform,
+        named::Named,
+        plane::Plane,
+        quad::{Quad, make_box},
+        quadric::Quadric,
+        sdf::{SdfNode, SdfShape},
+        sphere::Sphere,
+        torus::Torus,
+        transform::Transform,
+        volume::{DynDensityField, HeterogeneousMedium, PerlinDensity, VoxelGrid},
+    },
+    material::{
+        ComplexIor, Conductor, Dielectric, DiffuseLight, DynMaterial, HenyeyGreenstein, Isotropic,
+        Lambertian, Metal,
+    },
+    matrix::{Affine, Mat3, Quat, Rotation},
     ray::Ray,
     texture::{CheckerTexture, DynTexture, ImageTexture, NoiseTexture, SolidColor},
     vec::{Point3, Vec3},
@@ -13,9 +42,20 @@ use crate::{
 
 type MaterialKey = String;
 type TextureKey = String;
+type PrototypeKey = String;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum ShapeSpec {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: MaterialKey,
+        /// Displacement of the center between time 0 and 1, for motion blur
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        motion: Option<Vec3>,
+    },
+    /// Sphere in the original format with its center stored as a ray. Still
+    /// loaded for older scene files, new files are written with `Sphere`.
     Circle {
         radius: f64,
         center: Ray,
@@ -27,52 +67,731 @@ pub enum ShapeSpec {
         v: Vec3,
         material: MaterialKey,
     },
+    Triangle {
+        q: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: MaterialKey,
+    },
+    Ellipse {
+        center: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: MaterialKey,
+    },
+    Disk {
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        material: MaterialKey,
+    },
+    Annulus {
+        center: Point3,
+        u: Vec3,
+        v: Vec3,
+        inner: f64,
+        material: MaterialKey,
+    },
+    Box {
+        a: Point3,
+        b: Point3,
+        material: MaterialKey,
+    },
+    Cylinder {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: MaterialKey,
+    },
+    Cone {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: MaterialKey,
+    },
+    Paraboloid {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: MaterialKey,
+    },
+    Hyperboloid {
+        center: Point3,
+        radius: f64,
+        end_radius: f64,
+        height: f64,
+        capped: bool,
+        material: MaterialKey,
+    },
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: MaterialKey,
+    },
+    Plane {
+        point: Point3,
+        normal: Vec3,
+        material: MaterialKey,
+    },
+    Sdf {
+        root: SdfNode,
+        material: MaterialKey,
+    },
+    /// Terrain from a grayscale heightmap covering `size_x` by `size_z`
+    /// from `origin`, with white at `height` above it
+    Heightfield {
+        path: PathBuf,
+        origin: Point3,
+        size_x: f64,
+        size_z: f64,
+        height: f64,
+        material: MaterialKey,
+    },
+    /// Triangle mesh stored in the scene file, see [`MeshData`]
+    Mesh {
+        positions: Vec<Point3>,
+        indices: Vec<[usize; 3]>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        normals: Vec<Vec3>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        uvs: Vec<[f64; 2]>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        colors: Vec<Color>,
+        material: MaterialKey,
+    },
+    /// Triangle mesh loaded from a PLY file
+    Ply {
+        path: PathBuf,
+        material: MaterialKey,
+    },
+    /// Triangle mesh loaded from an STL file
+    Stl {
+        path: PathBuf,
+        material: MaterialKey,
+    },
+    /// Boolean combination of two closed shapes
+    Csg {
+        op: CsgOp,
+        left: Box<ShapeSpec>,
+        right: Box<ShapeSpec>,
+    },
     List(Vec<ShapeSpec>),
     BVH {
         left: Box<ShapeSpec>,
         right: Box<ShapeSpec>,
     },
+    ConstantMedium {
+        boundary: Box<ShapeSpec>,
+        density: f64,
+        material: MaterialKey,
+    },
+    HeterogeneousMedium {
+        boundary: Box<ShapeSpec>,
+        density: DensitySpec,
+        sigma_a: f64,
+        sigma_s: f64,
+        emission: Color,
+        material: MaterialKey,
+    },
+    Transform {
+        shape: Box<ShapeSpec>,
+        transform: TransformSpec,
+    },
+    /// Shape moving through the transforms of its keyframes over time
+    Motion {
+        shape: Box<ShapeSpec>,
+        keyframes: Vec<Keyframe>,
+    },
+    /// Shape that can be looked up by name and whose hits carry the name's
+    /// [`ObjectId`](crate::hittable::named::ObjectId)
+    Named {
+        name: String,
+        shape: Box<ShapeSpec>,
+    },
+    /// A transformed reference to one of the scene's shared prototypes
+    Instance {
+        prototype: PrototypeKey,
+        transform: TransformSpec,
+    },
 }
 
 impl ShapeSpec {
-    fn build(self, materials: &HashMap<String, Arc<DynMaterial>>) -> Arc<DynHittable> {
+    /// Name of the variant, for reporting
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Sphere { .. } => "Sphere",
+            Self::Circle { .. } => "Circle",
+            Self::Quad { .. } => "Quad",
+            Self::Triangle { .. } => "Triangle",
+            Self::Ellipse { .. } => "Ellipse",
+            Self::Disk { .. } => "Disk",
+            Self::Annulus { .. } => "Annulus",
+            Self::Box { .. } => "Box",
+            Self::Cylinder { .. } => "Cylinder",
+            Self::Cone { .. } => "Cone",
+            Self::Paraboloid { .. } => "Paraboloid",
+            Self::Hyperboloid { .. } => "Hyperboloid",
+            Self::Torus { .. } => "Torus",
+            Self::Plane { .. } => "Plane",
+            Self::Sdf { .. } => "Sdf",
+            Self::Heightfield { .. } => "Heightfield",
+            Self::Mesh { .. } => "Mesh",
+            Self::Ply { .. } => "Ply",
+            Self::Stl { .. } => "Stl",
+            Self::Csg { .. } => "Csg",
+            Self::List(_) => "List",
+            Self::BVH { .. } => "BVH",
+            Self::ConstantMedium { .. } => "ConstantMedium",
+            Self::HeterogeneousMedium { .. } => "HeterogeneousMedium",
+            Self::Transform { .. } => "Transform",
+            Self::Motion { .. } => "Motion",
+            Self::Named { .. } => "Named",
+            Self::Instance { .. } => "Instance",
+        }
+    }
+
+    /// Shapes nested directly inside this one
+    pub fn children(&self) -> Vec<&ShapeSpec> {
+        match self {
+            Self::Csg { left, right, .. } | Self::BVH { left, right } => vec![left, right],
+            Self::List(shapes) => shapes.iter().collect(),
+            Self::ConstantMedium { boundary, .. } | Self::HeterogeneousMedium { boundary, .. } => {
+                vec![boundary]
+            }
+            Self::Transform { shape, .. }
+            | Self::Motion { shape, .. }
+            | Self::Named { shape, .. } => vec![shape],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Shapes nested directly inside this one
+    fn children_mut(&mut self) -> Vec<&mut ShapeSpec> {
+        match self {
+            Self::Csg { left, right, .. } | Self::BVH { left, right } => vec![left, right],
+            Self::List(shapes) => shapes.iter_mut().collect(),
+            Self::ConstantMedium { boundary, .. } | Self::HeterogeneousMedium { boundary, .. } => {
+                vec![boundary]
+            }
+            Self::Transform { shape, .. }
+            | Self::Motion { shape, .. }
+            | Self::Named { shape, .. } => vec![shape],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Material this shape is made of, if it refers to one directly
+    pub fn material(&self) -> Option<&str> {
+        match self {
+            Self::Sphere { material, .. }
+            | Self::Circle { material, .. }
+            | Self::Quad { material, .. }
+            | Self::Triangle { material, .. }
+            | Self::Ellipse { material, .. }
+            | Self::Disk { material, .. }
+            | Self::Annulus { material, .. }
+            | Self::Box { material, .. }
+            | Self::Cylinder { material, .. }
+            | Self::Cone { material, .. }
+            | Self::Paraboloid { material, .. }
+            | Self::Hyperboloid { material, .. }
+            | Self::Torus { material, .. }
+            | Self::Plane { material, .. }
+            | Self::Sdf { material, .. }
+            | Self::Heightfield { material, .. }
+            | Self::Mesh { material, .. }
+            | Self::Ply { material, .. }
+            | Self::Stl { material, .. }
+            | Self::ConstantMedium { material, .. }
+            | Self::HeterogeneousMedium { material, .. } => Some(material),
+            Self::Csg { .. }
+            | Self::List(_)
+            | Self::BVH { .. }
+            | Self::Transform { .. }
+            | Self::Motion { .. }
+            | Self::Named { .. }
+            | Self::Instance { .. } => None,
+        }
+    }
+
+    fn material_mut(&mut self) -> Option<&mut MaterialKey> {
         match self {
+            Self::Sphere { material, .. }
+            | Self::Circle { material, .. }
+            | Self::Quad { material, .. }
+            | Self::Triangle { material, .. }
+            | Self::Ellipse { material, .. }
+            | Self::Disk { material, .. }
+            | Self::Annulus { material, .. }
+            | Self::Box { material, .. }
+            | Self::Cylinder { material, .. }
+            | Self::Cone { material, .. }
+            | Self::Paraboloid { material, .. }
+            | Self::Hyperboloid { material, .. }
+            | Self::Torus { material, .. }
+            | Self::Plane { material, .. }
+            | Self::Sdf { material, .. }
+            | Self::Heightfield { material, .. }
+            | Self::Mesh { material, .. }
+            | Self::Ply { material, .. }
+            | Self::Stl { material, .. }
+            | Self::ConstantMedium { material, .. }
+            | Self::HeterogeneousMedium { material, .. } => Some(material),
+            Self::Csg { .. }
+            | Self::List(_)
+            | Self::BVH { .. }
+            | Self::Transform { .. }
+            | Self::Motion { .. }
+            | Self::Named { .. }
+            | Self::Instance { .. } => None,
+        }
+    }
+
+    /// Collect the files this shape and the shapes nested in it read from
+    fn collect_asset_paths<'a>(&'a mut self, paths: &mut Vec<&'a mut PathBuf>) {
+        match self {
+            Self::Heightfield { path, .. } | Self::Ply { path, .. } | Self::Stl { path, .. } => {
+                paths.push(path)
+            }
+            Self::HeterogeneousMedium {
+                boundary, density, ..
+            } => {
+                if let DensitySpec::DenseGrid { path, .. } | DensitySpec::SparseGrid { path } =
+                    density
+                {
+                    paths.push(path);
+                }
+                boundary.collect_asset_paths(paths);
+            }
+            shape => {
+                for child in shape.children_mut() {
+                    child.collect_asset_paths(paths);
+                }
+            }
+        }
+    }
+
+    /// Call `f` on this shape and then on every shape nested inside it
+    pub fn visit(&self, f: &mut impl FnMut(&ShapeSpec)) {
+        f(self);
+        for child in self.children() {
+            child.visit(f);
+        }
+    }
+
+    /// Call `f` on this shape and then on every shape nested inside it
+    fn visit_mut(&mut self, f: &mut impl FnMut(&mut ShapeSpec)) {
+        f(self);
+        for child in self.children_mut() {
+            child.visit_mut(f);
+        }
+    }
+
+    fn build(self, resources: &SceneResources) -> anyhow::Result<Arc<DynHittable>> {
+        let shape: Arc<DynHittable> = match self {
+            Self::Sphere {
+                center,
+                radius,
+                material,
+                motion,
+            } => {
+                let material = resources.material(&material)?;
+                match motion {
+                    Some(motion) => Arc::new(Sphere::with_motion(center, motion, radius, material)),
+                    None => Arc::new(Sphere::new(center, radius, material)),
+                }
+            }
             Self::Circle {
                 radius,
                 center,
                 material,
             } => {
-                let material = materials[&material].clone();
-                Arc::new(Sphere::new_moving(
+                let material = resources.material(&material)?;
+                Arc::new(Sphere::with_motion(
                     center.origin().clone(),
-                    center.origin() + center.direction(),
+                    center.direction().clone(),
                     radius,
                     material,
                 ))
             }
             Self::Quad { q, u, v, material } => {
-                let material = materials[&material].clone();
+                let material = resources.material(&material)?;
                 Arc::new(Quad::new(q, u, v, material))
             }
+            Self::Triangle { q, u, v, material } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quad::triangle(q, u, v, material))
+            }
+            Self::Ellipse {
+                center,
+                u,
+                v,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quad::ellipse(center, u, v, material))
+            }
+            Self::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quad::disk(center, &normal, radius, material))
+            }
+            Self::Annulus {
+                center,
+                u,
+                v,
+                inner,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quad::annulus(center, u, v, inner, material))
+            }
+            Self::Box { a, b, material } => {
+                let material = resources.material(&material)?;
+                Arc::new(make_box(&a, &b, material))
+            }
+            Self::Cylinder {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quadric::cylinder(center, radius, height, capped, material)?)
+            }
+            Self::Cone {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quadric::cone(center, radius, height, capped, material)?)
+            }
+            Self::Paraboloid {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quadric::paraboloid(
+                    center, radius, height, capped, material,
+                )?)
+            }
+            Self::Hyperboloid {
+                center,
+                radius,
+                end_radius,
+                height,
+                capped,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Quadric::hyperboloid(
+                    center, radius, end_radius, height, capped, material,
+                )?)
+            }
+            Self::Torus {
+                center,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Torus::new(center, major_radius, minor_radius, material))
+            }
+            Self::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Plane::new(point, normal, material))
+            }
+            Self::Sdf { root, material } => {
+                let material = resources.material(&material)?;
+                Arc::new(SdfShape::new(root, material)?)
+            }
+            Self::Heightfield {
+                path,
+                origin,
+                size_x,
+                size_z,
+                height,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                Arc::new(Heightfield::new(
+                    path, origin, size_x, size_z, height, material,
+                )?)
+            }
+            Self::Mesh {
+                positions,
+                indices,
+                normals,
+                uvs,
+                colors,
+                material,
+            } => {
+                let material = resources.material(&material)?;
+                let data = MeshData {
+                    positions,
+                    indices,
+                    normals,
+                    uvs,
+                    colors,
+                };
+                Arc::new(TriangleMesh::new(data, material)?)
+            }
+            Self::Ply { path, material } => {
+                let material = resources.material(&material)?;
+                Arc::new(TriangleMesh::from_ply(path, material)?)
+            }
+            Self::Stl { path, material } => {
+                let material = resources.material(&material)?;
+                Arc::new(TriangleMesh::from_stl(path, material)?)
+            }
+            Self::Csg { op, left, right } => {
+                let left = left.build(resources)?;
+                let right = right.build(resources)?;
+
+                Arc::new(Csg::new(op, left, right))
+            }
             Self::List(shape_specs) => {
                 let mut world = HittableList::default();
                 for spec in shape_specs {
-                    world.add(spec.build(materials));
+                    world.add(spec.build(resources)?);
                 }
 
                 Arc::new(world)
             }
             Self::BVH { left, right } => {
-                let left = left.build(materials);
-                let right = right.build(materials);
+                let left = left.build(resources)?;
+                let right = right.build(resources)?;
 
                 Arc::new(BVHNode::from_slice(&mut [left, right]))
             }
+            Self::ConstantMedium {
+                boundary,
+                density,
+                material,
+            } => {
+                let boundary = boundary.build(resources)?;
+                let material = resources.material(&material)?;
+                Arc::new(ConstantMedium::new(boundary, density, material)?)
+            }
+            Self::HeterogeneousMedium {
+                boundary,
+                density,
+                sigma_a,
+                sigma_s,
+                emission,
+                material,
+            } => {
+                let boundary = boundary.build(resources)?;
+                let density = density.build(boundary.bounding_box())?;
+                let material = resources.material(&material)?;
+                Arc::new(HeterogeneousMedium::new(
+                    boundary, density, sigma_a, sigma_s, emission, material,
+                )?)
+            }
+            Self::Transform { shape, transform } => {
+                let shape = shape.build(resources)?;
+                Arc::new(Transform::new(shape, transform)?)
+            }
+            Self::Motion { shape, keyframes } => {
+                let shape = shape.build(resources)?;
+                Arc::new(MotionTransform::new(shape, keyframes)?)
+            }
+            Self::Named { name, shape } => Arc::new(Named::new(name, shape.build(resources)?)),
+            Self::Instance {
+                prototype,
+                transform,
+            } => {
+                let shape = resources
+                    .prototypes
+                    .get(&prototype)
+                    .ok_or_else(|| anyhow::format_err!("unknown prototype: '{prototype}'"))?
+                    .clone();
+                Arc::new(Transform::instance(prototype, shape, transform)?)
+            }
+        };
+
+        Ok(shape)
+    }
+}
+
+/// Translation, rotation and scale of a shape, applied in the order scale,
+/// rotate, translate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransformSpec {
+    #[serde(default = "TransformSpec::default_translate")]
+    translate: Vec3,
+    #[serde(default)]
+    rotate: Rotation,
+    #[serde(default = "TransformSpec::default_scale")]
+    scale: Vec3,
+}
+
+impl Default for TransformSpec {
+    fn default() -> Self {
+        Self {
+            translate: Self::default_translate(),
+            rotate: Rotation::default(),
+            scale: Self::default_scale(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl TransformSpec {
+    fn default_translate() -> Vec3 {
+        Vec3::ZERO
+    }
+
+    fn default_scale() -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
+
+    pub fn translate(mut self, translate: Vec3) -> Self {
+        self.translate = translate;
+        self
+    }
+
+    pub fn rotate(mut self, rotate: Rotation) -> Self {
+        self.rotate = rotate;
+        self
+    }
+
+    pub fn scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn affine(&self) -> Affine {
+        let linear = &self.rotate.matrix() * &Mat3::from_scale(&self.scale);
+        Affine::new(linear, self.translate.clone())
+    }
+
+    /// Blend towards `other` by `t` in 0..1. Translation and scale are
+    /// interpolated linearly and rotation along the shortest arc.
+    pub fn interpolate(&self, other: &Self, t: f64) -> Affine {
+        let lerp = |a: &Vec3, b: &Vec3| (1.0 - t) * a + t * b;
+
+        let rotation = Quat::from_mat3(&self.rotate.matrix())
+            .slerp(&Quat::from_mat3(&other.rotate.matrix()), t);
+        let scale = lerp(&self.scale, &other.scale);
+        let linear = &rotation.to_mat3() * &Mat3::from_scale(&scale);
+
+        Affine::new(linear, lerp(&self.translate, &other.translate))
+    }
+
+    /// How far points of `bbox` stray from the straight line between their
+    /// positions at consecutive steps, when blending towards `other` in
+    /// `steps` equal steps. A path with acceleration at most `a` stays within
+    /// `a h² / 8` of its chords of length `h`, and rotating `S x` at the arc's
+    /// constant rate `θ` accelerates it by at most `θ² |S x| + 2 θ |S' x|`.
+    pub fn chord_error(&self, other: &Self, bbox: &AABB, steps: usize) -> f64 {
+        if !bbox.is_bounded() {
+            return 0.0;
+        }
+
+        let from = Quat::from_mat3(&self.rotate.matrix());
+        let to = Quat::from_mat3(&other.rotate.matrix());
+        let angle = 2.0 * from.dot(&to).abs().min(1.0).acos();
+
+        let (scale_from, scale_to) = (
+            Mat3::from_scale(&self.scale),
+            Mat3::from_scale(&other.scale),
+        );
+        let scale_change = Mat3::from_scale(&(&other.scale - &self.scale));
+        let mut acceleration: f64 = 0.0;
+        for x in [bbox.x.min, bbox.x.max] {
+            for y in [bbox.y.min, bbox.y.max] {
+                for z in [bbox.z.min, bbox.z.max] {
+                    let corner = Vec3::new(x, y, z);
+                    let radius = (&scale_from * &corner)
+                        .length()
+                        .max((&scale_to * &corner).length());
+                    let growth = (&scale_change * &corner).length();
+                    acceleration = acceleration.max(angle * angle * radius + 2.0 * angle * growth);
+                }
+            }
+        }
+
+        let h = 1.0 / steps as f64;
+        acceleration * h * h / 8.0
+    }
+
+    /// Whether the scale stays non-zero on every axis while blending towards
+    /// `other`, which needs both scales to have the same sign on each axis
+    pub fn blends_invertibly(&self, other: &Self) -> bool {
+        let (a, b) = (&self.scale, &other.scale);
+        [a.x() * b.x(), a.y() * b.y(), a.z() * b.z()]
+            .iter()
+            .all(|product| *product > 0.0)
+    }
+}
+
+/// Transform of an animated shape at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub transform: TransformSpec,
+}
+
+/// Density source of a heterogeneous medium. Grids are stretched over the
+/// bounding box of the medium's boundary.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum DensitySpec {
+    /// Raw little endian `f32` values, x varying fastest
+    DenseGrid {
+        path: PathBuf,
+        resolution: [usize; 3],
+    },
+    /// Text file with a `nx ny nz` header followed by `i j k density` lines
+    SparseGrid { path: PathBuf },
+    Perlin {
+        scale: f64,
+        octaves: i32,
+        /// Picks the noise pattern, older files get seed 0
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+impl DensitySpec {
+    fn build(self, bounds: &AABB) -> anyhow::Result<Arc<DynDensityField>> {
+        Ok(match self {
+            Self::DenseGrid { path, resolution } => Arc::new(VoxelGrid::from_dense_file(
+                &path,
+                resolution,
+                bounds.clone(),
+            )?),
+            Self::SparseGrid { path } => {
+                Arc::new(VoxelGrid::from_sparse_file(&path, bounds.clone())?)
+            }
+            Self::Perlin {
+                scale,
+                octaves,
+                seed,
+            } => Arc::new(PerlinDensity::new(scale, octaves, seed)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TextureSpec {
     SolidColor {
         albedo: Color,
@@ -87,10 +806,31 @@ pub enum TextureSpec {
     },
     Perlin {
         scale: f64,
+        /// Picks the noise pattern, older files get seed 0
+        #[serde(default)]
+        seed: u64,
     },
 }
 
 impl TextureSpec {
+    /// Name of the variant, for reporting
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SolidColor { .. } => "SolidColor",
+            Self::Checker { .. } => "Checker",
+            Self::Image { .. } => "Image",
+            Self::Perlin { .. } => "Perlin",
+        }
+    }
+
+    /// Other textures this one is built from
+    pub(crate) fn texture_keys_mut(&mut self) -> Vec<&mut TextureKey> {
+        match self {
+            Self::Checker { even, odd, .. } => vec![even, odd],
+            Self::SolidColor { .. } | Self::Image { .. } | Self::Perlin { .. } => Vec::new(),
+        }
+    }
+
     fn build(
         self,
         name: &str,
@@ -99,35 +839,103 @@ impl TextureSpec {
         match self {
             Self::SolidColor { albedo } => Ok(Arc::new(SolidColor::new(name, albedo))),
             Self::Checker { scale, even, odd } => {
-                let even = textures[&even].clone();
-                let odd = textures[&odd].clone();
-                Ok(Arc::new(CheckerTexture::new(scale, even, odd)))
+                let even = find_texture(textures, &even)?;
+                let odd = find_texture(textures, &odd)?;
+                Ok(Arc::new(CheckerTexture::new(name, scale, even, odd)))
             }
-            Self::Image { path } => Ok(Arc::new(ImageTexture::new(&path)?)),
-            Self::Perlin { scale } => Ok(Arc::new(NoiseTexture::new(scale))),
+            Self::Image { path } => Ok(Arc::new(ImageTexture::new(name, &path)?)),
+            Self::Perlin { scale, seed } => Ok(Arc::new(NoiseTexture::new(name, scale, seed))),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MaterialSpec {
-    Lambertian { texture: TextureKey },
-    Metal { albedo: Color, fuzz: f64 },
-    Dielectric { refraction_index: f64 },
+    Lambertian {
+        texture: TextureKey,
+    },
+    Metal {
+        albedo: Color,
+        fuzz: f64,
+    },
+    /// Microfacet metal, see [`Conductor`]
+    Conductor {
+        ior: ComplexIor,
+        roughness: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        roughness_v: Option<f64>,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    Isotropic {
+        texture: TextureKey,
+    },
+    HenyeyGreenstein {
+        texture: TextureKey,
+        g: f64,
+    },
+    DiffuseLight {
+        texture: TextureKey,
+    },
 }
 
 impl MaterialSpec {
-    fn build(self, name: &str, textures: &HashMap<String, Arc<DynTexture>>) -> Arc<DynMaterial> {
+    /// Name of the variant, for reporting
+    pub fn kind(&self) -> &'static str {
         match self {
+            Self::Lambertian { .. } => "Lambertian",
+            Self::Metal { .. } => "Metal",
+            Self::Conductor { .. } => "Conductor",
+            Self::Dielectric { .. } => "Dielectric",
+            Self::Isotropic { .. } => "Isotropic",
+            Self::HenyeyGreenstein { .. } => "HenyeyGreenstein",
+            Self::DiffuseLight { .. } => "DiffuseLight",
+        }
+    }
+
+    pub(crate) fn texture_mut(&mut self) -> Option<&mut TextureKey> {
+        match self {
+            Self::Lambertian { texture }
+            | Self::Isotropic { texture }
+            | Self::HenyeyGreenstein { texture, .. }
+            | Self::DiffuseLight { texture } => Some(texture),
+            Self::Metal { .. } | Self::Conductor { .. } | Self::Dielectric { .. } => None,
+        }
+    }
+
+    fn build(
+        self,
+        name: &str,
+        textures: &HashMap<String, Arc<DynTexture>>,
+    ) -> anyhow::Result<Arc<DynMaterial>> {
+        Ok(match self {
             Self::Lambertian { texture } => {
-                let texture = textures[&texture].clone();
-                Arc::new(Lambertian::from_texture(texture))
+                let texture = find_texture(textures, &texture)?;
+                Arc::new(Lambertian::from_texture(name, texture))
             }
             Self::Metal { albedo, fuzz } => Arc::new(Metal::new(name, albedo, fuzz)),
+            Self::Conductor {
+                ior,
+                roughness,
+                roughness_v,
+            } => Arc::new(Conductor::anisotropic(name, ior, roughness, roughness_v)),
             Self::Dielectric { refraction_index } => {
                 Arc::new(Dielectric::new(name, refraction_index))
             }
-        }
+            Self::Isotropic { texture } => {
+                let texture = find_texture(textures, &texture)?;
+                Arc::new(Isotropic::from_texture(name, texture))
+            }
+            Self::HenyeyGreenstein { texture, g } => {
+                let texture = find_texture(textures, &texture)?;
+                Arc::new(HenyeyGreenstein::from_texture(name, texture, g))
+            }
+            Self::DiffuseLight { texture } => {
+                let texture = find_texture(textures, &texture)?;
+                Arc::new(DiffuseLight::from_texture(name, texture))
+            }
+        })
     }
 }
 
@@ -135,6 +943,7 @@ impl MaterialSpec {
 pub struct ResourceRegistry {
     materials: Vec<(String, MaterialSpec)>,
     textures: Vec<(String, TextureSpec)>,
+    prototypes: Vec<(String, ShapeSpec)>,
 }
 
 impl ResourceRegistry {
@@ -153,17 +962,203 @@ impl ResourceRegistry {
 
         self.textures.push((name, spec));
     }
+
+    pub fn has_prototype(&self, name: &str) -> bool {
+        self.prototypes.iter().any(|(n, _)| name == n)
+    }
+
+    pub fn register_prototype(&mut self, name: String, spec: ShapeSpec) {
+        if self.has_prototype(&name) {
+            return;
+        }
+
+        self.prototypes.push((name, spec));
+    }
+}
+
+/// Built resources that shapes refer to by key
+struct SceneResources {
+    materials: HashMap<String, Arc<DynMaterial>>,
+    prototypes: HashMap<String, Arc<DynHittable>>,
+}
+
+impl SceneResources {
+    fn material(&self, key: &str) -> anyhow::Result<Arc<DynMaterial>> {
+        self.materials
+            .get(key)
+            .cloned()
+            .with_context(|| format!("unknown material '{key}'"))
+    }
+}
+
+/// Textures must be defined before the textures and materials using them
+fn find_texture(
+    textures: &HashMap<String, Arc<DynTexture>>,
+    key: &str,
+) -> anyhow::Result<Arc<DynTexture>> {
+    textures
+        .get(key)
+        .cloned()
+        .with_context(|| format!("unknown texture '{key}'"))
+}
+
+/// Scene file format version written by this build
+pub const SCENE_VERSION: u32 = 2;
+
+/// Upgrades from each version to the next, starting at version 1
+const MIGRATIONS: [fn(&mut SceneFile); SCENE_VERSION as usize - 1] = [migrate_circle_to_sphere];
+
+/// Version 2 replaced `Circle` and its ray center with `Sphere`
+fn migrate_circle_to_sphere(scene: &mut SceneFile) {
+    scene.visit_shapes_mut(|shape| {
+        if let ShapeSpec::Circle {
+            radius,
+            center,
+            material,
+        } = shape
+        {
+            let motion = center.direction();
+            *shape = ShapeSpec::Sphere {
+                center: center.origin().clone(),
+                radius: *radius,
+                material: std::mem::take(material),
+                motion: (*motion != Vec3::ZERO).then(|| motion.clone()),
+            };
+        }
+    });
+}
+
+/// Text format of a scene file, picked from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    Json,
+    Toml,
+    Yaml,
+    Ron,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("json") => Ok(Self::Json),
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("ron") => Ok(Self::Ron),
+            _ => anyhow::bail!(
+                "unknown scene format for {path:?}, expected .json, .toml, .yaml, .yml or .ron"
+            ),
+        }
+    }
+}
+
+/// Camera settings stored with a scene. Settings that are left out keep the
+/// renderer's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CameraSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_width: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples_per_pixel: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookfrom: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookat: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vup: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defocus_angle: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_dist: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl CameraSpec {
+    /// Override the builder's settings with the ones given here
+    pub fn apply(&self, mut builder: CameraBuilder) -> CameraBuilder {
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            builder = builder.aspect_ratio(aspect_ratio);
+        }
+        if let Some(image_width) = self.image_width {
+            builder = builder.image_width(image_width);
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            builder = builder.samples_per_pixel(samples_per_pixel);
+        }
+        if let Some(max_depth) = self.max_depth {
+            builder = builder.max_depth(max_depth);
+        }
+        if let Some(vfov) = self.vfov {
+            builder = builder.vfov(vfov);
+        }
+        if let Some(lookfrom) = &self.lookfrom {
+            builder = builder.lookfrom(lookfrom.clone());
+        }
+        if let Some(lookat) = &self.lookat {
+            builder = builder.lookat(lookat.clone());
+        }
+        if let Some(vup) = &self.vup {
+            builder = builder.vup(vup.clone());
+        }
+        if let Some(defocus_angle) = self.defocus_angle {
+            builder = builder.defocus_angle(defocus_angle);
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            builder = builder.focus_dist(focus_dist);
+        }
+
+        builder
+    }
+}
+
+/// Another scene file merged into the including one, typically a shared
+/// library of textures and materials
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Include {
+    /// Relative to the including file
+    pub path: PathBuf,
+    /// Prefix for every key the included file defines, which the including
+    /// file refers to as `namespace::key`, and for its object names. Keys are
+    /// shared unprefixed when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct SceneFile {
+    /// Files written before versioning was introduced are version 1
+    #[serde(default = "SceneFile::unversioned")]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    includes: Vec<Include>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    camera: Option<CameraSpec>,
     textures: Vec<(String, TextureSpec)>,
     materials: Vec<(String, MaterialSpec)>,
+    /// Shapes that are only rendered through `Instance` references
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prototypes: Vec<(String, ShapeSpec)>,
     shapes: Vec<ShapeSpec>,
 }
 
 impl From<HittableList> for SceneFile {
     fn from(value: HittableList) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<&HittableList> for SceneFile {
+    fn from(value: &HittableList) -> Self {
         let mut registry = ResourceRegistry::default();
         let mut shapes = Vec::new();
 
@@ -175,13 +1170,344 @@ impl From<HittableList> for SceneFile {
         Self {
             materials: registry.materials,
             textures: registry.textures,
+            prototypes: registry.prototypes,
             shapes,
+            ..Self::new()
         }
     }
 }
 
+impl Default for SceneFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SceneFile {
-    pub fn into_list(self) -> anyhow::Result<HittableList> {
+    /// Empty scene at the current version
+    pub fn new() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            includes: Vec::new(),
+            camera: None,
+            textures: Vec::new(),
+            materials: Vec::new(),
+            prototypes: Vec::new(),
+            shapes: Vec::new(),
+        }
+    }
+
+    fn unversioned() -> u32 {
+        1
+    }
+
+    /// Read a scene in the format given by the path's extension. The scene is
+    /// returned as written, see [`SceneFile::migrate`].
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let format = SceneFormat::from_path(path)?;
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scene file: {path:?}"))?;
+
+        Self::parse(&text, format).with_context(|| format!("Failed to load scene file: {path:?}"))
+    }
+
+    /// Read a scene and merge in everything it includes, with every file
+    /// upgraded to the latest version.
+    ///
+    /// Relative asset paths are looked up next to the file that mentions
+    /// them first and then in each of `search_paths`.
+    pub fn load_resolved(path: impl AsRef<Path>, search_paths: &[PathBuf]) -> anyhow::Result<Self> {
+        Self::load_included(path.as_ref(), search_paths, &mut Vec::new())
+    }
+
+    /// `stack` holds the files currently being included, to detect cycles
+    fn load_included(
+        path: &Path,
+        search_paths: &[PathBuf],
+        stack: &mut Vec<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read scene file: {path:?}"))?;
+        if let Some(start) = stack.iter().position(|p| *p == canonical) {
+            let cycle: Vec<_> = stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| format!("{p:?}"))
+                .collect();
+            anyhow::bail!("include cycle: {}", cycle.join(" -> "));
+        }
+
+        let mut scene = Self::load(path)?;
+        scene.migrate()?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        scene.resolve_asset_paths(base, search_paths);
+
+        stack.push(canonical);
+        let mut resolved = Self::new();
+        for include in std::mem::take(&mut scene.includes) {
+            let include_path = base.join(&include.path);
+            let mut included = Self::load_included(&include_path, search_paths, stack)?;
+            if let Some(namespace) = &include.namespace {
+                included.add_namespace(namespace);
+            }
+            resolved
+                .merge(included)
+                .with_context(|| format!("Failed to include {:?} in {path:?}", include.path))?;
+        }
+        stack.pop();
+
+        resolved
+            .merge(scene)
+            .with_context(|| format!("Failed to load scene file: {path:?}"))?;
+
+        Ok(resolved)
+    }
+
+    fn asset_paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = Vec::new();
+        for (_, texture) in &mut self.textures {
+            if let TextureSpec::Image { path } = texture {
+                paths.push(path);
+            }
+        }
+
+        let prototypes = self.prototypes.iter_mut().map(|(_, spec)| spec);
+        for shape in prototypes.chain(self.shapes.iter_mut()) {
+            shape.collect_asset_paths(&mut paths);
+        }
+
+        paths
+    }
+
+    /// Point relative asset paths at `base`, or at the first search path that
+    /// has the file when `base` doesn't
+    fn resolve_asset_paths(&mut self, base: &Path, search_paths: &[PathBuf]) {
+        for path in self.asset_paths_mut() {
+            if path.is_absolute() {
+                continue;
+            }
+
+            let candidates = std::iter::once(base).chain(search_paths.iter().map(PathBuf::as_path));
+            *path = candidates
+                .map(|dir| dir.join(&*path))
+                .find(|candidate| candidate.exists())
+                .unwrap_or_else(|| base.join(&*path));
+        }
+    }
+
+    /// Rewrite asset paths, taken relative to the working directory, to be
+    /// relative to `dir` instead. Used when writing a scene to `dir`.
+    pub fn relativize_asset_paths(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        // The parent of a bare file name is empty, meaning the working directory
+        let dir = match dir.as_ref() {
+            dir if dir.as_os_str().is_empty() => std::env::current_dir()?,
+            dir => std::path::absolute(dir)?,
+        };
+        for path in self.asset_paths_mut() {
+            let absolute = std::path::absolute(&*path)?;
+            if let Some(relative) = pathdiff::diff_paths(&absolute, &dir) {
+                *path = relative;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prefix every key defined in the scene, every reference to them and
+    /// every object name with `namespace::`
+    fn add_namespace(&mut self, namespace: &str) {
+        let prefix = |key: &mut String| *key = format!("{namespace}::{key}");
+
+        for (name, spec) in &mut self.textures {
+            prefix(name);
+            spec.texture_keys_mut().into_iter().for_each(prefix);
+        }
+        for (name, spec) in &mut self.materials {
+            prefix(name);
+            spec.texture_mut().into_iter().for_each(prefix);
+        }
+        for (name, _) in &mut self.prototypes {
+            prefix(name);
+        }
+        self.visit_shapes_mut(|shape| {
+            shape.material_mut().into_iter().for_each(prefix);
+            match shape {
+                ShapeSpec::Instance { prototype, .. } => prefix(prototype),
+                ShapeSpec::Named { name, .. } => prefix(name),
+                _ => {}
+            }
+        });
+    }
+
+    /// Append `other`'s resources and shapes. A key may only be defined twice
+    /// with the same spec, as when two files include the same library.
+    fn merge(&mut self, other: Self) -> anyhow::Result<()> {
+        fn merge_named<T: PartialEq>(
+            kind: &str,
+            into: &mut Vec<(String, T)>,
+            from: Vec<(String, T)>,
+        ) -> anyhow::Result<()> {
+            for (name, spec) in from {
+                match into.iter().find(|(existing, _)| *existing == name) {
+                    Some((_, existing)) if *existing == spec => {}
+                    Some(_) => anyhow::bail!("{kind} '{name}' is defined more than once"),
+                    None => into.push((name, spec)),
+                }
+            }
+            Ok(())
+        }
+
+        merge_named("texture", &mut self.textures, other.textures)?;
+        merge_named("material", &mut self.materials, other.materials)?;
+        merge_named("prototype", &mut self.prototypes, other.prototypes)?;
+        self.shapes.extend(other.shapes);
+        // The including file is merged last, so its camera wins
+        if other.camera.is_some() {
+            self.camera = other.camera;
+        }
+
+        Ok(())
+    }
+
+    /// Write the scene in the format given by the path's extension
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let text = self.serialize(SceneFormat::from_path(path)?)?;
+
+        std::fs::write(path, text).with_context(|| format!("Failed to write scene file: {path:?}"))
+    }
+
+    pub fn parse(text: &str, format: SceneFormat) -> anyhow::Result<Self> {
+        let scene = match format {
+            SceneFormat::Json => serde_json::from_str(text)?,
+            SceneFormat::Toml => toml::from_str(text)?,
+            SceneFormat::Yaml => serde_norway::from_str(text)?,
+            SceneFormat::Ron => ron::from_str(text)?,
+        };
+
+        Ok(scene)
+    }
+
+    pub fn serialize(&self, format: SceneFormat) -> anyhow::Result<String> {
+        let text = match format {
+            SceneFormat::Json => serde_json::to_string_pretty(self)?,
+            SceneFormat::Toml => toml::to_string_pretty(self)?,
+            SceneFormat::Yaml => serde_norway::to_string(self)?,
+            SceneFormat::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?
+            }
+        };
+
+        Ok(text)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Camera settings the scene was set up with, if it has any
+    pub fn camera(&self) -> Option<&CameraSpec> {
+        self.camera.as_ref()
+    }
+
+    pub fn set_camera(&mut self, camera: Option<CameraSpec>) {
+        self.camera = camera;
+    }
+
+    pub fn textures(&self) -> &[(String, TextureSpec)] {
+        &self.textures
+    }
+
+    pub fn materials(&self) -> &[(String, MaterialSpec)] {
+        &self.materials
+    }
+
+    pub fn prototypes(&self) -> &[(String, ShapeSpec)] {
+        &self.prototypes
+    }
+
+    pub fn shapes(&self) -> &[ShapeSpec] {
+        &self.shapes
+    }
+
+    /// Textures must be added after the textures they are built from
+    pub fn add_texture(&mut self, name: impl Into<String>, spec: TextureSpec) {
+        self.textures.push((name.into(), spec));
+    }
+
+    pub fn add_material(&mut self, name: impl Into<String>, spec: MaterialSpec) {
+        self.materials.push((name.into(), spec));
+    }
+
+    pub fn add_prototype(&mut self, name: impl Into<String>, spec: ShapeSpec) {
+        self.prototypes.push((name.into(), spec));
+    }
+
+    pub fn add_shape(&mut self, spec: ShapeSpec) {
+        self.shapes.push(spec);
+    }
+
+    /// Call `f` on every shape, including prototypes and nested shapes
+    pub fn visit_shapes(&self, mut f: impl FnMut(&ShapeSpec)) {
+        let prototypes = self.prototypes.iter().map(|(_, spec)| spec);
+        for shape in prototypes.chain(self.shapes.iter()) {
+            shape.visit(&mut f);
+        }
+    }
+
+    /// Materials that no shape is made of
+    pub fn unused_materials(&self) -> Vec<&str> {
+        let mut used = HashSet::new();
+        self.visit_shapes(|shape| {
+            used.extend(shape.material().map(str::to_owned));
+        });
+
+        self.materials
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| !used.contains(*name))
+            .collect()
+    }
+
+    /// Upgrade the scene to [`SCENE_VERSION`]. Returns whether anything had
+    /// to be migrated.
+    pub fn migrate(&mut self) -> anyhow::Result<bool> {
+        anyhow::ensure!(
+            self.version >= 1 && self.version <= SCENE_VERSION,
+            "unsupported scene version {}, this build reads versions 1 to {SCENE_VERSION}",
+            self.version
+        );
+
+        let outdated = self.version < SCENE_VERSION;
+        while self.version < SCENE_VERSION {
+            MIGRATIONS[self.version as usize - 1](self);
+            self.version += 1;
+        }
+
+        Ok(outdated)
+    }
+
+    fn visit_shapes_mut(&mut self, mut f: impl FnMut(&mut ShapeSpec)) {
+        let prototypes = self.prototypes.iter_mut().map(|(_, spec)| spec);
+        for shape in prototypes.chain(self.shapes.iter_mut()) {
+            shape.visit_mut(&mut f);
+        }
+    }
+
+    /// Builds the scene, migrating it from older versions first. Includes
+    /// must already be merged in, see [`SceneFile::load_resolved`].
+    pub fn into_list(mut self) -> anyhow::Result<HittableList> {
+        if let Some(include) = self.includes.first() {
+            anyhow::bail!(
+                "scene includes {:?} which has not been resolved, load it with its includes first",
+                include.path
+            );
+        }
+        self.migrate()?;
+
         let mut textures: HashMap<String, Arc<DynTexture>> = HashMap::new();
         for (name, spec) in self.textures {
             let texture = spec.build(&name, &textures)?;
@@ -190,13 +1516,22 @@ impl SceneFile {
 
         let mut materials: HashMap<String, Arc<DynMaterial>> = HashMap::new();
         for (name, spec) in self.materials {
-            let material = spec.build(&name, &textures);
+            let material = spec.build(&name, &textures)?;
             materials.insert(name, material);
         }
 
+        let mut resources = SceneResources {
+            materials,
+            prototypes: HashMap::new(),
+        };
+        for (name, spec) in self.prototypes {
+            let prototype = spec.build(&resources)?;
+            resources.prototypes.insert(name, prototype);
+        }
+
         let mut world = HittableList::default();
         for shape_spec in self.shapes {
-            let hittable = shape_spec.build(&materials);
+            let hittable = shape_spec.build(&resources)?;
             world.add(hittable);
         }
 
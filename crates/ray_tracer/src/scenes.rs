@@ -0,0 +1,500 @@
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use anyhow::Context;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    color::Color,
+    hittable::{HittableList, plane::Plane, quad::Quad, sphere::Sphere},
+    material::{Dielectric, Lambertian, Metal},
+    texture::{CheckerTexture, ImageTexture, NoiseTexture},
+    vec::{Point3, Vec3},
+};
+
+/// Description of a generator parameter for listings
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Current value, formatted so `set_param` accepts it back
+    pub value: String,
+}
+
+/// Built-in procedural scene. Generators are plain structs whose fields are
+/// their parameters, and randomness comes from a seeded generator so the same
+/// parameters always produce the same scene.
+pub trait SceneGenerator {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    fn params(&self) -> Vec<ParamInfo>;
+
+    /// Set a parameter from its textual value, as given on a command line
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()>;
+
+    fn generate(&self) -> anyhow::Result<HittableList>;
+}
+
+/// Every built-in generator with default parameters
+pub fn generators() -> Vec<Box<dyn SceneGenerator>> {
+    vec![
+        Box::new(BookCover::default()),
+        Box::new(CheckeredSpheres::default()),
+        Box::new(Earth::default()),
+        Box::new(PerlinSpheres::default()),
+        Box::new(Quads::default()),
+    ]
+}
+
+pub fn generator(name: &str) -> Option<Box<dyn SceneGenerator>> {
+    generators().into_iter().find(|g| g.name() == name)
+}
+
+fn parse<T>(name: &str, value: &str) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("invalid value for '{name}': '{value}'"))
+}
+
+fn unknown_param(generator: &str, name: &str) -> anyhow::Error {
+    anyhow::format_err!("scene '{generator}' has no parameter '{name}'")
+}
+
+/// Final scene of Ray Tracing in One Weekend, a field of small random spheres
+/// around three large ones
+pub struct BookCover {
+    /// Small spheres are placed on a grid from `-grid` to `grid` on x and z
+    pub grid: i32,
+    /// Share of small spheres that are diffuse
+    pub diffuse_ratio: f64,
+    /// Share of small spheres that are metal, the rest are glass
+    pub metal_ratio: f64,
+    /// Let diffuse spheres bounce upward during the exposure
+    pub motion: bool,
+    pub seed: u64,
+}
+
+impl Default for BookCover {
+    fn default() -> Self {
+        Self {
+            grid: 11,
+            diffuse_ratio: 0.8,
+            metal_ratio: 0.15,
+            motion: true,
+            seed: 0,
+        }
+    }
+}
+
+impl SceneGenerator for BookCover {
+    fn name(&self) -> &'static str {
+        "cover"
+    }
+
+    fn description(&self) -> &'static str {
+        "random spheres around three large ones, from the first book's cover"
+    }
+
+    fn params(&self) -> Vec<ParamInfo> {
+        vec![
+            ParamInfo {
+                name: "grid",
+                description: "half the width of the grid of small spheres",
+                value: self.grid.to_string(),
+            },
+            ParamInfo {
+                name: "diffuse_ratio",
+                description: "share of small spheres that are diffuse",
+                value: self.diffuse_ratio.to_string(),
+            },
+            ParamInfo {
+                name: "metal_ratio",
+                description: "share of small spheres that are metal, the rest are glass",
+                value: self.metal_ratio.to_string(),
+            },
+            ParamInfo {
+                name: "motion",
+                description: "let diffuse spheres bounce during the exposure",
+                value: self.motion.to_string(),
+            },
+            ParamInfo {
+                name: "seed",
+                description: "random seed",
+                value: self.seed.to_string(),
+            },
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "grid" => self.grid = parse(name, value)?,
+            "diffuse_ratio" => self.diffuse_ratio = parse(name, value)?,
+            "metal_ratio" => self.metal_ratio = parse(name, value)?,
+            "motion" => self.motion = parse(name, value)?,
+            "seed" => self.seed = parse(name, value)?,
+            _ => return Err(unknown_param(self.name(), name)),
+        }
+
+        Ok(())
+    }
+
+    fn generate(&self) -> anyhow::Result<HittableList> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut world = HittableList::default();
+
+        let checker = Arc::new(CheckerTexture::from_color(
+            "checker",
+            0.32,
+            Color::new(0.2, 0.3, 0.1),
+            Color::new(0.9, 0.9, 0.9),
+        ));
+
+        let ground_material = Arc::new(Lambertian::from_texture("ground", checker));
+        world.add(Arc::new(Plane::new(
+            Point3::ZERO,
+            Vec3::new(0.0, 1.0, 0.0),
+            ground_material,
+        )));
+
+        for a in -self.grid..self.grid {
+            for b in -self.grid..self.grid {
+                let choose_mat: f64 = rng.random();
+                let center = Point3::new(
+                    a as f64 + 0.9 * rng.random::<f64>(),
+                    0.2,
+                    b as f64 + 0.9 * rng.random::<f64>(),
+                );
+
+                if (&center - Point3::new(4.0, 0.2, 0.0)).length() <= 0.9 {
+                    continue;
+                }
+
+                if choose_mat < self.diffuse_ratio {
+                    // diffuse
+                    let name = format!("diffuse_{a}_{b}");
+                    let albedo = Color::random_with(&mut rng) * Color::random_with(&mut rng);
+                    let sphere_material = Arc::new(Lambertian::new(name, albedo));
+                    // Drawn either way so toggling motion keeps the rest of the scene
+                    let bounce = rng.random_range(0.0..0.5);
+                    if self.motion {
+                        let center_2 = &center + Vec3::new(0.0, bounce, 0.0);
+                        world.add(Arc::new(Sphere::new_moving(
+                            center,
+                            center_2,
+                            0.2,
+                            sphere_material,
+                        )));
+                    } else {
+                        world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                    }
+                } else if choose_mat < self.diffuse_ratio + self.metal_ratio {
+                    // metal
+                    let name = format!("metal_{a}_{b}");
+                    let albedo = Color::random_bounded_with(&mut rng, 0.5, 1.0);
+                    let fuzz = rng.random_range(0.0..0.5);
+                    let sphere_material = Arc::new(Metal::new(name, albedo, fuzz));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    // glass
+                    let name = format!("glass_{a}_{b}");
+                    let sphere_material = Arc::new(Dielectric::new(name, 1.5));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+
+        let material_1 = Arc::new(Dielectric::new("material_1", 1.5));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            material_1,
+        )));
+
+        let material_2 = Arc::new(Lambertian::new("material_2", Color::new(0.4, 0.2, 0.1)));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(-4.0, 1.0, 0.0),
+            1.0,
+            material_2,
+        )));
+
+        let material_3 = Arc::new(Metal::new("material_3", Color::new(0.7, 0.6, 0.5), 0.0));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(4.0, 1.0, 0.0),
+            1.0,
+            material_3,
+        )));
+
+        Ok(world.into_bvh())
+    }
+}
+
+/// Two large checkered spheres touching at the origin
+pub struct CheckeredSpheres {
+    pub scale: f64,
+}
+
+impl Default for CheckeredSpheres {
+    fn default() -> Self {
+        Self { scale: 0.32 }
+    }
+}
+
+impl SceneGenerator for CheckeredSpheres {
+    fn name(&self) -> &'static str {
+        "checkered_spheres"
+    }
+
+    fn description(&self) -> &'static str {
+        "two checkered spheres touching at the origin"
+    }
+
+    fn params(&self) -> Vec<ParamInfo> {
+        vec![ParamInfo {
+            name: "scale",
+            description: "size of the checker squares",
+            value: self.scale.to_string(),
+        }]
+    }
+
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "scale" => self.scale = parse(name, value)?,
+            _ => return Err(unknown_param(self.name(), name)),
+        }
+
+        Ok(())
+    }
+
+    fn generate(&self) -> anyhow::Result<HittableList> {
+        let mut world = HittableList::default();
+
+        let checker = Arc::new(CheckerTexture::from_color(
+            "checker",
+            self.scale,
+            Color::new(0.2, 0.3, 0.1),
+            Color::new(0.9, 0.9, 0.9),
+        ));
+
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -10.0, 0.0),
+            10.0,
+            Arc::new(Lambertian::from_texture("lower", checker.clone())),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 10.0, 0.0),
+            10.0,
+            Arc::new(Lambertian::from_texture("upper", checker.clone())),
+        )));
+
+        Ok(world)
+    }
+}
+
+/// Globe textured with an image
+pub struct Earth {
+    pub texture: PathBuf,
+}
+
+impl Default for Earth {
+    fn default() -> Self {
+        Self {
+            texture: PathBuf::from("textures/earthmap.jpg"),
+        }
+    }
+}
+
+impl SceneGenerator for Earth {
+    fn name(&self) -> &'static str {
+        "earth"
+    }
+
+    fn description(&self) -> &'static str {
+        "globe textured with an image"
+    }
+
+    fn params(&self) -> Vec<ParamInfo> {
+        vec![ParamInfo {
+            name: "texture",
+            description: "image wrapped around the globe",
+            value: self.texture.display().to_string(),
+        }]
+    }
+
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "texture" => self.texture = PathBuf::from(value),
+            _ => return Err(unknown_param(self.name(), name)),
+        }
+
+        Ok(())
+    }
+
+    fn generate(&self) -> anyhow::Result<HittableList> {
+        let earth_texture = Arc::new(ImageTexture::new("earth", &self.texture)?);
+        let earth_surface = Arc::new(Lambertian::from_texture("earth_surface", earth_texture));
+        let globe = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, earth_surface));
+
+        let mut world = HittableList::default();
+        world.add(globe);
+
+        Ok(world)
+    }
+}
+
+/// Perlin noise textured sphere on a noise textured ground
+pub struct PerlinSpheres {
+    pub scale: f64,
+    pub seed: u64,
+}
+
+impl Default for PerlinSpheres {
+    fn default() -> Self {
+        Self {
+            scale: 4.0,
+            seed: 0,
+        }
+    }
+}
+
+impl SceneGenerator for PerlinSpheres {
+    fn name(&self) -> &'static str {
+        "perlin_spheres"
+    }
+
+    fn description(&self) -> &'static str {
+        "perlin noise textured sphere and ground"
+    }
+
+    fn params(&self) -> Vec<ParamInfo> {
+        vec![
+            ParamInfo {
+                name: "scale",
+                description: "frequency of the noise",
+                value: self.scale.to_string(),
+            },
+            ParamInfo {
+                name: "seed",
+                description: "picks the noise pattern",
+                value: self.seed.to_string(),
+            },
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "scale" => self.scale = parse(name, value)?,
+            "seed" => self.seed = parse(name, value)?,
+            _ => return Err(unknown_param(self.name(), name)),
+        }
+
+        Ok(())
+    }
+
+    fn generate(&self) -> anyhow::Result<HittableList> {
+        let pertext = Arc::new(NoiseTexture::new("perlin", self.scale, self.seed));
+        let pertext_mat = Arc::new(Lambertian::from_texture("perlin", pertext));
+        let mut world = HittableList::default();
+
+        world.add(Arc::new(Plane::new(
+            Point3::ZERO,
+            Vec3::new(0.0, 1.0, 0.0),
+            pertext_mat.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            2.0,
+            pertext_mat.clone(),
+        )));
+
+        Ok(world)
+    }
+}
+
+/// Five quads forming an open box, with an image on the back wall
+pub struct Quads {
+    pub texture: PathBuf,
+}
+
+impl Default for Quads {
+    fn default() -> Self {
+        Self {
+            texture: PathBuf::from("textures/earthmap.jpg"),
+        }
+    }
+}
+
+impl SceneGenerator for Quads {
+    fn name(&self) -> &'static str {
+        "quads"
+    }
+
+    fn description(&self) -> &'static str {
+        "five quads forming an open box with an image on the back"
+    }
+
+    fn params(&self) -> Vec<ParamInfo> {
+        vec![ParamInfo {
+            name: "texture",
+            description: "image on the back quad",
+            value: self.texture.display().to_string(),
+        }]
+    }
+
+    fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "texture" => self.texture = PathBuf::from(value),
+            _ => return Err(unknown_param(self.name(), name)),
+        }
+
+        Ok(())
+    }
+
+    fn generate(&self) -> anyhow::Result<HittableList> {
+        let mut world = HittableList::default();
+
+        let back_texture = Arc::new(ImageTexture::new("back", &self.texture)?);
+        let back_surface = Arc::new(Lambertian::from_texture("back_surface", back_texture));
+
+        let left_red = Arc::new(Lambertian::new("left_red", Color::new(1.0, 0.2, 0.2)));
+        let right_blue = Arc::new(Lambertian::new("right_blue", Color::new(0.2, 0.2, 1.0)));
+        let upper_orange = Arc::new(Lambertian::new("upper_orange", Color::new(1.0, 0.5, 0.0)));
+        let lower_teal = Arc::new(Lambertian::new("lower_teal", Color::new(0.2, 0.8, 0.8)));
+
+        world.add(Arc::new(Quad::new(
+            Point3::new(-3.0, -2.0, 5.0),
+            Vec3::new(0.0, 0.0, -4.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            left_red,
+        )));
+        world.add(Arc::new(Quad::new(
+            Point3::new(-2.0, -2.0, 0.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            back_surface,
+        )));
+        world.add(Arc::new(Quad::new(
+            Point3::new(3.0, -2.0, 1.0),
+            Vec3::new(0.0, 0.0, 4.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            right_blue,
+        )));
+        world.add(Arc::new(Quad::new(
+            Point3::new(-2.0, 3.0, 1.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 4.0),
+            upper_orange,
+        )));
+        world.add(Arc::new(Quad::new(
+            Point3::new(-2.0, -3.0, 5.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -4.0),
+            lower_teal,
+        )));
+
+        Ok(world)
+    }
+}
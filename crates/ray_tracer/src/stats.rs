@@ -0,0 +1,116 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use crate::{
+    aabb::AABB,
+    hittable::{Hittable, HittableList},
+    scene_loader::{MaterialSpec, SceneFile, ShapeSpec, TextureSpec},
+};
+
+/// Summary of what a built scene contains, see [`HittableList::stats`]
+#[derive(Default, Clone)]
+pub struct SceneStats {
+    /// Shapes of each kind, counting groups, wrappers and prototypes once
+    pub shapes: BTreeMap<&'static str, usize>,
+    pub materials: BTreeMap<&'static str, usize>,
+    pub textures: BTreeMap<&'static str, usize>,
+    pub bounds: AABB,
+    pub bvh_nodes: usize,
+    /// BVH nodes on the longest path from a root to a leaf
+    pub bvh_depth: usize,
+    /// Size of the decoded image textures
+    pub texture_bytes: u64,
+    /// Image textures whose size couldn't be read and are missing from
+    /// `texture_bytes`, with the reason
+    pub unreadable_images: Vec<(PathBuf, String)>,
+    /// Names of materials that are defined identically, counting textures
+    /// defined identically as the same texture
+    pub duplicate_materials: Vec<Vec<String>>,
+}
+
+impl SceneStats {
+    fn add_shape(&mut self, shape: &ShapeSpec, bvh_depth: usize) {
+        *self.shapes.entry(shape.kind()).or_default() += 1;
+
+        let bvh_depth = match shape {
+            ShapeSpec::BVH { .. } => {
+                self.bvh_nodes += 1;
+                self.bvh_depth = self.bvh_depth.max(bvh_depth + 1);
+                bvh_depth + 1
+            }
+            _ => bvh_depth,
+        };
+
+        for child in shape.children() {
+            self.add_shape(child, bvh_depth);
+        }
+    }
+}
+
+impl HittableList {
+    /// Count what the scene is made of. Only materials and textures that are
+    /// used are seen here, see [`SceneFile::unused_materials`] for the rest.
+    pub fn stats(&self) -> SceneStats {
+        let scene = SceneFile::from(self);
+        let mut stats = SceneStats {
+            bounds: self.bounding_box().clone(),
+            ..Default::default()
+        };
+
+        let prototypes = scene.prototypes().iter().map(|(_, spec)| spec);
+        for shape in prototypes.chain(scene.shapes()) {
+            stats.add_shape(shape, 0);
+        }
+
+        // Key of the first texture with the same definition as each texture,
+        // once the textures it is built from are replaced the same way
+        let mut canonical: HashMap<&str, String> = HashMap::new();
+        let mut distinct: Vec<(&str, TextureSpec)> = Vec::new();
+        for (name, texture) in scene.textures() {
+            *stats.textures.entry(texture.kind()).or_default() += 1;
+
+            if let TextureSpec::Image { path } = texture {
+                // Images are stored as 8 bit RGB, the header is enough to size them
+                match image::image_dimensions(path) {
+                    Ok((width, height)) => stats.texture_bytes += width as u64 * height as u64 * 3,
+                    Err(e) => stats.unreadable_images.push((path.clone(), e.to_string())),
+                }
+            }
+
+            let mut texture = texture.clone();
+            for key in texture.texture_keys_mut() {
+                *key = canonical[key.as_str()].clone();
+            }
+            match distinct.iter().find(|(_, other)| *other == texture) {
+                Some((first, _)) => canonical.insert(name, first.to_string()),
+                None => {
+                    distinct.push((name, texture));
+                    canonical.insert(name, name.clone())
+                }
+            };
+        }
+
+        let mut groups: Vec<(MaterialSpec, Vec<String>)> = Vec::new();
+        for (name, material) in scene.materials() {
+            *stats.materials.entry(material.kind()).or_default() += 1;
+
+            let mut material = material.clone();
+            if let Some(key) = material.texture_mut() {
+                *key = canonical[key.as_str()].clone();
+            }
+            match groups.iter_mut().find(|(other, _)| *other == material) {
+                Some((_, names)) => names.push(name.clone()),
+                None => groups.push((material, vec![name.clone()])),
+            }
+        }
+        stats.duplicate_materials = groups
+            .into_iter()
+            .map(|(_, names)| names)
+            .filter(|names| names.len() > 1)
+            .collect();
+
+        stats
+    }
+}
@@ -64,9 +64,14 @@ pub struct CheckerTexture {
 }
 
 impl CheckerTexture {
-    pub fn new(scale: f64, even: Arc<DynTexture>, odd: Arc<DynTexture>) -> Self {
+    pub fn new(
+        name: impl Into<String>,
+        scale: f64,
+        even: Arc<DynTexture>,
+        odd: Arc<DynTexture>,
+    ) -> Self {
         Self {
-            name: format!("checker_{}_{}", even.name(), odd.name()),
+            name: name.into(),
             inv_scale: 1.0 / scale,
             even,
             odd,
@@ -126,13 +131,15 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
-    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    pub fn new(name: impl Into<String>, path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let path = path.as_ref().to_owned();
-        let name = format!("image_{path:?}");
-
         let image = RtwImage::new(&path)?;
 
-        Ok(Self { path, name, image })
+        Ok(Self {
+            name: name.into(),
+            path,
+            image,
+        })
     }
 }
 
@@ -166,15 +173,20 @@ impl Texture for ImageTexture {
 }
 
 pub struct NoiseTexture {
+    name: String,
     noise: Perlin,
     scale: f64,
+    seed: u64,
 }
 
 impl NoiseTexture {
-    pub fn new(scale: f64) -> Self {
+    /// The noise is the same for every texture with the same `seed`
+    pub fn new(name: impl Into<String>, scale: f64, seed: u64) -> Self {
         Self {
+            name: name.into(),
+            noise: Perlin::from_seed(seed),
             scale,
-            noise: Perlin::default(),
+            seed,
         }
     }
 }
@@ -186,10 +198,13 @@ impl Texture for NoiseTexture {
     }
 
     fn to_spec(&self, _registry: &mut ResourceRegistry) -> TextureSpec {
-        TextureSpec::Perlin { scale: self.scale }
+        TextureSpec::Perlin {
+            scale: self.scale,
+            seed: self.seed,
+        }
     }
 
     fn name(&self) -> &str {
-        "perlin_default"
+        &self.name
     }
 }
@@ -60,12 +60,19 @@ impl Vec3 {
     }
 
     pub fn random() -> Self {
-        let mut rng = rand::rng();
+        Self::random_with(&mut rand::rng())
+    }
+
+    /// Like [`Vec3::random`] but drawing from `rng`, for reproducible results
+    pub fn random_with(rng: &mut impl Rng) -> Self {
         Self::new(rng.random(), rng.random(), rng.random())
     }
 
     pub fn random_bounded(min: f64, max: f64) -> Self {
-        let mut rng = rand::rng();
+        Self::random_bounded_with(&mut rand::rng(), min, max)
+    }
+
+    pub fn random_bounded_with(rng: &mut impl Rng, min: f64, max: f64) -> Self {
         Self::new(
             rng.random_range(min..max),
             rng.random_range(min..max),
@@ -0,0 +1,80 @@
+//! Shapes must stay inside their bounding boxes, which should not be much
+//! bigger than the shapes themselves.
+
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::{Hittable, quad::Quad},
+    material::Lambertian,
+    scene_loader::{SceneFile, SceneFormat, TransformSpec},
+    vec::{Point3, Vec3},
+};
+
+#[test]
+fn triangle_fits_its_corners() {
+    let mat = Arc::new(Lambertian::new("matte", Color::new(0.5, 0.5, 0.5)));
+    let triangle = Quad::triangle(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(-1.0, 2.0, 1.0),
+        mat,
+    );
+
+    // The fourth corner of the parallelogram, at (0, 2, 2), is left out. Flat
+    // sides of the boxes built along the way get a little padding.
+    let bbox = triangle.bounding_box();
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+    for (axis, (min, max)) in [
+        (&bbox.x, (-1.0, 1.0)),
+        (&bbox.y, (0.0, 2.0)),
+        (&bbox.z, (0.0, 1.0)),
+    ] {
+        assert!(
+            close(axis.min, min) && close(axis.max, max),
+            "{} to {}",
+            axis.min,
+            axis.max
+        );
+    }
+}
+
+/// Moving shapes stay inside at every point in time, not just at the times
+/// the motion was sampled at to build the box
+#[test]
+fn rotation_stays_in_bounds() {
+    let from =
+        r#"{ "translate": [0, 0, 0], "rotate": { "Euler": [0, 0, 0] }, "scale": [1, 1, 1] }"#;
+    let to =
+        r#"{ "translate": [0, 1, 0], "rotate": { "Euler": [0, 179, 0] }, "scale": [2, 1, 2] }"#;
+    let scene = SceneFile::parse(
+        &format!(
+            r#"{{
+                "version": 2,
+                "textures": [["grey", {{ "SolidColor": {{ "albedo": [0.5, 0.5, 0.5] }} }}]],
+                "materials": [["matte", {{ "Lambertian": {{ "texture": "grey" }} }}]],
+                "shapes": [{{ "Motion": {{
+                    "shape": {{ "Box": {{ "a": [3, 0, 0], "b": [4, 0.1, 0.1], "material": "matte" }} }},
+                    "keyframes": [{{ "time": 0, "transform": {from} }}, {{ "time": 1, "transform": {to} }}]
+                }} }}]
+            }}"#
+        ),
+        SceneFormat::Json,
+    )
+    .unwrap();
+    let list = scene.into_list().unwrap();
+    let bbox = list.bounding_box();
+
+    let from: TransformSpec = serde_json::from_str(from).unwrap();
+    let to: TransformSpec = serde_json::from_str(to).unwrap();
+    for step in 0..=1000 {
+        let affine = from.interpolate(&to, step as f64 / 1000.0);
+        for corner in [Point3::new(3.0, 0.0, 0.0), Point3::new(4.0, 0.1, 0.1)] {
+            let p = affine.transform_point(&corner);
+            assert!(
+                bbox.x.contains(p.x()) && bbox.y.contains(p.y()) && bbox.z.contains(p.z()),
+                "{p:?} at step {step} is outside the bounds"
+            );
+        }
+    }
+}
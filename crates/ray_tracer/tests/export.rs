@@ -0,0 +1,205 @@
+//! Exported files must hold the tessellated scene in a form other tools, and
+//! our own importer, read back the same.
+
+use std::path::{Path, PathBuf};
+
+use ray_tracer::{
+    export::{self, Tessellation},
+    import::gltf,
+    scene_loader::{MaterialSpec, SceneFile, SceneFormat, ShapeSpec, TextureSpec},
+    vec::Vec3,
+};
+
+/// The earth texture, reached through a detour that exports must not keep
+fn earthmap() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../scenes/../textures/earthmap.jpg")
+}
+
+/// A textured quad with texture coordinates, a bare metal triangle and a
+/// glass sphere
+fn tessellation() -> Tessellation {
+    let scene = format!(
+        r#"{{
+            "version": 2,
+            "textures": [["earth", {{ "Image": {{ "path": {} }} }}]],
+            "materials": [
+                ["paint", {{ "Lambertian": {{ "texture": "earth" }} }}],
+                ["chrome", {{ "Metal": {{ "albedo": [0.9, 0.8, 0.7], "fuzz": 0.1 }} }}],
+                ["glass", {{ "Dielectric": {{ "refraction_index": 1.5 }} }}]
+            ],
+            "shapes": [
+                {{ "Mesh": {{
+                    "positions": [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0]],
+                    "indices": [[0, 1, 2], [0, 2, 3]],
+                    "uvs": [[0, 0], [1, 0], [1, 1], [0, 1]],
+                    "material": "paint"
+                }} }},
+                {{ "Mesh": {{
+                    "positions": [[0, 0, 1], [1, 0, 1], [0, 1, 1]],
+                    "indices": [[0, 1, 2]],
+                    "material": "chrome"
+                }} }},
+                {{ "Sphere": {{ "center": [0, 0, -2], "radius": 1, "material": "glass" }} }}
+            ]
+        }}"#,
+        serde_json::to_string(&earthmap()).unwrap()
+    );
+    let scene = SceneFile::parse(&scene, SceneFormat::Json).unwrap();
+    export::tessellate(&scene, 8).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("export_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn triangles(tessellation: &Tessellation) -> usize {
+    tessellation
+        .parts
+        .iter()
+        .map(|part| part.mesh.indices.len())
+        .sum()
+}
+
+/// Texture paths are relative to the exported file and lead to the image
+fn assert_leads_to_earthmap(dir: &Path, texture: &str) {
+    assert!(!texture.contains("scenes"), "{texture}");
+    assert_eq!(
+        dir.join(texture).canonicalize().unwrap(),
+        earthmap().canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn obj() {
+    let tessellation = tessellation();
+    let dir = temp_dir("obj");
+    tessellation.write(dir.join("scene.obj")).unwrap();
+    let obj = std::fs::read_to_string(dir.join("scene.obj")).unwrap();
+    let mtl = std::fs::read_to_string(dir.join("scene.mtl")).unwrap();
+
+    let faces: Vec<&str> = obj.lines().filter(|l| l.starts_with("f ")).collect();
+    assert_eq!(faces.len(), triangles(&tessellation));
+    assert_eq!(&faces[..3], ["f 1/1 2/2 3/3", "f 1/1 3/3 4/4", "f 5 6 7"]);
+
+    // Every face refers to the vertices of its own object, counting from 1
+    // across the file separately for positions, uvs and normals
+    let mut counts = [0; 3];
+    let mut starts = [0; 3];
+    for line in obj.lines() {
+        let kind = ["v ", "vt ", "vn "]
+            .iter()
+            .position(|k| line.starts_with(k));
+        if let Some(kind) = kind {
+            counts[kind] += 1;
+        } else if line.starts_with("o ") {
+            starts = counts;
+        } else if let Some(face) = line.strip_prefix("f ") {
+            for corner in face.split(' ') {
+                for (kind, index) in corner.split('/').enumerate() {
+                    if index.is_empty() {
+                        continue;
+                    }
+                    let index: usize = index.parse().unwrap();
+                    assert!(
+                        starts[kind] < index && index <= counts[kind],
+                        "{line} refers outside its object"
+                    );
+                }
+            }
+        }
+    }
+
+    let materials: Vec<&str> = mtl
+        .lines()
+        .filter_map(|l| l.strip_prefix("newmtl "))
+        .collect();
+    assert_eq!(materials, ["paint", "chrome", "glass"]);
+    let texture = mtl.lines().find_map(|l| l.strip_prefix("map_Kd ")).unwrap();
+    assert_leads_to_earthmap(&dir, texture);
+    assert!(mtl.contains("Pm 1\n"));
+    assert!(mtl.contains("Ni 1.5\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gltf_texture_paths() {
+    let dir = temp_dir("gltf");
+    tessellation().write(dir.join("scene.gltf")).unwrap();
+    let text = std::fs::read_to_string(dir.join("scene.gltf")).unwrap();
+
+    let root: serde_json::Value = serde_json::from_str(&text).unwrap();
+    let uri = root["images"][0]["uri"].as_str().unwrap();
+    assert_leads_to_earthmap(&dir, uri);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn glb() {
+    let tessellation = tessellation();
+    let dir = temp_dir("glb");
+    let path = dir.join("scene.glb");
+    tessellation.write(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let imported = gltf::read(&path);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let imported = imported.unwrap();
+
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    assert_eq!(&bytes[..4], b"glTF");
+    assert_eq!(u32_at(4), 2);
+    assert_eq!(u32_at(8) as usize, bytes.len());
+
+    // Chunks are padded to 4 bytes and fill the file
+    let json_length = u32_at(12) as usize;
+    assert_eq!(&bytes[16..20], b"JSON");
+    let bin = 20 + json_length;
+    let bin_length = u32_at(bin) as usize;
+    assert_eq!(&bytes[bin + 4..bin + 8], b"BIN\0");
+    assert_eq!(json_length % 4, 0);
+    assert_eq!(bin_length % 4, 0);
+    assert_eq!(bin + 8 + bin_length, bytes.len());
+
+    let mut reimported = 0;
+    for shape in imported.scene.shapes() {
+        shape.visit(&mut |shape| {
+            if let ShapeSpec::Mesh { indices, .. } = shape {
+                reimported += indices.len();
+            }
+        });
+    }
+    assert_eq!(reimported, triangles(&tessellation));
+
+    let materials = imported.scene.materials();
+    let keys: Vec<&str> = materials.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["paint", "chrome", "glass"]);
+    let MaterialSpec::Lambertian { texture } = &materials[0].1 else {
+        panic!("paint stays diffuse");
+    };
+    let (_, texture) = imported
+        .scene
+        .textures()
+        .iter()
+        .find(|(key, _)| key == texture)
+        .unwrap();
+    assert!(matches!(texture, TextureSpec::Image { .. }));
+    assert_eq!(
+        imported.assets[0].data,
+        std::fs::read(earthmap()).unwrap(),
+        "the image is carried inside the file"
+    );
+    assert_eq!(
+        materials[1].1,
+        MaterialSpec::Metal {
+            albedo: Vec3::new(0.9, 0.8, 0.7),
+            fuzz: 0.1
+        }
+    );
+    assert_eq!(
+        materials[2].1,
+        MaterialSpec::Dielectric {
+            refraction_index: 1.5
+        }
+    );
+}
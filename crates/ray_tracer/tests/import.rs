@@ -0,0 +1,673 @@
+//! Importers read sources held in memory into the expected scene specs.
+
+use base64::Engine;
+
+use ray_tracer::{
+    color::Color,
+    hittable::mesh::MeshData,
+    import::{Imported, gltf, pbrt, ply, stl},
+    material::ComplexIor,
+    scene_loader::{MaterialSpec, ShapeSpec, TextureSpec, TransformSpec},
+    vec::{Point3, Vec3},
+};
+
+fn import_pbrt(source: &str) -> Imported {
+    pbrt::parse(source, "").unwrap()
+}
+
+fn pbrt_error(source: &str) -> String {
+    match pbrt::parse(source, "") {
+        Ok(_) => panic!("expected an error importing {source:?}"),
+        Err(e) => format!("{e:#}"),
+    }
+}
+
+fn assert_near(a: &Vec3, b: &Vec3) {
+    assert!((a - b).length() < 1e-9, "{a:?} != {b:?}");
+}
+
+/// Center and radius of every sphere
+fn spheres(imported: &Imported) -> Vec<(Point3, f64)> {
+    imported
+        .scene
+        .shapes()
+        .iter()
+        .map(|shape| match shape {
+            ShapeSpec::Sphere { center, radius, .. } => (center.clone(), *radius),
+            shape => panic!("expected a sphere, found {shape:?}"),
+        })
+        .collect()
+}
+
+fn material<'a>(imported: &'a Imported, shape: &ShapeSpec) -> &'a MaterialSpec {
+    let key = shape.material().expect("shape has a material");
+    let (_, spec) = imported
+        .scene
+        .materials()
+        .iter()
+        .find(|(name, _)| name == key)
+        .expect("material is defined");
+    spec
+}
+
+#[test]
+fn pbrt_tokens() {
+    let imported = import_pbrt(
+        r#"# comment with "quotes" and [brackets]
+        Material "dielectric"
+            "float eta" [ 1.33 ]  # trailing comment
+        Shape "sphere" "float radius" 25e-2
+        Shape "sphere" "float radius" [
+            -0.5
+        ]
+        "#,
+    );
+
+    let radii: Vec<f64> = spheres(&imported).iter().map(|(_, r)| *r).collect();
+    assert_eq!(radii, [0.25, -0.5]);
+    let shape = &imported.scene.shapes()[0];
+    assert_eq!(
+        material(&imported, shape),
+        &MaterialSpec::Dielectric {
+            refraction_index: 1.33
+        }
+    );
+
+    assert!(pbrt_error("Shape \"sphere\n").contains("line 1: unterminated string"));
+    assert!(pbrt_error("Shape \"sphere\" \"float radius\" [ 1").contains("unterminated list"));
+    assert!(pbrt_error("\n\n] Shape").contains("line 3"));
+}
+
+/// Without a camera PBRT looks down +z with x to the left of the image, so
+/// the scene is mirrored in x to show the same picture
+#[test]
+fn pbrt_transforms() {
+    let imported = import_pbrt(
+        r#"
+        AttributeBegin
+            Translate 1 2 3
+            Scale 2 2 2
+            Shape "sphere"
+        AttributeEnd
+        Shape "sphere" "float radius" 0.5
+        TransformBegin
+            Transform [ 1 0 0 0  0 1 0 0  0 0 1 0  4 5 6 1 ]
+            ConcatTransform [ 3 0 0 0  0 3 0 0  0 0 3 0  0 0 1 1 ]
+            Shape "sphere"
+        TransformEnd
+        Rotate 90 0 0 1
+        Translate 1 0 0
+        Shape "sphere"
+        "#,
+    );
+
+    let spheres = spheres(&imported);
+    let expected = [
+        (Point3::new(-1.0, 2.0, 3.0), 2.0),
+        (Point3::new(0.0, 0.0, 0.0), 0.5),
+        (Point3::new(-4.0, 5.0, 7.0), 3.0),
+        (Point3::new(0.0, 1.0, 0.0), 1.0),
+    ];
+    assert_eq!(spheres.len(), expected.len());
+    for ((center, radius), (expected_center, expected_radius)) in spheres.iter().zip(&expected) {
+        assert_near(center, expected_center);
+        assert!((radius - expected_radius).abs() < 1e-9);
+    }
+
+    assert!(pbrt_error("LookAt 0 0 0  0 0 1  0 0 1").contains("parallel"));
+}
+
+#[test]
+fn pbrt_camera() {
+    let imported = import_pbrt(
+        r#"
+        LookAt 0 0 5  0 0 0  0 1 0
+        Camera "perspective" "float fov" 60
+        Film "rgb" "integer xresolution" 200 "integer yresolution" 400
+        Sampler "halton" "integer pixelsamples" 64
+        WorldBegin
+        Translate 1 0 0
+        Shape "sphere"
+        "#,
+    );
+
+    let camera = imported.scene.camera().unwrap();
+    assert_near(
+        camera.lookfrom.as_ref().unwrap(),
+        &Point3::new(0.0, 0.0, 5.0),
+    );
+    assert_near(camera.lookat.as_ref().unwrap(), &Point3::new(0.0, 0.0, 4.0));
+    assert_near(camera.vup.as_ref().unwrap(), &Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(camera.image_width, Some(200));
+    assert_eq!(camera.aspect_ratio, Some(0.5));
+    assert_eq!(camera.samples_per_pixel, Some(64));
+
+    // The field of view spans the shorter image axis, here the width
+    let vfov = 2.0 * (30f64.to_radians().tan() * 2.0).atan().to_degrees();
+    assert!((camera.vfov.unwrap() - vfov).abs() < 1e-9);
+
+    // The camera's x axis points to -x, which PBRT shows on the right
+    let (center, _) = &spheres(&imported)[0];
+    assert_near(center, &Point3::new(-1.0, 0.0, 0.0));
+}
+
+#[test]
+fn pbrt_materials() {
+    let imported = import_pbrt(
+        r#"
+        Material "diffuse" "rgb reflectance" [ 0.2 0.4 0.6 ]
+        Shape "sphere"
+        Material "conductor" "spectrum eta" "metal-Au-eta" "spectrum k" "metal-Au-k"
+            "float roughness" 0.0625
+        Shape "sphere"
+        Material "conductor" "float uroughness" 0.01 "float vroughness" 0.04
+            "bool remaproughness" false
+        Shape "sphere"
+        Material "coateddiffuse"
+        Shape "sphere"
+        Material "interface"
+        Shape "sphere"
+        "#,
+    );
+
+    let shapes = imported.scene.shapes();
+    assert_eq!(shapes.len(), 4, "interfaces have no surface");
+
+    let MaterialSpec::Lambertian { texture } = material(&imported, &shapes[0]) else {
+        panic!("diffuse should be Lambertian");
+    };
+    let (_, texture) = imported
+        .scene
+        .textures()
+        .iter()
+        .find(|(name, _)| name == texture)
+        .unwrap();
+    assert_eq!(
+        texture,
+        &TextureSpec::SolidColor {
+            albedo: Vec3::new(0.2, 0.4, 0.6)
+        }
+    );
+
+    // Remapped roughness is the square root of alpha, ours squares to alpha
+    assert_eq!(
+        material(&imported, &shapes[1]),
+        &MaterialSpec::Conductor {
+            ior: ComplexIor::Gold,
+            roughness: 0.5,
+            roughness_v: None,
+        }
+    );
+    assert_eq!(
+        material(&imported, &shapes[2]),
+        &MaterialSpec::Conductor {
+            ior: ComplexIor::Copper,
+            roughness: 0.1,
+            roughness_v: Some(0.2),
+        }
+    );
+
+    assert!(matches!(
+        material(&imported, &shapes[3]),
+        MaterialSpec::Lambertian { .. }
+    ));
+    assert!(
+        imported
+            .warnings
+            .iter()
+            .any(|w| w.contains("'coateddiffuse' approximated as diffuse"))
+    );
+
+    let rough_glass = |params: &str| {
+        import_pbrt(&format!(
+            "Material \"dielectric\" {params}\nShape \"sphere\""
+        ))
+        .warnings
+        .iter()
+        .any(|w| w.contains("rough dielectrics are rendered smooth"))
+    };
+    assert!(rough_glass("\"float vroughness\" 0.1"));
+    assert!(!rough_glass("\"float roughness\" 0"));
+}
+
+#[test]
+fn pbrt_triangle_meshes() {
+    let imported = import_pbrt(
+        r#"
+        Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+        Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 3 ]
+        Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 -1 2 ]
+        "#,
+    );
+
+    let [
+        ShapeSpec::Mesh {
+            positions, indices, ..
+        },
+    ] = imported.scene.shapes()
+    else {
+        panic!("only the valid mesh is imported");
+    };
+    assert_eq!(indices.len(), 1);
+    assert_near(&positions[1], &Point3::new(-1.0, 0.0, 0.0));
+
+    let skipped: Vec<_> = imported
+        .warnings
+        .iter()
+        .filter(|w| w.contains("trianglemesh"))
+        .collect();
+    assert_eq!(skipped.len(), 2, "{skipped:?}");
+    assert!(skipped[0].contains("out of range"));
+    assert!(skipped[1].contains("invalid index -1"));
+
+    // A flattening transform keeps the mesh, only the normals are lost
+    let flattened = import_pbrt(
+        r#"
+        Scale 1 1 0
+        Shape "trianglemesh" "point3 P" [ 0 0 1  1 0 1  0 1 1 ] "integer indices" [ 0 1 2 ]
+            "normal N" [ 0 0 1  0 0 1  0 0 1 ]
+        "#,
+    );
+    let [
+        ShapeSpec::Mesh {
+            positions, normals, ..
+        },
+    ] = flattened.scene.shapes()
+    else {
+        panic!("the flattened mesh is imported");
+    };
+    assert_near(&positions[2], &Point3::new(0.0, 1.0, 0.0));
+    assert!(normals.is_empty());
+    assert!(
+        flattened
+            .warnings
+            .iter()
+            .any(|w| w.contains("normals dropped"))
+    );
+}
+
+#[test]
+fn pbrt_include_cycle() {
+    let dir = std::env::temp_dir().join(format!("pbrt_include_cycle_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.pbrt"), "Include \"b.pbrt\"\n").unwrap();
+    std::fs::write(dir.join("b.pbrt"), "Shape \"sphere\"\nInclude \"a.pbrt\"\n").unwrap();
+
+    let result = pbrt::read(dir.join("a.pbrt"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    let Err(error) = result else {
+        panic!("a file including itself must fail");
+    };
+    assert!(format!("{error:#}").contains("include cycle"), "{error:#}");
+}
+
+/// One triangle with texture coordinates, at the start of a data URI buffer
+fn gltf_buffer() -> serde_json::Value {
+    let floats: [f32; 15] = [
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // positions
+        0.0, 0.25, 1.0, 0.25, 0.0, 1.0, // uvs
+    ];
+    let bytes: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+    serde_json::json!({
+        "byteLength": bytes.len(),
+        "uri": format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&bytes)
+        ),
+    })
+}
+
+fn texture<'a>(imported: &'a Imported, key: &str) -> &'a TextureSpec {
+    let (_, spec) = imported
+        .scene
+        .textures()
+        .iter()
+        .find(|(name, _)| name == key)
+        .expect("texture is defined");
+    spec
+}
+
+#[test]
+fn gltf_scene() {
+    let primitive = |material: usize| serde_json::json!({ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": material });
+    let document = serde_json::json!({
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_materials_transmission", "KHR_materials_ior"],
+        "buffers": [gltf_buffer()],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 24 },
+            { "buffer": 0, "byteOffset": 0, "byteLength": 1000 },
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" },
+        ],
+        "images": [{ "bufferView": 2, "mimeType": "image/png" }],
+        "textures": [{ "source": 0 }],
+        "materials": [
+            { "name": "matte", "pbrMetallicRoughness": { "baseColorFactor": [0.2, 0.4, 0.6, 1], "metallicFactor": 0 } },
+            { "name": "metal", "pbrMetallicRoughness": { "baseColorFactor": [0.9, 0.8, 0.7, 1], "roughnessFactor": 0.25 } },
+            { "name": "light", "emissiveFactor": [1, 0.5, 0] },
+            { "name": "glass", "extensions": {
+                "KHR_materials_transmission": { "transmissionFactor": 1 },
+                "KHR_materials_ior": { "ior": 1.33 },
+            } },
+            { "name": "textured", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 }, "metallicFactor": 0 } },
+        ],
+        "meshes": [
+            { "name": "parts", "primitives": (0..5).map(primitive).collect::<Vec<_>>() },
+            { "name": "shared", "primitives": [primitive(0)] },
+            { "name": "hidden", "primitives": [primitive(0)] },
+        ],
+        "nodes": [
+            { "name": "parent", "translation": [1, 2, 3], "scale": [2, 2, 2], "children": [1] },
+            { "name": "child", "translation": [1, 0, 0], "mesh": 0 },
+            { "name": "a", "translation": [0, 5, 0], "mesh": 1 },
+            { "name": "b", "translation": [0, -5, 0], "mesh": 1 },
+            { "name": "hidden", "scale": [0, 0, 0], "mesh": 2 },
+        ],
+        "scenes": [{ "nodes": [0, 2, 3, 4] }],
+        "scene": 0,
+    });
+    let imported = gltf::parse(&serde_json::to_vec(&document).unwrap(), "").unwrap();
+
+    let shapes = imported.scene.shapes();
+    assert_eq!(shapes.len(), 3, "the zero scale node is skipped");
+    assert!(
+        imported
+            .warnings
+            .iter()
+            .any(|w| w.contains("node 'hidden' skipped"))
+    );
+
+    // Parent transforms apply to their children
+    let ShapeSpec::Named { name, shape } = &shapes[0] else {
+        panic!("nodes keep their names");
+    };
+    assert_eq!(name, "child");
+    let ShapeSpec::List(parts) = shape.as_ref() else {
+        panic!("every primitive is a mesh of its own");
+    };
+    assert_eq!(parts.len(), 5);
+    let ShapeSpec::Mesh { positions, uvs, .. } = &parts[0] else {
+        panic!("primitives are meshes");
+    };
+    assert_near(&positions[0], &Point3::new(3.0, 2.0, 3.0));
+    assert_near(&positions[1], &Point3::new(5.0, 2.0, 3.0));
+    // glTF textures start at the top, ours at the bottom
+    assert_eq!(uvs, &[[0.0, 0.75], [1.0, 0.75], [0.0, 0.0]]);
+
+    // A mesh used by several nodes becomes a prototype
+    let [(key, _)] = imported.scene.prototypes() else {
+        panic!("the shared mesh is a prototype");
+    };
+    assert_eq!(key, "shared");
+    for (shape, y) in shapes[1..].iter().zip([5.0, -5.0]) {
+        let ShapeSpec::Named { shape, .. } = shape else {
+            panic!("nodes keep their names");
+        };
+        let ShapeSpec::Instance {
+            prototype,
+            transform,
+        } = shape.as_ref()
+        else {
+            panic!("shared meshes are instanced");
+        };
+        assert_eq!(prototype, "shared");
+        assert_eq!(
+            transform,
+            &TransformSpec::default().translate(Vec3::new(0.0, y, 0.0))
+        );
+    }
+
+    let materials: Vec<&MaterialSpec> =
+        parts.iter().map(|part| material(&imported, part)).collect();
+    let MaterialSpec::Lambertian { texture: matte } = materials[0] else {
+        panic!("non-metals are diffuse");
+    };
+    assert_eq!(
+        texture(&imported, matte),
+        &TextureSpec::SolidColor {
+            albedo: Vec3::new(0.2, 0.4, 0.6)
+        }
+    );
+    assert_eq!(
+        materials[1],
+        &MaterialSpec::Metal {
+            albedo: Vec3::new(0.9, 0.8, 0.7),
+            fuzz: 0.25
+        }
+    );
+    let MaterialSpec::DiffuseLight { texture: light } = materials[2] else {
+        panic!("emissive materials are lights");
+    };
+    assert_eq!(
+        texture(&imported, light),
+        &TextureSpec::SolidColor {
+            albedo: Vec3::new(1.0, 0.5, 0.0)
+        }
+    );
+    assert_eq!(
+        materials[3],
+        &MaterialSpec::Dielectric {
+            refraction_index: 1.33
+        }
+    );
+
+    // An image outside its buffer falls back to the base color
+    let MaterialSpec::Lambertian { texture: textured } = materials[4] else {
+        panic!("textured non-metals are diffuse");
+    };
+    assert!(matches!(
+        texture(&imported, textured),
+        TextureSpec::SolidColor { .. }
+    ));
+    assert!(
+        imported
+            .warnings
+            .iter()
+            .any(|w| w.contains("image 0 lies outside of its buffer"))
+    );
+}
+
+fn parse_error(result: anyhow::Result<MeshData>) -> String {
+    match result {
+        Ok(mesh) => panic!("expected an error, parsed {mesh:?}"),
+        Err(e) => format!("{e:#}"),
+    }
+}
+
+/// A colored quad, split into two triangles, followed by an element the
+/// importer skips
+fn ply_quad(format: &str) -> Vec<u8> {
+    let positions = [
+        [0.0f32, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    let colors = [[255u8, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+    let face = [0i32, 1, 2, 3];
+    let edge = [0i32, 2];
+
+    let mut bytes = format!(
+        "ply\n\
+        format {format} 1.0\n\
+        comment a quad\n\
+        element vertex 4\n\
+        property float x\nproperty float y\nproperty float z\n\
+        property uchar red\nproperty uchar green\nproperty uchar blue\n\
+        element face 1\n\
+        property list uchar int vertex_indices\n\
+        element edge 1\n\
+        property int vertex1\nproperty int vertex2\n\
+        end_header\n"
+    )
+    .into_bytes();
+
+    if format == "ascii" {
+        let mut body = String::new();
+        for (p, c) in positions.iter().zip(&colors) {
+            body += &format!("{} {} {} {} {} {}\n", p[0], p[1], p[2], c[0], c[1], c[2]);
+        }
+        body += "4 0 1 2 3\n0 2\n";
+        bytes.extend(body.as_bytes());
+        return bytes;
+    }
+
+    let big_endian = format == "binary_big_endian";
+    let f32_bytes = |v: f32| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    let i32_bytes = |v: i32| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    for (p, c) in positions.iter().zip(&colors) {
+        p.iter().for_each(|&v| bytes.extend(f32_bytes(v)));
+        bytes.extend(c);
+    }
+    bytes.push(face.len() as u8);
+    face.iter()
+        .chain(&edge)
+        .for_each(|&v| bytes.extend(i32_bytes(v)));
+    bytes
+}
+
+#[test]
+fn ply_formats() {
+    let expected = MeshData {
+        positions: vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ],
+        indices: vec![[0, 1, 2], [0, 2, 3]],
+        colors: vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0),
+        ],
+        ..Default::default()
+    };
+
+    for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+        let mesh = ply::parse(&ply_quad(format)).unwrap_or_else(|e| panic!("{format}: {e:#}"));
+        assert_eq!(mesh, expected, "{format}");
+    }
+}
+
+#[test]
+fn ply_malformed() {
+    let error = |source: &str| parse_error(ply::parse(source.as_bytes()));
+
+    assert!(error("PLY\nformat ascii 1.0\nend_header\n").contains("not a PLY file"));
+    assert!(error("ply\nformat ascii 1.0\nelement vertex 0\n").contains("missing 'end_header'"));
+    assert!(error("ply\nelement vertex 0\nend_header\n").contains("missing 'format' line"));
+    assert!(error("ply\nformat ascii 2.0\nend_header\n").contains("unsupported version '2.0'"));
+    assert!(error("ply\nformat utf8 1.0\nend_header\n").contains("unknown format 'utf8'"));
+    assert!(
+        error("ply\nformat ascii 1.0\nelement vertex 1\nproperty float128 x\nend_header\n")
+            .contains("header line 4: unknown property type 'float128'")
+    );
+    assert!(
+        error("ply\nformat ascii 1.0\nproperty float x\nend_header\n")
+            .contains("property before any element")
+    );
+
+    let vertices = "ply\nformat ascii 1.0\nelement vertex 2\n\
+        property float x\nproperty float y\nproperty float z\nend_header\n";
+    assert!(error(&format!("{vertices}0 0 0\n")).contains("unexpected end of data"));
+    assert!(error(&format!("{vertices}0 0 0\n1 zero 0\n")).contains("invalid number 'zero'"));
+
+    // A corrupt list length fails before the list is read
+    let mut quad = ply_quad("binary_little_endian");
+    let face = quad.len() - 6 * 4 - 1;
+    quad[face] = 200;
+    assert!(parse_error(ply::parse(&quad)).contains("longer than the data left"));
+    quad.truncate(face - 1);
+    assert!(parse_error(ply::parse(&quad)).contains("unexpected end of data"));
+}
+
+/// A binary STL with the given header and declared triangle count
+fn binary_stl(header: &[u8], count: u32, triangles: &[[f32; 9]]) -> Vec<u8> {
+    let mut bytes = header.to_vec();
+    bytes.resize(80, 0);
+    bytes.extend(count.to_le_bytes());
+    for triangle in triangles {
+        bytes.extend([0; 12]);
+        triangle.iter().for_each(|v| bytes.extend(v.to_le_bytes()));
+        bytes.extend([0; 2]);
+    }
+    bytes
+}
+
+#[test]
+fn stl_formats() {
+    let expected = MeshData {
+        positions: vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 0.5, 2.0),
+        ],
+        indices: vec![[0, 1, 2]],
+        ..Default::default()
+    };
+
+    let ascii = "solid triangle
+        facet normal 0 0 1
+            outer loop
+                vertex 0 0 0
+                vertex 1 0 0
+                vertex 0 0.5 2
+            endloop
+        endfacet
+    endsolid triangle
+    ";
+    assert_eq!(stl::parse(ascii.as_bytes()).unwrap(), expected);
+
+    let triangle = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.5, 2.0];
+    let binary = binary_stl(b"exported by a modeler", 1, &[triangle]);
+    assert_eq!(stl::parse(&binary).unwrap(), expected);
+
+    // Many exporters start binary headers with "solid" as well
+    let binary = binary_stl(b"solid triangle", 1, &[triangle]);
+    assert_eq!(stl::parse(&binary).unwrap(), expected);
+}
+
+#[test]
+fn stl_malformed() {
+    let error = |bytes: &[u8]| parse_error(stl::parse(bytes));
+
+    assert!(error(b"solid").contains("missing 'endsolid'"));
+    assert!(error(b"STL").contains("too short for a binary header"));
+
+    let triangle = [0.0; 9];
+    let truncated = binary_stl(b"", 2, &[triangle]);
+    assert!(
+        error(&truncated)
+            .contains("declares 2 triangles, which needs 184 bytes but the file has 134")
+    );
+
+    let facet = |vertex: &str| {
+        format!(
+            "solid\nfacet normal 0 0 1\nouter loop\n\
+            vertex 0 0 0\nvertex 1 0 0\n{vertex}\nendloop\nendfacet\nendsolid\n"
+        )
+    };
+    assert!(error(facet("vertex 0 one 0").as_bytes()).contains("line 6: invalid number 'one'"));
+    assert!(
+        error(facet("vertex 0 1").as_bytes()).contains("line 6: vertex needs three coordinates")
+    );
+    assert!(error(facet("").as_bytes()).contains("facet has 2 vertices, expected 3"));
+}
@@ -0,0 +1,299 @@
+//! Scenes must survive being built and written back out unchanged, so that
+//! `SceneFile::from(list).into_list()` reproduces the same scene.
+
+use std::path::PathBuf;
+
+use ray_tracer::{
+    scene_loader::{SceneFile, SceneFormat},
+    scenes,
+};
+
+fn earthmap() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../textures/earthmap.jpg")
+}
+
+fn parse(text: &str) -> SceneFile {
+    SceneFile::parse(text, SceneFormat::Json).unwrap()
+}
+
+/// Build the scene and write it back out
+fn rebuild(scene: SceneFile) -> SceneFile {
+    SceneFile::from(scene.into_list().unwrap())
+}
+
+/// Resources must be listed in the order shapes first use them, which is the
+/// order `to_spec` registers them in
+fn assert_round_trip(text: &str) {
+    let rebuilt = rebuild(parse(text));
+    assert_eq!(rebuilt, parse(text));
+
+    for format in [
+        SceneFormat::Json,
+        SceneFormat::Toml,
+        SceneFormat::Yaml,
+        SceneFormat::Ron,
+    ] {
+        let serialized = rebuilt.serialize(format).unwrap();
+        assert_eq!(
+            SceneFile::parse(&serialized, format).unwrap(),
+            rebuilt,
+            "{format:?}"
+        );
+    }
+}
+
+/// Scene with a single grey material and the given shapes
+fn with_matte(shapes: &str) -> String {
+    format!(
+        r#"{{
+            "version": 2,
+            "textures": [["grey", {{ "SolidColor": {{ "albedo": [0.5, 0.5, 0.5] }} }}]],
+            "materials": [["matte", {{ "Lambertian": {{ "texture": "grey" }} }}]],
+            "shapes": [{shapes}]
+        }}"#
+    )
+}
+
+#[test]
+fn spheres() {
+    assert_round_trip(&with_matte(
+        r#"
+        { "Sphere": { "center": [0, 1, 2], "radius": 0.5, "material": "matte" } },
+        { "Sphere": { "center": [0.1, 0.2, 0.3], "radius": 0.7, "material": "matte", "motion": [0.1, 0.7, 0.3] } }
+        "#,
+    ));
+}
+
+#[test]
+fn legacy_circle_becomes_sphere() {
+    let circle = with_matte(
+        r#"{ "Circle": { "radius": 0.5, "center": { "orig": [0, 1, 2], "dir": [0, 0.3, 0], "tm": 0 }, "material": "matte" } }"#,
+    );
+    let sphere = with_matte(
+        r#"{ "Sphere": { "center": [0, 1, 2], "radius": 0.5, "material": "matte", "motion": [0, 0.3, 0] } }"#,
+    );
+
+    assert_eq!(rebuild(parse(&circle)), parse(&sphere));
+}
+
+#[test]
+fn planar_shapes() {
+    assert_round_trip(&with_matte(
+        r#"
+        { "Quad": { "q": [0, 0, 0], "u": [1, 0, 0], "v": [0, 1, 0], "material": "matte" } },
+        { "Triangle": { "q": [0, 0, 1], "u": [1, 0, 0], "v": [0, 1, 0], "material": "matte" } },
+        { "Ellipse": { "center": [0, 0, 2], "u": [2, 0, 0], "v": [0, 1, 0], "material": "matte" } },
+        { "Disk": { "center": [0, 0, 3], "normal": [0.3, 2, 0.1], "radius": 0.7, "material": "matte" } },
+        { "Annulus": { "center": [0, 0, 4], "u": [1, 0, 0], "v": [0, 1, 0], "inner": 0.4, "material": "matte" } },
+        { "Box": { "a": [1, 2, 3], "b": [-1, 0, 1], "material": "matte" } },
+        { "Plane": { "point": [0, -1, 0], "normal": [0, 3, 0.5], "material": "matte" } }
+        "#,
+    ));
+}
+
+#[test]
+fn quadrics_and_torus() {
+    assert_round_trip(&with_matte(
+        r#"
+        { "Cylinder": { "center": [0, 0, 0], "radius": 1, "height": 2, "capped": true, "material": "matte" } },
+        { "Cone": { "center": [3, 0, 0], "radius": 1, "height": 2, "capped": false, "material": "matte" } },
+        { "Paraboloid": { "center": [6, 0, 0], "radius": 1, "height": 2, "capped": true, "material": "matte" } },
+        { "Hyperboloid": { "center": [9, 0, 0], "radius": 1, "end_radius": 1.5, "height": 2, "capped": false, "material": "matte" } },
+        { "Torus": { "center": [0, 3, 0], "major_radius": 1, "minor_radius": 0.25, "material": "matte" } }
+        "#,
+    ));
+}
+
+#[test]
+fn sdf() {
+    assert_round_trip(&with_matte(
+        r#"{ "Sdf": {
+            "root": { "SmoothUnion": {
+                "a": { "Sphere": { "radius": 1 } },
+                "b": { "Translate": { "offset": [1, 0, 0], "node": { "Box": { "half_extents": [0.5, 0.5, 0.5] } } } },
+                "k": 0.2
+            } },
+            "material": "matte"
+        } }"#,
+    ));
+}
+
+#[test]
+fn heightfield() {
+    let path = serde_json::to_string(&earthmap()).unwrap();
+    assert_round_trip(&with_matte(&format!(
+        r#"{{ "Heightfield": {{ "path": {path}, "origin": [-5, 0, -5], "size_x": 10, "size_z": 10, "height": 1.5, "material": "matte" }} }}"#
+    )));
+}
+
+#[test]
+fn meshes() {
+    assert_round_trip(&with_matte(
+        r#"
+        { "Mesh": { "positions": [[0, 0, 0], [1, 0, 0], [0, 1, 0], [0, 0, 1]], "indices": [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]], "material": "matte" } },
+        { "Mesh": { "positions": [[0, 0, 0], [1, 0, 0], [0, 1, 0]], "indices": [[0, 1, 2]], "normals": [[0, 0, 1], [0, 0, 1], [0, 0, 1]], "uvs": [[0, 0], [1, 0], [0, 1]], "colors": [[1, 0, 0], [0, 1, 0], [0, 0, 1]], "material": "matte" } }
+        "#,
+    ));
+}
+
+#[test]
+fn groups() {
+    assert_round_trip(&with_matte(
+        r#"
+        { "Csg": {
+            "op": "Difference",
+            "left": { "Box": { "a": [-1, -1, -1], "b": [1, 1, 1], "material": "matte" } },
+            "right": { "Sphere": { "center": [0, 0, 0], "radius": 1.2, "material": "matte" } }
+        } },
+        { "List": [
+            { "Sphere": { "center": [0, 0, 0], "radius": 1, "material": "matte" } },
+            { "BVH": {
+                "left": { "Sphere": { "center": [5, 0, 0], "radius": 1, "material": "matte" } },
+                "right": { "Sphere": { "center": [-5, 0, 0], "radius": 1, "material": "matte" } }
+            } }
+        ] },
+        { "Named": { "name": "ball", "shape": { "Sphere": { "center": [0, 4, 0], "radius": 1, "material": "matte" } } } }
+        "#,
+    ));
+}
+
+#[test]
+fn transforms_and_instances() {
+    assert_round_trip(
+        r#"{
+            "version": 2,
+            "textures": [["grey", { "SolidColor": { "albedo": [0.5, 0.5, 0.5] } }]],
+            "materials": [["matte", { "Lambertian": { "texture": "grey" } }]],
+            "prototypes": [
+                ["ring", { "Torus": { "center": [0, 0, 0], "major_radius": 1, "minor_radius": 0.2, "material": "matte" } }]
+            ],
+            "shapes": [
+                { "Instance": { "prototype": "ring", "transform": { "translate": [1, 2, 3], "rotate": { "Euler": [10, 20, 30] }, "scale": [1, 2, 1] } } },
+                { "Instance": { "prototype": "ring", "transform": { "translate": [-1, 0, 0], "rotate": { "AxisAngle": { "axis": [0, 1, 1], "degrees": 45 } }, "scale": [1, 1, 1] } } },
+                { "Transform": {
+                    "shape": { "Box": { "a": [0, 0, 0], "b": [1, 1, 1], "material": "matte" } },
+                    "transform": { "translate": [0, 3, 0], "rotate": { "Euler": [0, 45, 0] }, "scale": [2, 2, 2] }
+                } },
+                { "Transform": {
+                    "shape": { "Sphere": { "center": [0, 0, 0], "radius": 20000, "material": "matte" } },
+                    "transform": { "translate": [0, 0, 5], "rotate": { "Euler": [0, 0, 0] }, "scale": [0.00001, 0.00001, 0.00001] }
+                } },
+                { "Motion": {
+                    "shape": { "Sphere": { "center": [0, 0, 0], "radius": 1, "material": "matte" } },
+                    "keyframes": [
+                        { "time": 0, "transform": { "translate": [0, 0, 0], "rotate": { "Euler": [0, 0, 0] }, "scale": [1, 1, 1] } },
+                        { "time": 0.5, "transform": { "translate": [1, 0, 0], "rotate": { "Euler": [0, 90, 0] }, "scale": [1, 1, 1] } },
+                        { "time": 1, "transform": { "translate": [2, 1, 0], "rotate": { "Euler": [0, 180, 0] }, "scale": [1, 0.5, 1] } }
+                    ]
+                } }
+            ]
+        }"#,
+    );
+}
+
+#[test]
+fn media() {
+    let grid = std::env::temp_dir().join(format!("round_trip_grid_{}.raw", std::process::id()));
+    let densities: Vec<u8> = (0..8)
+        .flat_map(|i| (i as f32 * 0.1).to_le_bytes())
+        .collect();
+    std::fs::write(&grid, densities).unwrap();
+    let grid_path = serde_json::to_string(&grid).unwrap();
+
+    assert_round_trip(&format!(
+        r#"{{
+            "version": 2,
+            "textures": [
+                ["white", {{ "SolidColor": {{ "albedo": [1, 1, 1] }} }}],
+                ["smoke", {{ "SolidColor": {{ "albedo": [0.8, 0.8, 0.8] }} }}]
+            ],
+            "materials": [
+                ["fog", {{ "Isotropic": {{ "texture": "white" }} }}],
+                ["clouds", {{ "HenyeyGreenstein": {{ "texture": "smoke", "g": 0.6 }} }}],
+                ["voxels", {{ "Isotropic": {{ "texture": "smoke" }} }}]
+            ],
+            "shapes": [
+                {{ "ConstantMedium": {{
+                    "boundary": {{ "Sphere": {{ "center": [0, 0, 0], "radius": 1, "material": "fog" }} }},
+                    "density": 0.5,
+                    "material": "fog"
+                }} }},
+                {{ "HeterogeneousMedium": {{
+                    "boundary": {{ "Box": {{ "a": [-1, -1, -1], "b": [1, 1, 1], "material": "clouds" }} }},
+                    "density": {{ "Perlin": {{ "scale": 2, "octaves": 5, "seed": 7 }} }},
+                    "sigma_a": 0.1,
+                    "sigma_s": 0.9,
+                    "emission": [0, 0, 0],
+                    "material": "clouds"
+                }} }},
+                {{ "HeterogeneousMedium": {{
+                    "boundary": {{ "Box": {{ "a": [2, -1, -1], "b": [4, 1, 1], "material": "voxels" }} }},
+                    "density": {{ "DenseGrid": {{ "path": {grid_path}, "resolution": [2, 2, 2] }} }},
+                    "sigma_a": 0.2,
+                    "sigma_s": 0.5,
+                    "emission": [0.1, 0.0, 0.0],
+                    "material": "voxels"
+                }} }}
+            ]
+        }}"#
+    ));
+
+    std::fs::remove_file(grid).unwrap();
+}
+
+#[test]
+fn textures_and_materials() {
+    let path = serde_json::to_string(&earthmap()).unwrap();
+    assert_round_trip(&format!(
+        r#"{{
+            "version": 2,
+            "textures": [
+                ["even", {{ "SolidColor": {{ "albedo": [0.2, 0.3, 0.1] }} }}],
+                ["odd", {{ "SolidColor": {{ "albedo": [0.9, 0.9, 0.9] }} }}],
+                ["board", {{ "Checker": {{ "scale": 0.32, "even": "even", "odd": "odd" }} }}],
+                ["earth", {{ "Image": {{ "path": {path} }} }}],
+                ["marble", {{ "Perlin": {{ "scale": 4, "seed": 42 }} }}],
+                ["glow", {{ "SolidColor": {{ "albedo": [4, 4, 4] }} }}]
+            ],
+            "materials": [
+                ["ground", {{ "Lambertian": {{ "texture": "board" }} }}],
+                ["wall", {{ "Lambertian": {{ "texture": "board" }} }}],
+                ["globe", {{ "Lambertian": {{ "texture": "earth" }} }}],
+                ["stone", {{ "Lambertian": {{ "texture": "marble" }} }}],
+                ["gold", {{ "Metal": {{ "albedo": [0.8, 0.6, 0.2], "fuzz": 0.1 }} }}],
+                ["glass", {{ "Dielectric": {{ "refraction_index": 1.5 }} }}],
+                ["copper", {{ "Conductor": {{ "ior": "Copper", "roughness": 0.3 }} }}],
+                ["brushed", {{ "Conductor": {{ "ior": "Aluminium", "roughness": 0.1, "roughness_v": 0.5 }} }}],
+                ["alloy", {{ "Conductor": {{ "ior": {{ "Custom": {{ "eta": [0.2, 0.4, 1.4], "k": [3.9, 2.4, 1.6] }} }}, "roughness": 0 }} }}],
+                ["lamp", {{ "DiffuseLight": {{ "texture": "glow" }} }}]
+            ],
+            "shapes": [
+                {{ "Plane": {{ "point": [0, 0, 0], "normal": [0, 1, 0], "material": "ground" }} }},
+                {{ "Quad": {{ "q": [0, 0, -5], "u": [1, 0, 0], "v": [0, 1, 0], "material": "wall" }} }},
+                {{ "Sphere": {{ "center": [0, 1, 0], "radius": 1, "material": "globe" }} }},
+                {{ "Sphere": {{ "center": [2, 1, 0], "radius": 1, "material": "stone" }} }},
+                {{ "Sphere": {{ "center": [4, 1, 0], "radius": 1, "material": "gold" }} }},
+                {{ "Sphere": {{ "center": [6, 1, 0], "radius": 1, "material": "glass" }} }},
+                {{ "Sphere": {{ "center": [8, 1, 0], "radius": 1, "material": "copper" }} }},
+                {{ "Sphere": {{ "center": [10, 1, 0], "radius": 1, "material": "brushed" }} }},
+                {{ "Sphere": {{ "center": [12, 1, 0], "radius": 1, "material": "alloy" }} }},
+                {{ "Quad": {{ "q": [0, 5, 0], "u": [1, 0, 0], "v": [0, 0, 1], "material": "lamp" }} }}
+            ]
+        }}"#
+    ));
+}
+
+#[test]
+fn generated_scenes() {
+    for mut generator in scenes::generators() {
+        if generator.params().iter().any(|p| p.name == "texture") {
+            generator
+                .set_param("texture", earthmap().to_str().unwrap())
+                .unwrap();
+        }
+
+        let scene = SceneFile::from(generator.generate().unwrap());
+        let text = scene.serialize(SceneFormat::Json).unwrap();
+        assert_eq!(rebuild(scene), parse(&text), "{}", generator.name());
+    }
+}
@@ -0,0 +1,152 @@
+//! Mistakes in a scene file fail to build with an error naming them, never
+//! with a panic.
+
+use ray_tracer::scene_loader::{SceneFile, SceneFormat};
+
+/// Build the scene and return the error it fails with
+fn build_error(text: &str) -> String {
+    let scene = SceneFile::parse(text, SceneFormat::Json).unwrap();
+    match scene.into_list() {
+        Ok(_) => panic!("expected an error building {text}"),
+        Err(e) => format!("{e:#}"),
+    }
+}
+
+/// Scene with a single grey material and the given shapes
+fn with_matte(shapes: &str) -> String {
+    format!(
+        r#"{{
+            "version": 2,
+            "textures": [["grey", {{ "SolidColor": {{ "albedo": [0.5, 0.5, 0.5] }} }}]],
+            "materials": [["matte", {{ "Lambertian": {{ "texture": "grey" }} }}]],
+            "shapes": [{shapes}]
+        }}"#
+    )
+}
+
+#[test]
+fn unknown_keys() {
+    let error = build_error(&with_matte(
+        r#"{ "Sphere": { "center": [0, 0, 0], "radius": 1, "material": "lib::paint" } }"#,
+    ));
+    assert!(error.contains("unknown material 'lib::paint'"), "{error}");
+
+    let error = build_error(
+        r#"{
+            "version": 2,
+            "textures": [],
+            "materials": [["matte", { "Lambertian": { "texture": "grey" } }]],
+            "shapes": []
+        }"#,
+    );
+    assert!(error.contains("unknown texture 'grey'"), "{error}");
+
+    let error = build_error(
+        r#"{
+            "version": 2,
+            "textures": [
+                ["board", { "Checker": { "scale": 1, "even": "white", "odd": "black" } }],
+                ["white", { "SolidColor": { "albedo": [1, 1, 1] } }]
+            ],
+            "materials": [],
+            "shapes": []
+        }"#,
+    );
+    assert!(error.contains("unknown texture 'white'"), "{error}");
+
+    let error = build_error(&with_matte(
+        r#"{ "Instance": { "prototype": "ring", "transform": { "translate": [0, 0, 0], "rotate": { "Euler": [0, 0, 0] }, "scale": [1, 1, 1] } } }"#,
+    ));
+    assert!(error.contains("unknown prototype: 'ring'"), "{error}");
+}
+
+#[test]
+fn degenerate_shapes() {
+    for quadric in [
+        r#"{ "Cone": { "center": [0, 0, 0], "radius": 1, "height": 0, "capped": true, "material": "matte" } }"#,
+        r#"{ "Paraboloid": { "center": [0, 0, 0], "radius": 1, "height": -2, "capped": true, "material": "matte" } }"#,
+        r#"{ "Hyperboloid": { "center": [0, 0, 0], "radius": 0, "end_radius": 1, "height": 1, "capped": false, "material": "matte" } }"#,
+    ] {
+        let error = build_error(&with_matte(quadric));
+        assert!(
+            error.contains("quadric radius and height must be positive"),
+            "{error}"
+        );
+    }
+
+    let error = build_error(&with_matte(
+        r#"{ "Transform": {
+            "shape": { "Sphere": { "center": [0, 0, 0], "radius": 1, "material": "matte" } },
+            "transform": { "translate": [0, 0, 0], "rotate": { "Euler": [0, 0, 0] }, "scale": [1, 1, 1e-14] }
+        } }"#,
+    ));
+    assert!(error.contains("scale must be non-zero"), "{error}");
+}
+
+#[test]
+fn media() {
+    let constant = |density: &str| {
+        build_error(&with_matte(&format!(
+            r#"{{ "ConstantMedium": {{
+                "boundary": {{ "Sphere": {{ "center": [0, 0, 0], "radius": 1, "material": "matte" }} }},
+                "density": {density},
+                "material": "matte"
+            }} }}"#
+        )))
+    };
+    assert!(constant("0").contains("medium density must be positive, got 0"));
+    assert!(constant("-0.5").contains("medium density must be positive, got -0.5"));
+
+    let error = build_error(&with_matte(
+        r#"{ "HeterogeneousMedium": {
+            "boundary": { "Box": { "a": [-1, -1, -1], "b": [1, 1, 1], "material": "matte" } },
+            "density": { "Perlin": { "scale": 2, "octaves": 5, "seed": 7 } },
+            "sigma_a": -0.1,
+            "sigma_s": 0.9,
+            "emission": [0, 0, 0],
+            "material": "matte"
+        } }"#,
+    ));
+    assert!(error.contains("coefficients can't be negative"), "{error}");
+
+    let grid = std::env::temp_dir().join(format!("scene_errors_grid_{}.txt", std::process::id()));
+    std::fs::write(&grid, "2 2 2\n0 0 0 0.5\n1 1 1 -2\n").unwrap();
+    let error = build_error(&with_matte(&format!(
+        r#"{{ "HeterogeneousMedium": {{
+            "boundary": {{ "Box": {{ "a": [-1, -1, -1], "b": [1, 1, 1], "material": "matte" }} }},
+            "density": {{ "SparseGrid": {{ "path": {} }} }},
+            "sigma_a": 0.1,
+            "sigma_s": 0.9,
+            "emission": [0, 0, 0],
+            "material": "matte"
+        }} }}"#,
+        serde_json::to_string(&grid).unwrap()
+    )));
+    std::fs::remove_file(grid).unwrap();
+    assert!(error.contains("invalid density -2"), "{error}");
+}
+
+#[test]
+fn sdf_parameters() {
+    let sdf = |root: &str| {
+        build_error(&with_matte(&format!(
+            r#"{{ "Sdf": {{ "root": {root}, "material": "matte" }} }}"#
+        )))
+    };
+
+    let error = sdf(r#"{ "Scale": { "factor": 0, "node": { "Sphere": { "radius": 1 } } } }"#);
+    assert!(
+        error.contains("scale factor must be non-zero, got 0"),
+        "{error}"
+    );
+
+    // Nested below other nodes too
+    let error = sdf(r#"{ "Union": {
+            "a": { "Sphere": { "radius": 1 } },
+            "b": { "Mandelbulb": { "power": 1, "iterations": 8 } }
+        } }"#);
+    assert!(
+        error.contains("mandelbulb needs a power above 1"),
+        "{error}"
+    );
+}
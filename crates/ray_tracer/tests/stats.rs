@@ -0,0 +1,130 @@
+//! What `HittableList::stats` and `SceneFile::unused_materials` report about
+//! a scene.
+
+use std::path::PathBuf;
+
+use ray_tracer::scene_loader::{SceneFile, SceneFormat};
+
+fn earthmap() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../textures/earthmap.jpg")
+}
+
+fn parse(text: &str) -> SceneFile {
+    SceneFile::parse(text, SceneFormat::Json).unwrap()
+}
+
+fn sphere(x: f64, material: &str) -> String {
+    format!(r#"{{ "Sphere": {{ "center": [{x}, 0, 0], "radius": 1, "material": "{material}" }} }}"#)
+}
+
+#[test]
+fn counts_shapes_and_bvh_depth() {
+    let leaf = |x: f64| sphere(x, "matte");
+    let scene = parse(&format!(
+        r#"{{
+            "version": 2,
+            "textures": [["grey", {{ "SolidColor": {{ "albedo": [0.5, 0.5, 0.5] }} }}]],
+            "materials": [["matte", {{ "Lambertian": {{ "texture": "grey" }} }}]],
+            "shapes": [
+                {{ "BVH": {{
+                    "left": {{ "BVH": {{ "left": {}, "right": {} }} }},
+                    "right": {}
+                }} }},
+                {{ "Quad": {{ "q": [0, 0, 0], "u": [1, 0, 0], "v": [0, 1, 0], "material": "matte" }} }}
+            ]
+        }}"#,
+        leaf(0.0),
+        leaf(2.0),
+        leaf(4.0),
+    ));
+
+    let stats = scene.into_list().unwrap().stats();
+    assert_eq!(stats.shapes["Sphere"], 3);
+    assert_eq!(stats.shapes["Quad"], 1);
+    assert_eq!(stats.bvh_nodes, 2);
+    assert_eq!(stats.bvh_depth, 2);
+    assert_eq!(stats.materials["Lambertian"], 1);
+    assert_eq!(stats.textures["SolidColor"], 1);
+    assert_eq!(stats.bounds.x.min, -1.0);
+    assert_eq!(stats.bounds.x.max, 5.0);
+}
+
+#[test]
+fn finds_duplicate_and_unused_materials() {
+    // The checkers differ only in the names of identical textures, so the
+    // materials built from them are the same
+    let scene = parse(&format!(
+        r#"{{
+            "version": 2,
+            "textures": [
+                ["black", {{ "SolidColor": {{ "albedo": [0, 0, 0] }} }}],
+                ["white", {{ "SolidColor": {{ "albedo": [1, 1, 1] }} }}],
+                ["dark", {{ "SolidColor": {{ "albedo": [0, 0, 0] }} }}],
+                ["board", {{ "Checker": {{ "scale": 1, "even": "black", "odd": "white" }} }}],
+                ["tiles", {{ "Checker": {{ "scale": 1, "even": "dark", "odd": "white" }} }}],
+                ["stripes", {{ "Checker": {{ "scale": 2, "even": "dark", "odd": "white" }} }}]
+            ],
+            "materials": [
+                ["floor", {{ "Lambertian": {{ "texture": "board" }} }}],
+                ["wall", {{ "Lambertian": {{ "texture": "tiles" }} }}],
+                ["ceiling", {{ "Lambertian": {{ "texture": "stripes" }} }}],
+                ["gold", {{ "Metal": {{ "albedo": [0.8, 0.6, 0.2], "fuzz": 0.1 }} }}],
+                ["brass", {{ "Metal": {{ "albedo": [0.8, 0.6, 0.2], "fuzz": 0.1 }} }}],
+                ["spare", {{ "Metal": {{ "albedo": [0.1, 0.1, 0.1], "fuzz": 0 }} }}]
+            ],
+            "shapes": [{}, {}, {}, {}, {}]
+        }}"#,
+        sphere(0.0, "floor"),
+        sphere(2.0, "wall"),
+        sphere(4.0, "ceiling"),
+        sphere(6.0, "gold"),
+        sphere(8.0, "brass"),
+    ));
+    assert_eq!(scene.unused_materials(), ["spare"]);
+
+    let stats = scene.into_list().unwrap().stats();
+    let mut duplicates = stats.duplicate_materials;
+    for group in &mut duplicates {
+        group.sort();
+    }
+    duplicates.sort();
+    assert_eq!(
+        duplicates,
+        [
+            vec!["brass".to_owned(), "gold".to_owned()],
+            vec!["floor".to_owned(), "wall".to_owned()],
+        ]
+    );
+    assert_eq!(stats.materials["Lambertian"], 3);
+    assert_eq!(stats.materials["Metal"], 2);
+}
+
+#[test]
+fn sizes_image_textures_and_reports_unreadable_ones() {
+    let image = std::env::temp_dir().join(format!("stats-{}.jpg", std::process::id()));
+    std::fs::copy(earthmap(), &image).unwrap();
+
+    let path = serde_json::to_string(&image).unwrap();
+    let scene = parse(&format!(
+        r#"{{
+            "version": 2,
+            "textures": [["earth", {{ "Image": {{ "path": {path} }} }}]],
+            "materials": [["globe", {{ "Lambertian": {{ "texture": "earth" }} }}]],
+            "shapes": [{}]
+        }}"#,
+        sphere(0.0, "globe")
+    ));
+    let world = scene.into_list().unwrap();
+
+    let (width, height) = image::image_dimensions(&image).unwrap();
+    let stats = world.stats();
+    assert_eq!(stats.texture_bytes, width as u64 * height as u64 * 3);
+    assert!(stats.unreadable_images.is_empty());
+
+    // Gone after the scene was built
+    std::fs::remove_file(&image).unwrap();
+    let stats = world.stats();
+    assert_eq!(stats.texture_bytes, 0);
+    assert_eq!(stats.unreadable_images.len(), 1);
+    assert_eq!(stats.unreadable_images[0].0, image);
+}